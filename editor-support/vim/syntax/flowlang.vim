@@ -8,7 +8,7 @@ if exists("b:current_syntax")
 endif
 
 " Keywords
-syn keyword flowlangKeyword if then else end while for from to do return break continue
+syn keyword flowlangKeyword if then else end while for from to by in do return break continue
 syn keyword flowlangDeclaration let def with
 syn keyword flowlangOperator be and or not
 syn keyword flowlangBuiltin show
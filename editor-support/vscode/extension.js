@@ -10,9 +10,9 @@ let outputChannel;
 
 // FlowLang keywords and built-in functions for code completion
 const FLOWLANG_KEYWORDS = [
-    'let', 'def', 'if', 'then', 'else', 'end', 'while', 'for', 'from', 'to', 'do',
+    'let', 'def', 'if', 'then', 'else', 'end', 'while', 'for', 'from', 'to', 'by', 'in', 'do',
     'return', 'break', 'continue', 'with', 'be', 'and', 'or', 'not', 'show',
-    'true', 'false', 'null', 'import', 'export', 'try', 'catch', 'in'
+    'true', 'false', 'null', 'import', 'export', 'try', 'catch', 'finally', 'throw', 'in'
 ];
 
 const FLOWLANG_FUNCTIONS = [
@@ -185,7 +185,9 @@ class FlowLangHoverProvider {
             'import': 'Imports a module or library',
             'export': 'Exports a function or variable',
             'try': 'Begins a try-catch block for error handling',
-            'catch': 'Catches exceptions in a try-catch block'
+            'catch': 'Catches exceptions in a try-catch block',
+            'finally': 'Runs cleanup code however a try-catch block is left',
+            'throw': 'Raises an error: `throw value`, or a bare `throw` in a catch block to rethrow'
         };
         
         // Built-in functions hover info
@@ -5,12 +5,24 @@ pub struct Program {
     pub statements: Vec<Statement>,
 }
 
+/// A statement and the source it was parsed from
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
-pub enum Statement {
+pub struct Statement {
+    pub kind: StatementKind,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum StatementKind {
     VariableDeclaration {
         name: String,
         value: Expression,
     },
+    /// `let [first, ...rest] be items` or `let {status, body} be response`
+    Destructuring {
+        pattern: Pattern,
+        value: Expression,
+    },
     FunctionDeclaration {
         name: String,
         parameters: Vec<Parameter>,
@@ -25,29 +37,63 @@ pub enum Statement {
         condition: Expression,
         body: Vec<Statement>,
     },
+    /// `for i from start to end [by step]`; both bounds are inclusive and a
+    /// negative step counts down. Without `by` the step is 1, or -1 when
+    /// start is greater than end.
     For {
         variable: String,
         start: Expression,
         end: Expression,
+        step: Option<Expression>,
         body: Vec<Statement>,
     },
+    /// `for item in items` or `for key, value in items`
+    /// With two variables, arrays and strings bind the index first and objects bind the key.
+    ForIn {
+        variable: String,
+        value_variable: Option<String>,
+        iterable: Expression,
+        body: Vec<Statement>,
+    },
+    /// Reassignment: `x = 1`, `arr[0] = 5`, `user.name = "x"`, `count += 1`
+    /// A compound assignment carries the operator applied to the current value.
+    Assignment {
+        target: Expression,
+        operator: Option<BinaryOperator>,
+        value: Expression,
+    },
     Show(Expression),
     Return(Option<Expression>),
+    Break,
+    Continue,
     Expression(Expression),
+    /// `module_path` is a built-in module name or, for file modules,
+    /// a path relative to the importing file
     Import {
         module_path: String,
         imports: ImportType,
     },
     Export(Box<Statement>),
+    /// `try ... catch err ... finally ... end`. Either `catch` or `finally`
+    /// can be left out, and `finally` runs however the other blocks are left
     TryCatch {
         try_block: Vec<Statement>,
-        catch_variable: String,
-        catch_block: Vec<Statement>,
+        catch_clause: Option<CatchClause>,
+        finally_block: Option<Vec<Statement>>,
     },
+    /// `throw value`, or a bare `throw` inside `catch` to rethrow the caught error
+    Throw(Option<Expression>),
 }
 
+/// An expression and the source it was parsed from
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
-pub enum Expression {
+pub struct Expression {
+    pub kind: ExpressionKind,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ExpressionKind {
     Literal(Literal),
     Identifier(String),
     Binary {
@@ -59,33 +105,116 @@ pub enum Expression {
         operator: UnaryOperator,
         operand: Box<Expression>,
     },
+    /// `position` is where the called name starts, for stack traces
     FunctionCall {
         name: String,
         arguments: Vec<Expression>,
+        position: Position,
     },
+    /// `optional` is set for `object?.method()`, which gives null without
+    /// calling anything when `object` is null
     MethodCall {
         object: Box<Expression>,
         method: String,
         arguments: Vec<Expression>,
+        position: Position,
+        optional: bool,
     },
     Array {
         elements: Vec<Expression>,
     },
     Object {
-        properties: Vec<(String, Expression)>,
+        properties: Vec<ObjectProperty>,
     },
+    /// `...items` in an array literal or argument list: every element of the
+    /// array in its place
+    Spread(Box<Expression>),
+    /// `object[index]`, or `object?[index]` when `optional`
     Index {
         object: Box<Expression>,
         index: Box<Expression>,
+        optional: bool,
     },
+    /// `object.property`, or `object?.property` when `optional`
     PropertyAccess {
         object: Box<Expression>,
         property: String,
+        optional: bool,
+    },
+    /// `if condition then a else b`, also written `a if condition else b`
+    Conditional {
+        condition: Box<Expression>,
+        then_branch: Box<Expression>,
+        else_branch: Box<Expression>,
     },
     Lambda {
         parameters: Vec<Parameter>,
-        body: Box<Expression>,
+        body: LambdaBody,
     },
+    /// String literal with embedded expressions: `"Hello, {name}!"`
+    InterpolatedString {
+        parts: Vec<InterpolationPart>,
+    },
+}
+
+/// An entry of an object literal
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ObjectProperty {
+    /// `key: value`
+    KeyValue(String, Expression),
+    /// `...other`: every entry of another object, overwriting earlier ones
+    Spread(Expression),
+}
+
+/// A line and column in the source, both starting at 1, and the byte offset
+/// from the start of the source
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+    pub offset: usize,
+}
+
+/// The source a node covers: `start` is its first character and `end` is just
+/// past its last one. Nodes built by the compiler rather than parsed from
+/// source have a default span with line 0.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Span {
+    pub start: Position,
+    pub end: Position,
+}
+
+impl Statement {
+    pub fn new(kind: StatementKind, span: Span) -> Self {
+        Statement { kind, span }
+    }
+}
+
+impl Expression {
+    pub fn new(kind: ExpressionKind, span: Span) -> Self {
+        Expression { kind, span }
+    }
+}
+
+/// Body of a lambda: `(x) => x * 2` or a `(x) => do ... end` block that
+/// returns with `return`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum LambdaBody {
+    Expression(Box<Expression>),
+    Block(Vec<Statement>),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum InterpolationPart {
+    Literal(String),
+    Expression(Expression),
+}
+
+/// The `catch err ...` part of a `try` statement
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CatchClause {
+    pub variable: String,
+    pub block: Vec<Statement>,
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -93,6 +222,58 @@ pub struct Parameter {
     pub name: String,
     pub default_value: Option<Expression>,
     pub is_variadic: bool,
+    /// Set when the argument is destructured, as in `def area with {width, height} do`.
+    /// `name` is then the pattern as written, which no variable can clash with.
+    pub pattern: Option<Pattern>,
+}
+
+/// The variables a destructuring `let` or parameter binds
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Pattern {
+    Identifier(String),
+    /// `[first, second = 0, ...rest]`
+    Array {
+        elements: Vec<PatternElement>,
+        rest: Option<String>,
+    },
+    /// `{status, body: text, headers: {server}}`: each key, with the pattern
+    /// its value is bound to
+    Object {
+        properties: Vec<(String, PatternElement)>,
+    },
+}
+
+/// A part of a pattern, and the value it takes when missing or null
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PatternElement {
+    pub pattern: Pattern,
+    pub default_value: Option<Expression>,
+}
+
+impl Pattern {
+    /// Every variable the pattern binds, in order
+    pub fn names(&self) -> Vec<String> {
+        let mut names = Vec::new();
+        self.collect_names(&mut names);
+        names
+    }
+    
+    fn collect_names(&self, names: &mut Vec<String>) {
+        match self {
+            Pattern::Identifier(name) => names.push(name.clone()),
+            Pattern::Array { elements, rest } => {
+                for element in elements {
+                    element.pattern.collect_names(names);
+                }
+                names.extend(rest.iter().cloned());
+            }
+            Pattern::Object { properties } => {
+                for (_, element) in properties {
+                    element.pattern.collect_names(names);
+                }
+            }
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -102,10 +283,11 @@ pub enum ImportType {
     All,
     
     /// Specific function imports: `import std { write_file, read_file }`
+    /// or `import { add, sub } from "./utils.flow"`
     /// Imports only the specified functions into the global namespace
     Specific(Vec<String>),
     
-    /// Module aliasing: `import std as stdlib`
+    /// Module aliasing: `import std as stdlib` or `import utils from "./utils.flow"`
     /// Imports the entire module under a different name
     Aliased(String, String), // (original_name, alias)
     
@@ -134,6 +316,15 @@ pub enum BinaryOperator {
     Multiply,
     Divide,
     Modulo,
+    Power,
+    IntegerDivide,
+    
+    // Bitwise
+    BitAnd,
+    BitOr,
+    BitXor,
+    ShiftLeft,
+    ShiftRight,
     
     // Comparison
     Equal,
@@ -142,16 +333,44 @@ pub enum BinaryOperator {
     GreaterEqual,
     Less,
     LessEqual,
+    In,
     
     // Logical
     And,
     Or,
+    /// `a ?? b`: `a` unless it is null, in which case `b`
+    NullCoalesce,
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum UnaryOperator {
     Not,
     Minus,
+    BitNot,
+}
+
+/// The pattern as it would be written, leaving out defaults
+impl std::fmt::Display for Pattern {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Pattern::Identifier(name) => write!(f, "{}", name),
+            Pattern::Array { elements, rest } => {
+                let mut parts: Vec<String> = elements.iter().map(|element| element.pattern.to_string()).collect();
+                parts.extend(rest.iter().map(|rest| format!("...{}", rest)));
+                write!(f, "[{}]", parts.join(", "))
+            }
+            Pattern::Object { properties } => {
+                let parts: Vec<String> = properties
+                    .iter()
+                    .map(|(key, element)| match &element.pattern {
+                        Pattern::Identifier(name) if name == key => key.clone(),
+                        pattern => format!("{}: {}", key, pattern),
+                    })
+                    .collect();
+                write!(f, "{{{}}}", parts.join(", "))
+            }
+        }
+    }
 }
 
 impl std::fmt::Display for BinaryOperator {
@@ -162,14 +381,23 @@ impl std::fmt::Display for BinaryOperator {
             BinaryOperator::Multiply => "*",
             BinaryOperator::Divide => "/",
             BinaryOperator::Modulo => "%",
+            BinaryOperator::Power => "**",
+            BinaryOperator::IntegerDivide => "//",
+            BinaryOperator::BitAnd => "&",
+            BinaryOperator::BitOr => "|",
+            BinaryOperator::BitXor => "^",
+            BinaryOperator::ShiftLeft => "<<",
+            BinaryOperator::ShiftRight => ">>",
             BinaryOperator::Equal => "==",
             BinaryOperator::NotEqual => "!=",
             BinaryOperator::Greater => ">",
             BinaryOperator::GreaterEqual => ">=",
             BinaryOperator::Less => "<",
             BinaryOperator::LessEqual => "<=",
+            BinaryOperator::In => "in",
             BinaryOperator::And => "and",
             BinaryOperator::Or => "or",
+            BinaryOperator::NullCoalesce => "??",
         };
         write!(f, "{}", symbol)
     }
@@ -180,6 +408,7 @@ impl std::fmt::Display for UnaryOperator {
         let symbol = match self {
             UnaryOperator::Not => "not",
             UnaryOperator::Minus => "-",
+            UnaryOperator::BitNot => "~",
         };
         write!(f, "{}", symbol)
     }
@@ -21,18 +21,14 @@ impl BigInt {
         }
     }
     
-    pub fn from_i64(mut value: i64) -> Self {
+    pub fn from_i64(value: i64) -> Self {
         if value == 0 {
             return BigInt::new();
         }
         
         let negative = value < 0;
-        if negative {
-            value = -value;
-        }
-        
         let mut digits = Vec::new();
-        let mut remaining = value as u64;
+        let mut remaining = value.unsigned_abs();
         
         while remaining > 0 {
             digits.push((remaining % Self::BASE) as u32);
@@ -89,10 +85,37 @@ impl BigInt {
         Some(if self.negative { -result } else { result })
     }
     
+    /// The nearest float, which loses precision beyond 2^53
+    pub fn to_f64(&self) -> f64 {
+        let magnitude = self.digits.iter().rev()
+            .fold(0.0, |total, &digit| total * Self::BASE as f64 + digit as f64);
+        if self.negative { -magnitude } else { magnitude }
+    }
+    
+    /// `self` raised to `exponent`, by repeated squaring
+    pub fn pow(&self, mut exponent: u32) -> BigInt {
+        let mut result = BigInt::from_i64(1);
+        let mut base = self.clone();
+        while exponent > 0 {
+            if exponent & 1 == 1 {
+                result = result * base.clone();
+            }
+            exponent >>= 1;
+            if exponent > 0 {
+                base = base.clone() * base;
+            }
+        }
+        result
+    }
+    
     pub fn is_zero(&self) -> bool {
         self.digits.len() == 1 && self.digits[0] == 0
     }
     
+    pub fn is_negative(&self) -> bool {
+        self.negative
+    }
+    
     fn normalize(&mut self) {
         while self.digits.len() > 1 && self.digits.last() == Some(&0) {
             self.digits.pop();
@@ -161,6 +184,37 @@ impl BigInt {
         big_int
     }
     
+    /// Quotient and remainder of dividing the magnitudes, by long division one
+    /// base-10^9 digit at a time
+    fn div_rem_magnitude(&self, divisor: &BigInt) -> (BigInt, BigInt) {
+        let divisor = BigInt { digits: divisor.digits.clone(), negative: false };
+        let mut quotient = vec![0u32; self.digits.len()];
+        let mut remainder = BigInt::new();
+        
+        for i in (0..self.digits.len()).rev() {
+            remainder.digits.insert(0, self.digits[i]);
+            remainder.normalize();
+            
+            // The largest digit d with divisor * d <= remainder
+            let (mut low, mut high) = (0u64, Self::BASE - 1);
+            while low < high {
+                let middle = (low + high).div_ceil(2);
+                let product = divisor.clone() * BigInt::from_i64(middle as i64);
+                if product.cmp_magnitude(&remainder) == Ordering::Greater {
+                    high = middle - 1;
+                } else {
+                    low = middle;
+                }
+            }
+            quotient[i] = low as u32;
+            remainder = remainder.sub_magnitude(&(divisor.clone() * BigInt::from_i64(low as i64)));
+        }
+        
+        let mut quotient = BigInt { digits: quotient, negative: false };
+        quotient.normalize();
+        (quotient, remainder)
+    }
+    
     fn cmp_magnitude(&self, other: &BigInt) -> Ordering {
         match self.digits.len().cmp(&other.digits.len()) {
             Ordering::Equal => {
@@ -235,6 +289,33 @@ impl Mul for BigInt {
     }
 }
 
+/// Division rounded toward zero, like `i64`. Panics when dividing by zero.
+impl Div for BigInt {
+    type Output = BigInt;
+    
+    fn div(self, other: BigInt) -> BigInt {
+        assert!(!other.is_zero(), "BigInt division by zero");
+        let (mut quotient, _) = self.div_rem_magnitude(&other);
+        quotient.negative = self.negative != other.negative;
+        quotient.normalize();
+        quotient
+    }
+}
+
+/// The remainder of `/`, which takes the sign of `self`, like `i64`. Panics
+/// when dividing by zero.
+impl Rem for BigInt {
+    type Output = BigInt;
+    
+    fn rem(self, other: BigInt) -> BigInt {
+        assert!(!other.is_zero(), "BigInt division by zero");
+        let (_, mut remainder) = self.div_rem_magnitude(&other);
+        remainder.negative = self.negative;
+        remainder.normalize();
+        remainder
+    }
+}
+
 impl fmt::Display for BigInt {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         if self.negative {
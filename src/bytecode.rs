@@ -3,12 +3,18 @@
 //! This module implements a stack-based virtual machine that executes
 //! FlowLang bytecode instructions.
 
-use crate::ast::{Statement, Expression, BinaryOperator, UnaryOperator, Literal, Program, Parameter};
+use crate::ast::{Statement, StatementKind, Expression, ExpressionKind, BinaryOperator, UnaryOperator, Literal, ObjectProperty, Program, Parameter, InterpolationPart, LambdaBody};
 
-use crate::error::{FlowError, Result};
+use crate::error::{FlowError, Result, StackFrame};
 use crate::value::{Value, FlowArray, FlowObject};
+use crate::destructure;
+use crate::methods;
+use crate::operators;
+use crate::stdlib::StandardLibrary;
+use std::cell::RefCell;
 use std::collections::{HashMap, HashSet};
 use std::fmt;
+use std::rc::Rc;
 use std::io::{Read, Write};
 use std::time::Instant;
 
@@ -108,7 +114,11 @@ pub enum Instruction {
     LoadLocal(usize),       // Load local variable
     StoreLocal(usize),      // Store to local variable
     LoadGlobal(String),     // Load global variable
+    LoadFunction(String),   // Load global variable being called, failing as an undefined function
     StoreGlobal(String),    // Store to global variable
+    LoadUpvalue(usize),     // Load captured variable of the running closure
+    StoreUpvalue(usize),    // Store to captured variable of the running closure
+    CloseUpvalue,           // Move the captured local on top of the stack into its upvalue, then pop it
     Duplicate,              // Duplicate top of stack
     Pop,                    // Remove top of stack
     Swap,                   // Swap top two values
@@ -120,6 +130,16 @@ pub enum Instruction {
     Divide,
     Modulo,
     Negate,                 // Unary negation
+    Power,
+    IntegerDivide,          // Division rounded down
+    
+    // Bitwise operations
+    BitAnd,
+    BitOr,
+    BitXor,
+    ShiftLeft,
+    ShiftRight,
+    BitNot,
     
     // Comparison operations
     Equal,
@@ -128,6 +148,7 @@ pub enum Instruction {
     LessEqual,
     Greater,
     GreaterEqual,
+    In,                     // Pop collection, item; push whether item is in collection
     
     // Logical operations
     And,
@@ -138,9 +159,17 @@ pub enum Instruction {
     Jump(usize),            // Unconditional jump
     JumpIfFalse(usize),     // Jump if top of stack is false
     JumpIfTrue(usize),      // Jump if top of stack is true
+    JumpIfNotNull(usize),   // Jump if top of stack is not null
     Call(usize),            // Call function with argc arguments
+    CallSpread,             // Pop function, argument array; call with the array's elements
     Return,                 // Return from function
     ReturnValue,            // Return with value from stack
+    Throw,                  // Pop a value and raise it as an error
+    
+    // Iteration
+    CheckRange,             // Check the start, end and step on top of the stack form a valid for-loop range
+    GetIterator(usize),     // Pop iterable; push its items (1 variable) or [key, value] pairs (2)
+    IterNext(usize),        // Push the next item and advance the index on top of the stack, or jump when done
     
     // Object operations
     NewArray(usize),        // Create array with size elements
@@ -149,14 +178,19 @@ pub enum Instruction {
     SetIndex,               // Pop value, index, array; array[index] = value
     GetProperty(String),    // Pop object; push object.name
     SetProperty(String),    // Pop value, object; object.name = value
+    UnpackArray(usize, usize, bool), // Pop array; push the rest if flagged, then elements count-1..0 (at least `required` present)
+    UnpackObject(Vec<(String, bool)>), // Pop object; push the value of each key, last first (flagged keys must be present)
+    ExtendArray,            // Pop array; append its elements to the array below
+    ExtendObject,           // Pop object; copy its entries into the object below
     
     // Function operations
     NewFunction(usize),     // Create function from constant pool
     NewClosure(usize),      // Create closure capturing locals
-    CallMethod(String),     // Call method on object
+    CallMethod(String, usize), // Pop argc arguments and an object; call the object's method with them
     
     // Built-in operations
     CallBuiltin(String),    // Call built-in function
+    SpreadArguments,        // Pop argument array; push its elements, then their count for CallBuiltin
     Print,                  // Print top of stack (for debugging)
     Halt,                   // Stop execution
 }
@@ -168,22 +202,57 @@ pub enum Instruction {
 /// Bytecode chunk containing instructions and constants
 #[derive(Debug, Clone)]
 pub struct Chunk {
+    /// Name of the function the chunk is the body of, for stack traces
+    pub name: String,
     pub instructions: Vec<Instruction>,
     pub constants: Vec<Value>,
     pub lines: Vec<usize>, // Line numbers for debugging
     pub header: Option<BytecodeHeader>,
+    /// Bodies of the functions and lambdas created by this chunk. A
+    /// `BytecodeFunction` constant's address indexes this list until the VM
+    /// loads the chunk and assigns each function its own chunk slot.
+    pub functions: Vec<Chunk>,
+    /// Variables a function chunk captures from the code that creates it
+    pub upvalues: Vec<UpvalueDescriptor>,
+    /// The chunk's `try` blocks, innermost first
+    pub handlers: Vec<ExceptionHandler>,
+    /// Whether the function's last parameter is `...rest`, which receives the
+    /// arguments beyond the others as an array
+    pub variadic: bool,
+}
+
+/// A `try` block in a chunk. An error raised while running the instructions
+/// in `start..end`, including inside functions they call, resumes at
+/// `handler` with the operand stack cut back to `stack_depth` slots above the
+/// frame's base and the error value pushed on top.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExceptionHandler {
+    pub start: usize,
+    pub end: usize,
+    pub handler: usize,
+    pub stack_depth: usize,
 }
 
 impl Chunk {
     pub fn new() -> Self {
         Self {
+            name: "<script>".to_string(),
             instructions: Vec::new(),
             constants: Vec::new(),
             lines: Vec::new(),
             header: None,
+            functions: Vec::new(),
+            upvalues: Vec::new(),
+            handlers: Vec::new(),
+            variadic: false,
         }
     }
     
+    /// The innermost handler covering the instruction at `address`
+    pub fn handler_for(&self, address: usize) -> Option<&ExceptionHandler> {
+        self.handlers.iter().find(|handler| (handler.start..handler.end).contains(&address))
+    }
+    
     pub fn write_instruction(&mut self, instruction: Instruction, line: usize) {
         self.instructions.push(instruction);
         self.lines.push(line);
@@ -284,18 +353,35 @@ impl Chunk {
                 writer.write_all(&[0x03])?;
                 writer.write_all(&(*index as u16).to_le_bytes())?;
             }
+            Instruction::LoadUpvalue(index) => {
+                writer.write_all(&[0x04])?;
+                writer.write_all(&(*index as u16).to_le_bytes())?;
+            }
+            Instruction::StoreUpvalue(index) => {
+                writer.write_all(&[0x05])?;
+                writer.write_all(&(*index as u16).to_le_bytes())?;
+            }
             Instruction::Add => writer.write_all(&[0x10])?,
             Instruction::Subtract => writer.write_all(&[0x11])?,
             Instruction::Multiply => writer.write_all(&[0x12])?,
             Instruction::Divide => writer.write_all(&[0x13])?,
             Instruction::Modulo => writer.write_all(&[0x14])?,
             Instruction::Negate => writer.write_all(&[0x15])?,
+            Instruction::Power => writer.write_all(&[0x16])?,
+            Instruction::IntegerDivide => writer.write_all(&[0x17])?,
+            Instruction::BitAnd => writer.write_all(&[0x18])?,
+            Instruction::BitOr => writer.write_all(&[0x19])?,
+            Instruction::BitXor => writer.write_all(&[0x1A])?,
+            Instruction::ShiftLeft => writer.write_all(&[0x1B])?,
+            Instruction::ShiftRight => writer.write_all(&[0x1C])?,
+            Instruction::BitNot => writer.write_all(&[0x1D])?,
             Instruction::Equal => writer.write_all(&[0x20])?,
             Instruction::NotEqual => writer.write_all(&[0x21])?,
             Instruction::Less => writer.write_all(&[0x22])?,
             Instruction::LessEqual => writer.write_all(&[0x23])?,
             Instruction::Greater => writer.write_all(&[0x24])?,
             Instruction::GreaterEqual => writer.write_all(&[0x25])?,
+            Instruction::In => writer.write_all(&[0x26])?,
             Instruction::And => writer.write_all(&[0x30])?,
             Instruction::Or => writer.write_all(&[0x31])?,
             Instruction::Not => writer.write_all(&[0x32])?,
@@ -311,15 +397,50 @@ impl Chunk {
                 writer.write_all(&[0x42])?;
                 writer.write_all(&(*offset as u16).to_le_bytes())?;
             }
+            Instruction::JumpIfNotNull(offset) => {
+                writer.write_all(&[0x43])?;
+                writer.write_all(&(*offset as u16).to_le_bytes())?;
+            }
             Instruction::Call(argc) => {
                 writer.write_all(&[0x50])?;
                 writer.write_all(&(*argc as u16).to_le_bytes())?;
             }
+            Instruction::CallSpread => writer.write_all(&[0x57])?,
+            Instruction::SpreadArguments => writer.write_all(&[0x58])?,
+            Instruction::ExtendArray => writer.write_all(&[0x59])?,
+            Instruction::ExtendObject => writer.write_all(&[0x5A])?,
             Instruction::Return => writer.write_all(&[0x51])?,
             Instruction::ReturnValue => writer.write_all(&[0x52])?,
+            Instruction::Throw => writer.write_all(&[0x56])?,
+            Instruction::CheckRange => writer.write_all(&[0x55])?,
+            Instruction::GetIterator(variables) => {
+                writer.write_all(&[0x53])?;
+                writer.write_all(&(*variables as u16).to_le_bytes())?;
+            }
+            Instruction::IterNext(address) => {
+                writer.write_all(&[0x54])?;
+                writer.write_all(&(*address as u16).to_le_bytes())?;
+            }
             Instruction::Pop => writer.write_all(&[0x60])?,
             Instruction::Duplicate => writer.write_all(&[0x61])?,
             Instruction::Swap => writer.write_all(&[0x62])?,
+            Instruction::CloseUpvalue => writer.write_all(&[0x63])?,
+            Instruction::UnpackArray(required, count, rest) => {
+                writer.write_all(&[0x64])?;
+                writer.write_all(&(*required as u16).to_le_bytes())?;
+                writer.write_all(&(*count as u16).to_le_bytes())?;
+                writer.write_all(&[if *rest { 1 } else { 0 }])?;
+            }
+            Instruction::UnpackObject(keys) => {
+                writer.write_all(&[0x65])?;
+                writer.write_all(&(keys.len() as u16).to_le_bytes())?;
+                for (key, required) in keys {
+                    writer.write_all(&[if *required { 1 } else { 0 }])?;
+                    let bytes = key.as_bytes();
+                    writer.write_all(&(bytes.len() as u32).to_le_bytes())?;
+                    writer.write_all(bytes)?;
+                }
+            }
             Instruction::Print => writer.write_all(&[0x70])?,
             Instruction::Halt => writer.write_all(&[0xFF])?,
             _ => {
@@ -345,7 +466,75 @@ pub struct FunctionRef {
 #[derive(Debug, Clone, PartialEq)]
 pub struct ClosureRef {
     pub function: FunctionRef,
-    pub captured: Vec<Value>,
+    pub captured: Vec<Upvalue>,
+}
+
+/// A variable captured by a closure. While the declaring function is still
+/// running it refers to the variable's stack slot; once the slot is popped the
+/// upvalue is closed and holds the value itself. Every closure that captures
+/// the same variable shares one upvalue, so updates are seen by all of them.
+#[derive(Clone)]
+pub struct Upvalue(Rc<RefCell<UpvalueState>>);
+
+enum UpvalueState {
+    Open(usize),
+    Closed(Value),
+}
+
+impl Upvalue {
+    fn open(slot: usize) -> Self {
+        Upvalue(Rc::new(RefCell::new(UpvalueState::Open(slot))))
+    }
+    
+    /// The stack slot this upvalue refers to, if it is still open
+    fn slot(&self) -> Option<usize> {
+        match &*self.0.borrow() {
+            UpvalueState::Open(slot) => Some(*slot),
+            UpvalueState::Closed(_) => None,
+        }
+    }
+    
+    fn close(&self, value: Value) {
+        *self.0.borrow_mut() = UpvalueState::Closed(value);
+    }
+    
+    fn get(&self, stack: &[Value]) -> Option<Value> {
+        match &*self.0.borrow() {
+            UpvalueState::Open(slot) => stack.get(*slot).cloned(),
+            UpvalueState::Closed(value) => Some(value.clone()),
+        }
+    }
+    
+    fn set(&self, stack: &mut [Value], value: Value) -> Option<()> {
+        match &mut *self.0.borrow_mut() {
+            UpvalueState::Open(slot) => *stack.get_mut(*slot)? = value,
+            UpvalueState::Closed(closed) => *closed = value,
+        }
+        Some(())
+    }
+}
+
+impl PartialEq for Upvalue {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl fmt::Debug for Upvalue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.slot() {
+            Some(slot) => write!(f, "Upvalue(open: {})", slot),
+            None => write!(f, "Upvalue(closed)"),
+        }
+    }
+}
+
+/// Where `NewClosure` finds a captured variable: a local slot of the function
+/// creating the closure, or one of that function's own upvalues
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct UpvalueDescriptor {
+    pub is_local: bool,
+    pub index: usize,
 }
 
 /// Call frame for function calls (matches specification)
@@ -355,6 +544,9 @@ struct Frame {
     pub locals: Vec<Value>,
     pub instruction_pointer: usize,
     pub stack_base: usize,
+    pub chunk_index: usize, // Chunk to resume in when the call returns
+    pub upvalues: Vec<Upvalue>, // Variables captured by the called closure
+    pub returns_to_native: bool, // Called by `call_value`, which resumes once it returns
 }
 
 /// Garbage Collection Statistics
@@ -380,6 +572,15 @@ pub struct VirtualMachine {
     pub allocated_objects: usize,
     pub locals: Vec<Value>,
     pub constant_pool: Vec<Value>,
+    /// Upvalues still pointing at live stack slots, so closures capturing the
+    /// same variable share one upvalue
+    open_upvalues: Vec<Upvalue>,
+    /// Stack trace of the last error to escape `run`, innermost call first
+    last_trace: Vec<StackFrame>,
+    /// Source line of the instruction the last error to escape `run` was raised at
+    last_error_line: usize,
+    /// Functions such as `str_upper`, found when a called name isn't a global
+    stdlib: StandardLibrary,
 }
 
 impl VirtualMachine {
@@ -402,19 +603,43 @@ impl VirtualMachine {
             allocated_objects: 0,
             locals: Vec::new(),
             constant_pool: Vec::new(),
+            open_upvalues: Vec::new(),
+            last_trace: Vec::new(),
+            last_error_line: 0,
+            stdlib: StandardLibrary::new(),
         };
         vm.add_builtins();
         vm
     }
     
     pub fn load_chunk(&mut self, chunk: Chunk) {
-        self.chunks.push(chunk);
-        if self.chunks.len() == 1 {
+        let index = self.register_chunk(chunk);
+        if index == 0 {
             self.current_chunk = 0;
             self.instruction_pointer = 0;
         }
     }
     
+    /// Add a chunk and, recursively, its function bodies to `chunks`,
+    /// pointing its function constants at their new chunk indexes
+    fn register_chunk(&mut self, mut chunk: Chunk) -> usize {
+        let index = self.chunks.len();
+        self.chunks.push(Chunk::new());
+        
+        let functions = std::mem::take(&mut chunk.functions);
+        let addresses: Vec<usize> = functions.into_iter()
+            .map(|function| self.register_chunk(function))
+            .collect();
+        for constant in &mut chunk.constants {
+            if let Value::BytecodeFunction { address, .. } = constant {
+                *address = addresses[*address];
+            }
+        }
+        
+        self.chunks[index] = chunk;
+        index
+    }
+    
     /// Get current chunk
     fn current_chunk(&self) -> &Chunk {
         &self.chunks[self.current_chunk]
@@ -503,14 +728,12 @@ impl VirtualMachine {
          
          match value {
              Value::Array(array) => {
-                let elements = &array.elements;
-                 for element in elements {
+                 for element in array.elements().iter() {
                      self.mark_value(element, marked);
                  }
              }
              Value::Object(object) => {
-                let properties = &object.properties;
-                 for prop_value in properties.values() {
+                 for prop_value in object.properties().values() {
                      self.mark_value(prop_value, marked);
                  }
              }
@@ -528,6 +751,93 @@ impl VirtualMachine {
      }
     
     pub fn run(&mut self) -> Result<()> {
+        loop {
+            match self.execute_instructions() {
+                Ok(()) => return Ok(()),
+                Err(error) => {
+                    let line = self.current_line();
+                    let error = error.with_line(line);
+                    let trace = self.capture_stack_trace();
+                    if !self.unwind_to_handler(&error, &trace, line) {
+                        self.last_trace = trace;
+                        self.last_error_line = line;
+                        return Err(error);
+                    }
+                }
+            }
+        }
+    }
+    
+    /// The calls that were active when the last error escaped `run`,
+    /// innermost first. Empty if the error was raised outside any function.
+    pub fn stack_trace(&self) -> &[StackFrame] {
+        &self.last_trace
+    }
+    
+    /// The source line the last error to escape `run` was raised at, when known
+    pub fn error_line(&self) -> Option<usize> {
+        Some(self.last_error_line).filter(|&line| line > 0)
+    }
+    
+    /// The source line of the instruction just run, or 0 if unknown
+    fn current_line(&self) -> usize {
+        let address = self.instruction_pointer.saturating_sub(1);
+        self.current_chunk().lines.get(address).copied().unwrap_or(0)
+    }
+    
+    /// The active calls, innermost first, with call-site lines taken from
+    /// `Chunk.lines` of each caller
+    fn capture_stack_trace(&self) -> Vec<StackFrame> {
+        let mut trace = Vec::new();
+        let mut callee = self.current_chunk;
+        for frame in self.call_stack.iter().rev() {
+            let caller = &self.chunks[frame.chunk_index];
+            // The frame's return address is just past the call
+            let line = caller.lines.get(frame.instruction_pointer.saturating_sub(1)).copied().unwrap_or(0);
+            trace.push(StackFrame {
+                function: self.chunks[callee].name.clone(),
+                line,
+                column: None,
+            });
+            callee = frame.chunk_index;
+        }
+        trace
+    }
+    
+    /// Unwind the operand stack and call frames to the nearest enclosing
+    /// `try` block and resume at its catch code with the error value pushed.
+    /// Returns false when no handler covers the failing instruction.
+    fn unwind_to_handler(&mut self, error: &FlowError, trace: &[StackFrame], line: usize) -> bool {
+        loop {
+            // The instruction pointer has already moved past the failing
+            // instruction, or past the call for a caller's frame
+            let address = self.instruction_pointer.saturating_sub(1);
+            if let Some(handler) = self.current_chunk().handler_for(address).cloned() {
+                let stack_height = self.frame_base() + handler.stack_depth;
+                self.close_upvalues(stack_height);
+                self.stack.truncate(stack_height);
+                self.stack.push(error.to_value(trace, Some(line).filter(|&line| line > 0)));
+                self.instruction_pointer = handler.handler;
+                return true;
+            }
+            
+            match self.call_stack.pop() {
+                Some(frame) => {
+                    self.close_upvalues(frame.stack_base);
+                    self.stack.truncate(frame.stack_base);
+                    self.current_chunk = frame.chunk_index;
+                    self.instruction_pointer = frame.instruction_pointer;
+                    // The error carries on from the native code that made the call
+                    if frame.returns_to_native {
+                        return false;
+                    }
+                }
+                None => return false,
+            }
+        }
+    }
+    
+    fn execute_instructions(&mut self) -> Result<()> {
         loop {
             if self.instruction_pointer >= self.current_chunk().instructions.len() {
                 break;
@@ -546,31 +856,51 @@ impl VirtualMachine {
                 }
                 
                 Instruction::LoadLocal(index) => {
-                    if let Some(frame) = self.call_stack.last() {
-                        if let Some(local) = frame.locals.get(index) {
-                            self.stack.push(local.clone());
-                        } else {
-                            return Err(FlowError::runtime_error("Invalid local index"));
-                        }
+                    // Locals live in stack slots relative to the current frame
+                    let slot = self.frame_base() + index;
+                    if let Some(local) = self.stack.get(slot) {
+                        self.stack.push(local.clone());
                     } else {
-                        return Err(FlowError::runtime_error("No active frame for local access"));
+                        return Err(FlowError::runtime_error("Invalid local index"));
                     }
                 }
                 
                 Instruction::StoreLocal(index) => {
-                    let value = self.stack.pop().ok_or_else(|| {
+                    // Assignment leaves the value on the stack
+                    let value = self.stack.last().cloned().ok_or_else(|| {
                         FlowError::runtime_error("Stack underflow")
                     })?;
                     
-                    if let Some(frame) = self.call_stack.last_mut() {
-                        if index < frame.locals.len() {
-                            frame.locals[index] = value;
-                        } else {
-                            return Err(FlowError::runtime_error("Invalid local index"));
-                        }
+                    let slot = self.frame_base() + index;
+                    if slot < self.stack.len() {
+                        self.stack[slot] = value;
                     } else {
-                        return Err(FlowError::runtime_error("No active frame for local storage"));
+                        return Err(FlowError::runtime_error("Invalid local index"));
+                    }
+                }
+                
+                Instruction::LoadUpvalue(index) => {
+                    let value = self.current_upvalue(index)?.get(&self.stack)
+                        .ok_or_else(|| FlowError::runtime_error("Invalid upvalue slot"))?;
+                    self.stack.push(value);
+                }
+                
+                Instruction::StoreUpvalue(index) => {
+                    // Like StoreLocal, assignment leaves the value on the stack
+                    let value = self.stack.last().cloned().ok_or_else(|| {
+                        FlowError::runtime_error("Stack underflow")
+                    })?;
+                    let upvalue = self.current_upvalue(index)?.clone();
+                    upvalue.set(&mut self.stack, value)
+                        .ok_or_else(|| FlowError::runtime_error("Invalid upvalue slot"))?;
+                }
+                
+                Instruction::CloseUpvalue => {
+                    if self.stack.is_empty() {
+                        return Err(FlowError::runtime_error("Stack underflow"));
                     }
+                    self.close_upvalues(self.stack.len() - 1);
+                    self.stack.pop();
                 }
                 
                 Instruction::LoadGlobal(name) => {
@@ -581,6 +911,23 @@ impl VirtualMachine {
                     }
                 }
                 
+                Instruction::LoadFunction(name) => {
+                    if let Some(value) = self.globals.get(&name) {
+                        self.stack.push(value.clone());
+                    } else if self.stdlib.has_function(&name) {
+                        // A stdlib function is called through a wrapper with no
+                        // body, as in the interpreter
+                        self.stack.push(Value::Function {
+                            name,
+                            parameters: Vec::new(),
+                            body: Vec::new(),
+                            closure: None,
+                        });
+                    } else {
+                        return Err(FlowError::undefined_function(name.clone()));
+                    }
+                }
+                
                 Instruction::StoreGlobal(name) => {
                     if let Some(value) = self.stack.pop() {
                         self.globals.insert(name, value);
@@ -631,7 +978,32 @@ impl VirtualMachine {
                     }
                     let b = self.stack.pop().unwrap();
                     let a = self.stack.pop().unwrap();
-                    let result = self.modulo_values(&a, &b)?;
+                    let result = operators::modulo(&a, &b)?;
+                    self.stack.push(result);
+                }
+                Instruction::Power
+                | Instruction::IntegerDivide
+                | Instruction::BitAnd
+                | Instruction::BitOr
+                | Instruction::BitXor
+                | Instruction::ShiftLeft
+                | Instruction::ShiftRight
+                | Instruction::In => {
+                    if self.stack.len() < 2 {
+                        return Err(FlowError::runtime_error("Stack underflow"));
+                    }
+                    let b = self.stack.pop().unwrap();
+                    let a = self.stack.pop().unwrap();
+                    let result = match instruction {
+                        Instruction::Power => operators::power(&a, &b)?,
+                        Instruction::IntegerDivide => operators::floor_divide(&a, &b)?,
+                        Instruction::BitAnd => operators::bitwise(&BinaryOperator::BitAnd, &a, &b)?,
+                        Instruction::BitOr => operators::bitwise(&BinaryOperator::BitOr, &a, &b)?,
+                        Instruction::BitXor => operators::bitwise(&BinaryOperator::BitXor, &a, &b)?,
+                        Instruction::ShiftLeft => operators::shift_left(&a, &b)?,
+                        Instruction::ShiftRight => operators::shift_right(&a, &b)?,
+                        _ => Value::Boolean(operators::contains(&a, &b)?),
+                    };
                     self.stack.push(result);
                 }
                 
@@ -641,7 +1013,7 @@ impl VirtualMachine {
                     }
                     let b = self.stack.pop().unwrap();
                     let a = self.stack.pop().unwrap();
-                    let result = Value::Boolean(self.values_equal(&a, &b));
+                    let result = Value::Boolean(a.equals(&b));
                     self.stack.push(result);
                 }
                 Instruction::NotEqual => {
@@ -650,7 +1022,7 @@ impl VirtualMachine {
                     }
                     let b = self.stack.pop().unwrap();
                     let a = self.stack.pop().unwrap();
-                    let result = Value::Boolean(!self.values_equal(&a, &b));
+                    let result = Value::Boolean(!a.equals(&b));
                     self.stack.push(result);
                 }
                 Instruction::Greater => {
@@ -710,12 +1082,21 @@ impl VirtualMachine {
                     }
                 }
                 
+                Instruction::BitNot => {
+                    if let Some(value) = self.stack.pop() {
+                        self.stack.push(operators::bit_not(&value)?);
+                    } else {
+                        return Err(FlowError::runtime_error("Stack underflow"));
+                    }
+                }
+                
                 Instruction::Jump(address) => {
                     self.instruction_pointer = address;
                 }
                 
                 Instruction::JumpIfFalse(address) => {
-                    if let Some(value) = self.stack.pop() {
+                    // The condition stays on the stack; the compiler emits the Pop
+                    if let Some(value) = self.stack.last() {
                         if !value.is_truthy() {
                             self.instruction_pointer = address;
                         }
@@ -724,6 +1105,81 @@ impl VirtualMachine {
                     }
                 }
                 
+                Instruction::JumpIfTrue(address) => {
+                    if let Some(value) = self.stack.last() {
+                        if value.is_truthy() {
+                            self.instruction_pointer = address;
+                        }
+                    } else {
+                        return Err(FlowError::runtime_error("Stack underflow"));
+                    }
+                }
+                
+                Instruction::JumpIfNotNull(address) => {
+                    match self.stack.last() {
+                        Some(Value::Null) => {}
+                        Some(_) => self.instruction_pointer = address,
+                        None => return Err(FlowError::runtime_error("Stack underflow")),
+                    }
+                }
+                
+                Instruction::CheckRange => {
+                    // Stack: [..., start, end, step]; the values stay as loop locals
+                    if self.stack.len() < 3 {
+                        return Err(FlowError::runtime_error("Stack underflow"));
+                    }
+                    let top = self.stack.len();
+                    match (&self.stack[top - 3], &self.stack[top - 2], &self.stack[top - 1]) {
+                        (Value::Integer(_), Value::Integer(_), Value::Integer(0)) => {
+                            return Err(FlowError::runtime_error("For loop step cannot be zero"));
+                        }
+                        (Value::Integer(_), Value::Integer(_), Value::Integer(_)) => {}
+                        (Value::Integer(_), Value::Integer(_), _) => {
+                            return Err(FlowError::type_error("For loop step must be an integer"));
+                        }
+                        _ => return Err(FlowError::type_error("For loop bounds must be integers")),
+                    }
+                }
+                
+                Instruction::GetIterator(variables) => {
+                    let iterable = self.stack.pop()
+                        .ok_or_else(|| FlowError::runtime_error("Stack underflow"))?;
+                    let items = if variables == 2 {
+                        iterable.iteration_entries().map(|entries| {
+                            entries.into_iter()
+                                .map(|(key, item)| Value::Array(FlowArray::from_values(vec![key, item])))
+                                .collect()
+                        })
+                    } else {
+                        iterable.iteration_items()
+                    };
+                    let elements = items.ok_or_else(|| FlowError::type_error(format!("Cannot iterate over a value of type {}", iterable.type_name())))?;
+                    self.stack.push(Value::Array(FlowArray::from_values(elements)));
+                }
+                
+                Instruction::IterNext(address) => {
+                    // Stack: [..., iterator, index]; both stay in place as loop locals
+                    if self.stack.len() < 2 {
+                        return Err(FlowError::runtime_error("Stack underflow"));
+                    }
+                    let index_slot = self.stack.len() - 1;
+                    let index = match &self.stack[index_slot] {
+                        Value::Integer(i) => *i as usize,
+                        _ => return Err(FlowError::runtime_error("Invalid iterator index")),
+                    };
+                    let next = match &self.stack[index_slot - 1] {
+                        Value::Array(items) => items.elements().get(index).cloned(),
+                        _ => return Err(FlowError::runtime_error("Invalid iterator")),
+                    };
+                    match next {
+                        Some(item) => {
+                            self.stack[index_slot] = Value::Integer(index as i64 + 1);
+                            self.stack.push(item);
+                        }
+                        None => self.instruction_pointer = address,
+                    }
+                }
+                
                 Instruction::Print => {
                     if let Some(value) = self.stack.pop() {
                         println!("{}", value);
@@ -737,6 +1193,12 @@ impl VirtualMachine {
                         return Err(FlowError::runtime_error("Stack underflow"));
                     }
                 }
+
+                Instruction::Duplicate => {
+                    let value = self.stack.last().cloned()
+                        .ok_or_else(|| FlowError::runtime_error("Stack underflow"))?;
+                    self.stack.push(value);
+                }
                 
                 Instruction::CallBuiltin(name) => {
                     if let Some(builtin) = self.builtins.get(&name).cloned() {
@@ -764,6 +1226,14 @@ impl VirtualMachine {
                     }
                 }
                 
+                Instruction::SpreadArguments => {
+                    let arguments = self.stack.pop().ok_or_else(|| FlowError::runtime_error("Stack underflow"))?;
+                    let arguments = operators::spread_elements(&arguments)?;
+                    let count = arguments.len();
+                    self.stack.extend(arguments);
+                    self.stack.push(Value::Integer(count as i64));
+                }
+                
                 Instruction::NewArray(size) => {
                     if self.stack.len() < size {
                         return Err(FlowError::runtime_error("Not enough elements for array creation"));
@@ -775,12 +1245,11 @@ impl VirtualMachine {
                     }
                     elements.reverse();
                     
-                    self.stack.push(Value::Array(FlowArray { elements }));
+                    self.stack.push(Value::Array(FlowArray::from_values(elements)));
                 }
                 
                 Instruction::NewObject => {
-                    let properties = std::collections::HashMap::new();
-                    self.stack.push(Value::Object(FlowObject { properties }));
+                    self.stack.push(Value::Object(FlowObject::new()));
                 }
                 
                 Instruction::GetIndex => {
@@ -793,13 +1262,15 @@ impl VirtualMachine {
                     
                     match (&array, &index) {
                         (Value::Array(array_ref), Value::Integer(i)) => {
-                            let elements = &array_ref.elements;
-                            if *i >= 0 && (*i as usize) < elements.len() {
-                                self.stack.push(elements[*i as usize].clone());
-                            } else {
-                                return Err(FlowError::runtime_error("Array index out of bounds"));
+                            match usize::try_from(*i).ok().and_then(|i| array_ref.get(i)) {
+                                Some(element) => self.stack.push(element),
+                                None => return Err(FlowError::IndexOutOfBounds { index: *i }),
                             }
                         }
+                        (Value::Object(object_ref), Value::String(key)) => {
+                            let value = object_ref.get(key).unwrap_or(Value::Null);
+                            self.stack.push(value);
+                        }
                         _ => return Err(FlowError::runtime_error("Invalid index operation")),
                     }
                 }
@@ -809,53 +1280,76 @@ impl VirtualMachine {
                         return Err(FlowError::runtime_error("Stack underflow for index assignment"));
                     }
                     
+                    // Arrays and objects are shared, so this updates every reference
+                    // to the container; it stays on the stack for object literals
                     let value = self.stack.pop().unwrap();
                     let index = self.stack.pop().unwrap();
-                    let mut array = self.stack.pop().unwrap();
+                    let array = self.stack.pop().unwrap();
                     
-                    match (&mut array, &index) {
+                    match (&array, &index) {
                         (Value::Array(array_ref), Value::Integer(i)) => {
-                            let elements = &mut array_ref.elements;
-                            if *i >= 0 && (*i as usize) < elements.len() {
-                                elements[*i as usize] = value;
-                                self.stack.push(array);
-                            } else {
-                                return Err(FlowError::runtime_error("Array index out of bounds"));
+                            if *i < 0 || array_ref.set(*i as usize, value).is_err() {
+                                return Err(FlowError::IndexOutOfBounds { index: *i });
                             }
+                            self.stack.push(array);
+                        }
+                        (Value::Object(object_ref), Value::String(key)) => {
+                            object_ref.set(key.clone(), value);
+                            self.stack.push(array);
                         }
                         _ => return Err(FlowError::runtime_error("Invalid index assignment")),
                     }
                 }
                 
-                Instruction::GetProperty(property) => {
-                    if let Some(object) = self.stack.pop() {
-                        match object {
-                            Value::Object(object) => {
-                let properties = &object.properties;
-                                if let Some(value) = properties.get(&property) {
-                                    self.stack.push(value.clone());
-                                } else {
-                                    self.stack.push(Value::Null);
-                                }
-                            }
-                            _ => return Err(FlowError::runtime_error("Property access on non-object")),
-                        }
-                    } else {
-                        return Err(FlowError::runtime_error("Stack underflow"));
+                Instruction::UnpackArray(required, count, has_rest) => {
+                    let value = self.stack.pop().ok_or_else(|| FlowError::runtime_error("Stack underflow"))?;
+                    let (elements, rest) = destructure::unpack_array(&value, required, count, has_rest)?;
+                    // The first element ends up on top, to be bound first
+                    self.stack.extend(rest);
+                    self.stack.extend(elements.into_iter().rev());
+                }
+                
+                Instruction::UnpackObject(keys) => {
+                    let value = self.stack.pop().ok_or_else(|| FlowError::runtime_error("Stack underflow"))?;
+                    let values = destructure::unpack_object(&value, &keys)?;
+                    self.stack.extend(values.into_iter().rev());
+                }
+                
+                Instruction::ExtendArray => {
+                    let items = self.stack.pop().ok_or_else(|| FlowError::runtime_error("Stack underflow"))?;
+                    let items = operators::spread_elements(&items)?;
+                    match self.stack.last() {
+                        Some(Value::Array(array)) => array.elements_mut().extend(items),
+                        _ => return Err(FlowError::runtime_error("Array extension on non-array")),
                     }
                 }
                 
+                Instruction::ExtendObject => {
+                    let source = self.stack.pop().ok_or_else(|| FlowError::runtime_error("Stack underflow"))?;
+                    let entries = operators::spread_entries(&source)?;
+                    match self.stack.last() {
+                        Some(Value::Object(object)) => object.properties_mut().extend(entries),
+                        _ => return Err(FlowError::runtime_error("Object extension on non-object")),
+                    }
+                }
+                
+                Instruction::GetProperty(property) => {
+                    let object = self.stack.pop()
+                        .ok_or_else(|| FlowError::runtime_error("Stack underflow"))?;
+                    self.stack.push(operators::get_property(&object, &property)?);
+                }
+                
                 Instruction::SetProperty(property) => {
                     if self.stack.len() < 2 {
                         return Err(FlowError::runtime_error("Stack underflow for property assignment"));
                     }
                     
                     let value = self.stack.pop().unwrap();
-                    let mut object = self.stack.pop().unwrap();
+                    let object = self.stack.pop().unwrap();
                     
-                    match &mut object {
+                    match &object {
                         Value::Object(object_ref) => {
-                            object_ref.properties.insert(property, value);
+                            object_ref.set(property, value);
                             self.stack.push(object);
                         }
                         _ => return Err(FlowError::runtime_error("Property assignment on non-object")),
@@ -868,24 +1362,45 @@ impl VirtualMachine {
                         name: "anonymous".to_string(),
                         parameters: Vec::new(),
                         body: Vec::new(),
+                        closure: None,
                     });
                 }
                 
-                Instruction::NewClosure(_index) => {
-                    // For now, just push a placeholder function value
-                    self.stack.push(Value::Function {
-                        name: "closure".to_string(),
-                        parameters: Vec::new(),
-                        body: Vec::new(),
-                    });
+                Instruction::NewClosure(index) => {
+                    let mut function = self.current_chunk().constants.get(index).cloned()
+                        .ok_or_else(|| FlowError::runtime_error("Invalid constant index"))?;
+                    if let Value::BytecodeFunction { address, captured, .. } = &mut function {
+                        let descriptors = self.chunks.get(*address)
+                            .map(|chunk| chunk.upvalues.clone())
+                            .ok_or_else(|| FlowError::runtime_error("Invalid function address"))?;
+                        for descriptor in descriptors {
+                            let upvalue = if descriptor.is_local {
+                                self.capture_upvalue(self.frame_base() + descriptor.index)
+                            } else {
+                                self.current_upvalue(descriptor.index)?.clone()
+                            };
+                            captured.push(upvalue);
+                        }
+                    }
+                    self.stack.push(function);
                 }
                 
-                Instruction::Call(argc) => {
+                instruction @ (Instruction::Call(_) | Instruction::CallSpread) => {
+                    let argc = match instruction {
+                        Instruction::Call(argc) => argc,
+                        _ => self.spread_call_arguments()?,
+                    };
+                    
                     // Pop function from stack
                     let function = self.stack.pop().ok_or_else(|| {
                         FlowError::runtime_error("Stack underflow: no function to call")
                     })?;
                     
+                    if let Value::BytecodeFunction { address, arity, captured, .. } = function {
+                        self.enter_function(address, arity, captured, argc, false)?;
+                        continue;
+                    }
+                    
                     // Pop arguments from stack (in reverse order)
                     let mut args = Vec::new();
                     for _ in 0..argc {
@@ -896,7 +1411,11 @@ impl VirtualMachine {
                     args.reverse(); // Arguments were pushed in reverse order
                     
                     match function {
-                        Value::Function { name: _, parameters, body } => {
+                        Value::Function { name, body, .. } if body.is_empty() && self.stdlib.has_function(&name) => {
+                            let result = self.stdlib.call_function(&name, &args)?;
+                            self.stack.push(result);
+                        }
+                        Value::Function { name: _, parameters, body, .. } => {
                             // Create new call frame
                             let mut locals = vec![Value::Null; parameters.len()];
                             
@@ -912,6 +1431,9 @@ impl VirtualMachine {
                                 locals,
                                 instruction_pointer: self.instruction_pointer,
                                 stack_base: self.stack.len(),
+                                chunk_index: self.current_chunk,
+                                upvalues: Vec::new(),
+                                returns_to_native: false,
                             };
                             
                             self.call_stack.push(frame);
@@ -930,8 +1452,8 @@ impl VirtualMachine {
                             // Execute function body
                             let mut result = Value::Null;
                             for stmt in &body {
-                                match stmt {
-                                    crate::ast::Statement::Return(Some(expr)) => {
+                                match &stmt.kind {
+                                    crate::ast::StatementKind::Return(Some(expr)) => {
                                         result = interpreter.evaluate_expression(expr)?;
                                         break;
                                     }
@@ -953,20 +1475,23 @@ impl VirtualMachine {
                     }
                 }
                 
-                Instruction::CallMethod(_method) => {
-                    // For now, just return an error as method calls are not fully implemented
-                    return Err(FlowError::runtime_error("Method calls not yet implemented"));
+                Instruction::CallMethod(method, argc) => {
+                    if self.stack.len() < argc + 1 {
+                        return Err(FlowError::runtime_error("Stack underflow: not enough arguments"));
+                    }
+                    let arguments = self.stack.split_off(self.stack.len() - argc);
+                    let object = self.stack.pop().unwrap();
+                    let result = methods::call_method(&object, &method, arguments, |callee, args| {
+                        self.call_value(callee, args)
+                    })?;
+                    self.stack.push(result);
                 }
                 
                 Instruction::Return => {
                     // Return from function with null value
-                    if let Some(frame) = self.call_stack.pop() {
-                        // Restore previous state
-                        self.instruction_pointer = frame.instruction_pointer;
-                        // Push null as return value
-                        self.stack.push(Value::Null);
-                    } else {
-                        // No call frame to return from, halt execution
+                    let returns_to_native = self.call_stack.last().is_some_and(|frame| frame.returns_to_native);
+                    if !self.return_from_frame(Value::Null) || returns_to_native {
+                        // No call frame to return from, or back to `call_value`
                         break;
                     }
                 }
@@ -977,17 +1502,20 @@ impl VirtualMachine {
                         FlowError::runtime_error("Stack underflow: no return value")
                     })?;
                     
-                    if let Some(frame) = self.call_stack.pop() {
-                        // Restore previous state
-                        self.instruction_pointer = frame.instruction_pointer;
-                        // Push return value
-                        self.stack.push(return_value);
-                    } else {
-                        // No call frame to return from, halt execution
+                    let returns_to_native = self.call_stack.last().is_some_and(|frame| frame.returns_to_native);
+                    if !self.return_from_frame(return_value) || returns_to_native {
+                        // No call frame to return from, or back to `call_value`
                         break;
                     }
                 }
                 
+                Instruction::Throw => {
+                    let value = self.stack.pop().ok_or_else(|| {
+                        FlowError::runtime_error("Stack underflow: no value to throw")
+                    })?;
+                    return Err(FlowError::thrown(value, &self.capture_stack_trace()));
+                }
+                
                 Instruction::Halt => break,
                 
                 _ => {
@@ -1002,6 +1530,132 @@ impl VirtualMachine {
         Ok(())
     }
     
+    /// Start running a bytecode function whose `argc` arguments are on top of
+    /// the stack. They become its first local slots; missing ones start as
+    /// null and take their defaults, and a variadic function's extra ones are
+    /// gathered into an array in its last slot.
+    fn enter_function(&mut self, address: usize, arity: usize, captured: Vec<Upvalue>, argc: usize, returns_to_native: bool) -> Result<()> {
+        let variadic = self.chunks.get(address).is_some_and(|chunk| chunk.variadic);
+        if !variadic && argc > arity {
+            return Err(FlowError::argument_count_error(format!(
+                "Expected at most {} arguments but got {}",
+                arity, argc
+            )));
+        }
+        if self.stack.len() < argc {
+            return Err(FlowError::runtime_error("Stack underflow: not enough arguments"));
+        }
+        
+        let stack_base = self.stack.len() - argc;
+        if variadic {
+            let fixed = arity - 1;
+            let rest = self.stack.split_off(stack_base + argc.min(fixed));
+            for _ in argc..fixed {
+                self.stack.push(Value::Null);
+            }
+            self.stack.push(Value::Array(FlowArray::from_values(rest)));
+        } else {
+            for _ in argc..arity {
+                self.stack.push(Value::Null);
+            }
+        }
+        
+        self.call_stack.push(Frame {
+            function: None,
+            locals: Vec::new(),
+            instruction_pointer: self.instruction_pointer,
+            stack_base,
+            chunk_index: self.current_chunk,
+            upvalues: captured,
+            returns_to_native,
+        });
+        self.current_chunk = address;
+        self.instruction_pointer = 0;
+        Ok(())
+    }
+    
+    /// Call a function value from native code, such as the callback of an
+    /// array's `map`, running it to completion. Errors it doesn't catch
+    /// itself are returned to the native code, leaving the caller's frame active.
+    fn call_value(&mut self, function: &Value, args: Vec<Value>) -> Result<Value> {
+        match function {
+            Value::BytecodeFunction { address, arity, captured, .. } => {
+                let argc = args.len();
+                self.stack.extend(args);
+                self.enter_function(*address, *arity, captured.clone(), argc, true)?;
+                self.run()?;
+                self.stack.pop().ok_or_else(|| FlowError::runtime_error("Stack underflow: no return value"))
+            }
+            Value::Function { name, body, .. } if body.is_empty() && self.stdlib.has_function(name) => {
+                self.stdlib.call_function(name, &args)
+            }
+            _ => Err(FlowError::runtime_error("Cannot call non-function value")),
+        }
+    }
+    
+    /// For `CallSpread`, replace the argument array below the function with
+    /// its elements, as `Call` expects them, and return how many there are
+    fn spread_call_arguments(&mut self) -> Result<usize> {
+        let function = self.stack.pop().ok_or_else(|| FlowError::runtime_error("Stack underflow: no function to call"))?;
+        let arguments = self.stack.pop().ok_or_else(|| FlowError::runtime_error("Stack underflow: no arguments"))?;
+        let arguments = operators::spread_elements(&arguments)?;
+        let argc = arguments.len();
+        self.stack.extend(arguments);
+        self.stack.push(function);
+        Ok(argc)
+    }
+    
+    /// Stack index of local slot 0 for the active frame
+    fn frame_base(&self) -> usize {
+        self.call_stack.last().map(|frame| frame.stack_base).unwrap_or(0)
+    }
+    
+    /// Upvalue `index` of the running closure
+    fn current_upvalue(&self, index: usize) -> Result<&Upvalue> {
+        self.call_stack.last()
+            .and_then(|frame| frame.upvalues.get(index))
+            .ok_or_else(|| FlowError::runtime_error("Invalid upvalue index"))
+    }
+    
+    /// The upvalue for a stack slot, reusing an open one if another closure
+    /// already captured the slot
+    fn capture_upvalue(&mut self, slot: usize) -> Upvalue {
+        if let Some(upvalue) = self.open_upvalues.iter().find(|upvalue| upvalue.slot() == Some(slot)) {
+            return upvalue.clone();
+        }
+        let upvalue = Upvalue::open(slot);
+        self.open_upvalues.push(upvalue.clone());
+        upvalue
+    }
+    
+    /// Close every open upvalue at or above `from_slot` before those slots are popped
+    fn close_upvalues(&mut self, from_slot: usize) {
+        let stack = &self.stack;
+        self.open_upvalues.retain(|upvalue| match upvalue.slot() {
+            Some(slot) if slot >= from_slot => {
+                upvalue.close(stack.get(slot).cloned().unwrap_or(Value::Null));
+                false
+            }
+            _ => true,
+        });
+    }
+    
+    /// Pop the active frame, discard its locals and resume the caller with
+    /// `value` on the stack. Returns false when there is no frame to pop.
+    fn return_from_frame(&mut self, value: Value) -> bool {
+        match self.call_stack.pop() {
+            Some(frame) => {
+                self.close_upvalues(frame.stack_base);
+                self.stack.truncate(frame.stack_base);
+                self.current_chunk = frame.chunk_index;
+                self.instruction_pointer = frame.instruction_pointer;
+                self.stack.push(value);
+                true
+            }
+            None => false,
+        }
+    }
+    
     fn binary_op<F>(&mut self, op: F) -> Result<()>
     where
         F: FnOnce(&Value, &Value) -> Result<Value>,
@@ -1026,8 +1680,8 @@ impl VirtualMachine {
             Value::Integer(i) => *i != 0,
             Value::BigInteger(bi) => !bi.is_zero(),
             Value::String(s) => !s.is_empty(),
-            Value::Array(array) => !array.elements.is_empty(),
-            Value::Object(object) => !object.properties.is_empty(),
+            Value::Array(array) => !array.elements().is_empty(),
+            Value::Object(object) => !object.properties().is_empty(),
             _ => true,
         }
     }
@@ -1163,28 +1817,28 @@ impl VirtualMachine {
         match (a, b) {
             (Value::Integer(x), Value::Integer(y)) => {
                 if *y == 0 {
-                    Err(FlowError::runtime_error("Division by zero"))
+                    Err(FlowError::DivisionByZero)
                 } else {
                     Ok(Value::Float(*x as f64 / *y as f64))
                 }
             }
             (Value::Float(x), Value::Float(y)) => {
                 if *y == 0.0 {
-                    Err(FlowError::runtime_error("Division by zero"))
+                    Err(FlowError::DivisionByZero)
                 } else {
                     Ok(Value::Float(x / y))
                 }
             }
             (Value::Integer(x), Value::Float(y)) => {
                 if *y == 0.0 {
-                    Err(FlowError::runtime_error("Division by zero"))
+                    Err(FlowError::DivisionByZero)
                 } else {
                     Ok(Value::Float(*x as f64 / y))
                 }
             }
             (Value::Float(x), Value::Integer(y)) => {
                 if *y == 0 {
-                    Err(FlowError::runtime_error("Division by zero"))
+                    Err(FlowError::DivisionByZero)
                 } else {
                     Ok(Value::Float(x / *y as f64))
                 }
@@ -1197,56 +1851,18 @@ impl VirtualMachine {
         }
     }
     
-    fn modulo_values(&self, a: &Value, b: &Value) -> Result<Value> {
-        match (a, b) {
-            (Value::Integer(x), Value::Integer(y)) => {
-                if *y == 0 {
-                    Err(FlowError::runtime_error("Modulo by zero"))
-                } else {
-                    Ok(Value::Integer(x % y))
-                }
-            }
-            _ => Err(FlowError::type_error(format!(
-                "Cannot modulo {} and {}",
-                a.type_name(),
-                b.type_name()
-            ))),
-        }
-    }
-    
-    fn values_equal(&self, a: &Value, b: &Value) -> bool {
-        match (a, b) {
-            (Value::Integer(x), Value::Integer(y)) => x == y,
-            (Value::Float(x), Value::Float(y)) => (x - y).abs() < f64::EPSILON,
-            (Value::Integer(x), Value::Float(y)) => (*x as f64 - y).abs() < f64::EPSILON,
-            (Value::Float(x), Value::Integer(y)) => (x - *y as f64).abs() < f64::EPSILON,
-            (Value::String(x), Value::String(y)) => x == y,
-            (Value::Boolean(x), Value::Boolean(y)) => x == y,
-            (Value::Null, Value::Null) => true,
-            _ => false,
-        }
-    }
-    
     fn compare_values<F>(&self, a: &Value, b: &Value, op: F) -> Result<Value>
     where
         F: FnOnce(std::cmp::Ordering) -> bool,
     {
-        let ordering = match (a, b) {
-            (Value::Integer(x), Value::Integer(y)) => x.cmp(y),
-            (Value::Float(x), Value::Float(y)) => x.partial_cmp(y).unwrap_or(std::cmp::Ordering::Equal),
-            (Value::Integer(x), Value::Float(y)) => (*x as f64).partial_cmp(y).unwrap_or(std::cmp::Ordering::Equal),
-            (Value::Float(x), Value::Integer(y)) => x.partial_cmp(&(*y as f64)).unwrap_or(std::cmp::Ordering::Equal),
-            (Value::String(x), Value::String(y)) => x.cmp(y),
-            _ => {
-                return Err(FlowError::type_error(format!(
-                    "Cannot compare {} and {}",
-                    a.type_name(),
-                    b.type_name()
-                )));
-            }
-        };
-        
-        Ok(Value::Boolean(op(ordering)))
+        match a.partial_compare(b) {
+            Some(ordering) => Ok(Value::Boolean(op(ordering))),
+            None => Err(FlowError::type_error(format!(
+                "Cannot compare {} and {}",
+                a.type_name(),
+                b.type_name()
+            ))),
+        }
     }
 }
 
@@ -1286,8 +1902,8 @@ impl BytecodeCompiler {
     
     /// Compile a statement
     fn compile_statement(&mut self, statement: &Statement) -> Result<()> {
-        match statement {
-            Statement::VariableDeclaration { name, value } => {
+        match &statement.kind {
+            StatementKind::VariableDeclaration { name, value } => {
                 self.compile_expression(value)?;
                 
                 if self.scope_depth == 0 {
@@ -1301,7 +1917,11 @@ impl BytecodeCompiler {
                 }
             }
             
-            Statement::FunctionDeclaration { name, parameters, body } => {
+            StatementKind::Destructuring { .. } => {
+                return Err(FlowError::compilation_error("Destructuring is not supported by this compiler"));
+            }
+            
+            StatementKind::FunctionDeclaration { name, parameters, body } => {
                 // Create function reference
                 let func_ref = FunctionRef {
                     name: name.clone(),
@@ -1343,7 +1963,7 @@ impl BytecodeCompiler {
                 self.emit_instruction(Instruction::StoreGlobal(name.clone()));
             }
             
-            Statement::If { condition, then_branch, else_branch } => {
+            StatementKind::If { condition, then_branch, else_branch } => {
                 // Compile condition
                 self.compile_expression(condition)?;
                 
@@ -1375,7 +1995,7 @@ impl BytecodeCompiler {
                 }
             }
             
-            Statement::While { condition, body } => {
+            StatementKind::While { condition, body } => {
                 let loop_start = self.current_chunk().instructions.len();
                 
                 // Compile condition
@@ -1402,7 +2022,11 @@ impl BytecodeCompiler {
                 self.patch_jump(exit_jump)?;
             }
             
-            Statement::For { variable, start, end, body } => {
+            StatementKind::For { variable, start, end, step, body } => {
+                if step.is_some() {
+                    return Err(FlowError::compilation_error("Stepped for loops are not supported by this compiler"));
+                }
+                
                 // Initialize loop variable
                 self.compile_expression(start)?;
                 self.locals.push(variable.clone());
@@ -1445,12 +2069,35 @@ impl BytecodeCompiler {
                 self.locals.pop();
             }
             
-            Statement::Show(expr) => {
+            StatementKind::Assignment { target, operator, value } => {
+                let name = match &target.kind {
+                    ExpressionKind::Identifier(name) => name,
+                    _ => return Err(FlowError::compilation_error("Only variables can be assigned in this compiler")),
+                };
+
+                if let Some(op) = operator {
+                    self.compile_expression(&Expression::new(ExpressionKind::Binary {
+                        left: Box::new(target.clone()),
+                        operator: op.clone(),
+                        right: Box::new(value.clone()),
+                    }, statement.span))?;
+                } else {
+                    self.compile_expression(value)?;
+                }
+
+                if let Some(index) = self.locals.iter().position(|local| local == name) {
+                    self.emit_instruction(Instruction::StoreLocal(index));
+                } else {
+                    self.emit_instruction(Instruction::StoreGlobal(name.clone()));
+                }
+            }
+
+            StatementKind::Show(expr) => {
                 self.compile_expression(expr)?;
                 self.emit_instruction(Instruction::Print);
             }
             
-            Statement::Return(expr) => {
+            StatementKind::Return(expr) => {
                 if let Some(expr) = expr {
                     self.compile_expression(expr)?;
                     self.emit_instruction(Instruction::ReturnValue);
@@ -1459,24 +2106,43 @@ impl BytecodeCompiler {
                 }
             }
             
-            Statement::Expression(expr) => {
+            StatementKind::Break | StatementKind::Continue => {
+                return Err(FlowError::compilation_error("'break' and 'continue' are not supported by this compiler"));
+            }
+            
+            StatementKind::ForIn { .. } => {
+                return Err(FlowError::compilation_error("'for ... in' loops are not supported by this compiler"));
+            }
+            
+            StatementKind::Expression(expr) => {
                 self.compile_expression(expr)?;
                 self.emit_instruction(Instruction::Pop);
             }
             
-            Statement::Import { .. } => {
+            StatementKind::Import { .. } => {
                 // Import handling would be done at a higher level
             }
             
-            Statement::Export(_) => {
+            StatementKind::Export(_) => {
                 // Export handling would be done at a higher level
             }
             
-            Statement::TryCatch { try_block, catch_variable: _, catch_block: _ } => {
+            StatementKind::TryCatch { try_block, catch_clause: _, finally_block } => {
                 // Basic try-catch compilation (simplified)
                 for stmt in try_block {
                     self.compile_statement(stmt)?;
                 }
+                for stmt in finally_block.iter().flatten() {
+                    self.compile_statement(stmt)?;
+                }
+            }
+            
+            StatementKind::Throw(value) => {
+                let Some(value) = value else {
+                    return Err(FlowError::compilation_error("'throw' without a value can only be used inside 'catch'"));
+                };
+                self.compile_expression(value)?;
+                self.emit_instruction(Instruction::Throw);
             }
         }
         
@@ -1485,13 +2151,13 @@ impl BytecodeCompiler {
     
     /// Compile an expression
     fn compile_expression(&mut self, expression: &Expression) -> Result<()> {
-        match expression {
-            Expression::Literal(literal) => {
+        match &expression.kind {
+            ExpressionKind::Literal(literal) => {
                 let value = self.literal_to_value(literal)?;
                 self.emit_constant(value);
             }
             
-            Expression::Identifier(name) => {
+            ExpressionKind::Identifier(name) => {
                 // Check if it's a local variable
                 if let Some(index) = self.locals.iter().position(|local| local == name) {
                     self.emit_instruction(Instruction::LoadLocal(index));
@@ -1501,7 +2167,15 @@ impl BytecodeCompiler {
                 }
             }
             
-            Expression::Binary { left, operator, right } => {
+            ExpressionKind::Binary { left, operator: BinaryOperator::NullCoalesce, right } => {
+                self.compile_expression(left)?;
+                let end_jump = self.emit_jump(Instruction::JumpIfNotNull(0));
+                self.emit_instruction(Instruction::Pop);
+                self.compile_expression(right)?;
+                self.patch_jump(end_jump)?;
+            }
+            
+            ExpressionKind::Binary { left, operator, right } => {
                 self.compile_expression(left)?;
                 self.compile_expression(right)?;
                 
@@ -1511,31 +2185,41 @@ impl BytecodeCompiler {
                     BinaryOperator::Multiply => Instruction::Multiply,
                     BinaryOperator::Divide => Instruction::Divide,
                     BinaryOperator::Modulo => Instruction::Modulo,
+                    BinaryOperator::Power => Instruction::Power,
+                    BinaryOperator::IntegerDivide => Instruction::IntegerDivide,
+                    BinaryOperator::BitAnd => Instruction::BitAnd,
+                    BinaryOperator::BitOr => Instruction::BitOr,
+                    BinaryOperator::BitXor => Instruction::BitXor,
+                    BinaryOperator::ShiftLeft => Instruction::ShiftLeft,
+                    BinaryOperator::ShiftRight => Instruction::ShiftRight,
                     BinaryOperator::Equal => Instruction::Equal,
                     BinaryOperator::NotEqual => Instruction::NotEqual,
                     BinaryOperator::Greater => Instruction::Greater,
                     BinaryOperator::GreaterEqual => Instruction::GreaterEqual,
                     BinaryOperator::Less => Instruction::Less,
                     BinaryOperator::LessEqual => Instruction::LessEqual,
+                    BinaryOperator::In => Instruction::In,
                     BinaryOperator::And => Instruction::And,
                     BinaryOperator::Or => Instruction::Or,
+                    BinaryOperator::NullCoalesce => unreachable!("compiled with a jump above"),
                 };
                 
                 self.emit_instruction(instruction);
             }
             
-            Expression::Unary { operator, operand } => {
+            ExpressionKind::Unary { operator, operand } => {
                 self.compile_expression(operand)?;
                 
                 let instruction = match operator {
                     UnaryOperator::Minus => Instruction::Negate,
                     UnaryOperator::Not => Instruction::Not,
+                    UnaryOperator::BitNot => Instruction::BitNot,
                 };
                 
                 self.emit_instruction(instruction);
             }
             
-            Expression::FunctionCall { name, arguments } => {
+            ExpressionKind::FunctionCall { name, arguments, .. } => {
                 // Compile arguments
                 for arg in arguments {
                     self.compile_expression(arg)?;
@@ -1548,9 +2232,10 @@ impl BytecodeCompiler {
                 self.emit_instruction(Instruction::Call(arguments.len()));
             }
             
-            Expression::MethodCall { object, method, arguments } => {
+            ExpressionKind::MethodCall { object, method, arguments, optional, .. } => {
                 // Compile object
                 self.compile_expression(object)?;
+                let null_jump = self.emit_null_check(*optional)?;
                 
                 // Compile arguments
                 for arg in arguments {
@@ -1558,10 +2243,13 @@ impl BytecodeCompiler {
                 }
                 
                 // Call method
-                self.emit_instruction(Instruction::CallMethod(method.clone()));
+                self.emit_instruction(Instruction::CallMethod(method.clone(), arguments.len()));
+                if let Some(null_jump) = null_jump {
+                    self.patch_jump(null_jump)?;
+                }
             }
             
-            Expression::Array { elements } => {
+            ExpressionKind::Array { elements } => {
                 // Compile elements
                 for element in elements {
                     self.compile_expression(element)?;
@@ -1571,29 +2259,71 @@ impl BytecodeCompiler {
                 self.emit_instruction(Instruction::NewArray(elements.len()));
             }
             
-            Expression::Object { properties } => {
+            ExpressionKind::Object { properties } => {
                 // Compile properties
-                for (key, value) in properties {
-                    self.emit_constant(Value::String(key.clone()));
-                    self.compile_expression(value)?;
+                for property in properties {
+                    match property {
+                        ObjectProperty::KeyValue(key, value) => {
+                            self.emit_constant(Value::String(key.clone()));
+                            self.compile_expression(value)?;
+                        }
+                        ObjectProperty::Spread(_) => {
+                            return Err(FlowError::compilation_error("Spread syntax is not supported by this compiler"));
+                        }
+                    }
                 }
                 
                 // Create object
                 self.emit_instruction(Instruction::NewObject);
             }
             
-            Expression::Index { object, index } => {
+            ExpressionKind::Spread(_) => {
+                return Err(FlowError::compilation_error("Spread syntax is not supported by this compiler"));
+            }
+            
+            ExpressionKind::Index { object, index, optional } => {
                 self.compile_expression(object)?;
+                let null_jump = self.emit_null_check(*optional)?;
                 self.compile_expression(index)?;
                 self.emit_instruction(Instruction::GetIndex);
+                if let Some(null_jump) = null_jump {
+                    self.patch_jump(null_jump)?;
+                }
             }
             
-            Expression::PropertyAccess { object, property } => {
+            ExpressionKind::PropertyAccess { object, property, optional } => {
                 self.compile_expression(object)?;
+                let null_jump = self.emit_null_check(*optional)?;
                 self.emit_instruction(Instruction::GetProperty(property.clone()));
+                if let Some(null_jump) = null_jump {
+                    self.patch_jump(null_jump)?;
+                }
+            }
+            
+            ExpressionKind::Conditional { condition, then_branch, else_branch } => {
+                self.compile_expression(condition)?;
+                let else_jump = self.emit_jump(Instruction::JumpIfFalse(0));
+                self.emit_instruction(Instruction::Pop);
+                self.compile_expression(then_branch)?;
+                let end_jump = self.emit_jump(Instruction::Jump(0));
+                self.patch_jump(else_jump)?;
+                self.emit_instruction(Instruction::Pop);
+                self.compile_expression(else_branch)?;
+                self.patch_jump(end_jump)?;
             }
             
-            Expression::Lambda { parameters, body } => {
+            ExpressionKind::InterpolatedString { parts } => {
+                self.emit_constant(Value::String(String::new()));
+                for part in parts {
+                    match part {
+                        InterpolationPart::Literal(text) => self.emit_constant(Value::String(text.clone())),
+                        InterpolationPart::Expression(expression) => self.compile_expression(expression)?,
+                    }
+                    self.emit_instruction(Instruction::Add);
+                }
+            }
+            
+            ExpressionKind::Lambda { parameters, body } => {
                 // Create closure reference
                 let closure_ref = ClosureRef {
                     function: FunctionRef {
@@ -1621,8 +2351,19 @@ impl BytecodeCompiler {
                 }
                 
                 // Compile lambda body
-                self.compile_expression(body)?;
-                self.emit_instruction(Instruction::ReturnValue);
+                match body {
+                    LambdaBody::Expression(expression) => {
+                        self.compile_expression(expression)?;
+                        self.emit_instruction(Instruction::ReturnValue);
+                    }
+                    LambdaBody::Block(statements) => {
+                        for statement in statements {
+                            self.compile_statement(statement)?;
+                        }
+                        self.emit_constant(Value::Null);
+                        self.emit_instruction(Instruction::ReturnValue);
+                    }
+                }
                 
                 // Restore scope
                 self.scope_depth -= 1;
@@ -1651,14 +2392,14 @@ impl BytecodeCompiler {
                 for element in elements {
                     values.push(self.literal_to_value(element)?);
                 }
-                Value::Array(FlowArray { elements: values })
+                Value::Array(FlowArray::from_values(values))
             }
             Literal::Object(properties) => {
                 let mut props = std::collections::HashMap::new();
                 for (key, value) in properties {
                     props.insert(key.clone(), self.literal_to_value(value)?);
                 }
-                Value::Object(FlowObject { properties: props })
+                Value::Object(FlowObject::from_map(props))
             }
         };
         Ok(value)
@@ -1682,6 +2423,17 @@ impl BytecodeCompiler {
     }
     
     /// Patch a jump instruction with the correct offset
+    /// Skip the access that follows when an optional chain reaches null
+    fn emit_null_check(&mut self, optional: bool) -> Result<Option<usize>> {
+        if !optional {
+            return Ok(None);
+        }
+        let not_null_jump = self.emit_jump(Instruction::JumpIfNotNull(0));
+        let null_jump = self.emit_jump(Instruction::Jump(0));
+        self.patch_jump(not_null_jump)?;
+        Ok(Some(null_jump))
+    }
+    
     fn patch_jump(&mut self, jump_index: usize) -> Result<()> {
         let offset = self.current_chunk().instructions.len() - jump_index - 1;
         
@@ -1689,6 +2441,7 @@ impl BytecodeCompiler {
             Instruction::Jump(ref mut jump_offset) => *jump_offset = offset,
             Instruction::JumpIfFalse(ref mut jump_offset) => *jump_offset = offset,
             Instruction::JumpIfTrue(ref mut jump_offset) => *jump_offset = offset,
+            Instruction::JumpIfNotNull(ref mut jump_offset) => *jump_offset = offset,
             _ => return Err(FlowError::runtime_error("Invalid jump instruction to patch")),
         }
         
@@ -1710,7 +2463,7 @@ impl VirtualMachine {
     // Built-in function implementations
     fn builtin_show(_vm: &mut VirtualMachine, args: &[Value]) -> Result<Value> {
         if args.len() != 1 {
-            return Err(FlowError::runtime_error("show() takes exactly 1 argument"));
+            return Err(FlowError::argument_count_error("show() takes exactly 1 argument"));
         }
         println!("{}", args[0]);
         Ok(Value::Null)
@@ -1718,7 +2471,7 @@ impl VirtualMachine {
 
     fn builtin_print(_vm: &mut VirtualMachine, args: &[Value]) -> Result<Value> {
         if args.len() != 1 {
-            return Err(FlowError::runtime_error("print() takes exactly 1 argument"));
+            return Err(FlowError::argument_count_error("print() takes exactly 1 argument"));
         }
         print!("{}", args[0]);
         Ok(Value::Null)
@@ -1726,19 +2479,19 @@ impl VirtualMachine {
 
     fn builtin_len(_vm: &mut VirtualMachine, args: &[Value]) -> Result<Value> {
         if args.len() != 1 {
-            return Err(FlowError::runtime_error("len() takes exactly 1 argument"));
+            return Err(FlowError::argument_count_error("len() takes exactly 1 argument"));
         }
         match &args[0] {
             Value::String(s) => Ok(Value::Integer(s.len() as i64)),
-            Value::Array(array) => Ok(Value::Integer(array.elements.len() as i64)),
-            Value::Object(object) => Ok(Value::Integer(object.properties.len() as i64)),
+            Value::Array(array) => Ok(Value::Integer(array.elements().len() as i64)),
+            Value::Object(object) => Ok(Value::Integer(object.properties().len() as i64)),
             _ => Err(FlowError::runtime_error("len() can only be called on strings, arrays, or objects")),
         }
     }
 
     fn builtin_type(_vm: &mut VirtualMachine, args: &[Value]) -> Result<Value> {
         if args.len() != 1 {
-            return Err(FlowError::runtime_error("type() takes exactly 1 argument"));
+            return Err(FlowError::argument_count_error("type() takes exactly 1 argument"));
         }
         let type_name = match &args[0] {
             Value::Integer(_) => "integer",
@@ -1758,14 +2511,14 @@ impl VirtualMachine {
 
     fn builtin_str(_vm: &mut VirtualMachine, args: &[Value]) -> Result<Value> {
         if args.len() != 1 {
-            return Err(FlowError::runtime_error("str() takes exactly 1 argument"));
+            return Err(FlowError::argument_count_error("str() takes exactly 1 argument"));
         }
         Ok(Value::String(args[0].to_string()))
     }
 
     fn builtin_int(_vm: &mut VirtualMachine, args: &[Value]) -> Result<Value> {
         if args.len() != 1 {
-            return Err(FlowError::runtime_error("int() takes exactly 1 argument"));
+            return Err(FlowError::argument_count_error("int() takes exactly 1 argument"));
         }
         match &args[0] {
             Value::Integer(i) => Ok(Value::Integer(*i)),
@@ -1783,7 +2536,7 @@ impl VirtualMachine {
 
     fn builtin_float(_vm: &mut VirtualMachine, args: &[Value]) -> Result<Value> {
         if args.len() != 1 {
-            return Err(FlowError::runtime_error("float() takes exactly 1 argument"));
+            return Err(FlowError::argument_count_error("float() takes exactly 1 argument"));
         }
         match &args[0] {
             Value::Integer(i) => Ok(Value::Float(*i as f64)),
@@ -4,11 +4,12 @@
 //! for execution by the virtual machine.
 
 use crate::ast::*;
-use crate::bytecode::{Chunk, Instruction};
+use crate::bytecode::{Chunk, ExceptionHandler, Instruction, UpvalueDescriptor};
+use crate::destructure;
 use crate::value::Value;
 use crate::error::{FlowError, Result};
 use crate::value::{FlowArray, FlowObject};
-use std::collections::HashMap;
+use std::collections::HashSet;
 
 /// Compiler state for generating bytecode
 pub struct Compiler {
@@ -18,6 +19,26 @@ pub struct Compiler {
     function_type: FunctionType,
     loop_starts: Vec<usize>,
     loop_exits: Vec<Vec<usize>>,
+    loop_scope_depths: Vec<usize>,
+    /// Compiler of the function this one is nested in, for resolving captures
+    enclosing: Option<Box<Compiler>>,
+    upvalues: Vec<UpvalueDescriptor>,
+    /// `finally` blocks of the enclosing `try` statements, innermost last,
+    /// which `return`, `break` and `continue` run on their way out
+    finally_blocks: Vec<FinallyBlock>,
+    /// Source line of the statement or expression being compiled, for the
+    /// instructions emitted without a line of their own
+    line: usize,
+    /// Script-level variables defined so far, which a `let` inside a loop
+    /// updates rather than declaring a variable of the iteration
+    globals: HashSet<String>,
+}
+
+#[derive(Debug, Clone)]
+struct FinallyBlock {
+    statements: Vec<Statement>,
+    /// Number of loops around the `try` statement
+    loop_depth: usize,
 }
 
 #[derive(Debug, Clone)]
@@ -43,6 +64,12 @@ impl Compiler {
             function_type: FunctionType::Script,
             loop_starts: Vec::new(),
             loop_exits: Vec::new(),
+            loop_scope_depths: Vec::new(),
+            enclosing: None,
+            upvalues: Vec::new(),
+            finally_blocks: Vec::new(),
+            line: 0,
+            globals: HashSet::new(),
         }
     }
     
@@ -63,27 +90,35 @@ impl Compiler {
     }
     
     fn compile_statement(&mut self, statement: &Statement) -> Result<()> {
+        let line = statement.span.start.line;
+        let enclosing_line = std::mem::replace(&mut self.line, line);
+        let result = self.compile_statement_kind(&statement.kind);
+        self.line = enclosing_line;
+        result.map_err(|error| error.with_line(line))
+    }
+    
+    fn compile_statement_kind(&mut self, statement: &StatementKind) -> Result<()> {
         match statement {
-            Statement::Expression(expr) => {
+            StatementKind::Expression(expr) => {
                 self.compile_expression(expr)?;
                 self.emit_instruction(Instruction::Pop, 0);
             }
             
-            Statement::VariableDeclaration { name, value } => {
+            StatementKind::VariableDeclaration { name, value } => {
                 self.compile_expression(value)?;
-                
-                if self.scope_depth == 0 {
-                    self.emit_instruction(Instruction::StoreGlobal(name.clone()), 0);
-                } else {
-                    self.add_local(name.clone());
-                }
+                self.emit_define_variable(name);
             }
             
-            Statement::FunctionDeclaration { name, parameters, body } => {
+            StatementKind::Destructuring { pattern, value } => {
+                self.compile_expression(value)?;
+                self.compile_pattern(pattern)?;
+            }
+            
+            StatementKind::FunctionDeclaration { name, parameters, body } => {
                 self.compile_function(name, parameters, body)?;
             }
             
-            Statement::If { condition, then_branch, else_branch } => {
+            StatementKind::If { condition, then_branch, else_branch } => {
                 self.compile_expression(condition)?;
                 
                 let else_jump = self.emit_jump(Instruction::JumpIfFalse(0));
@@ -108,115 +143,221 @@ impl Compiler {
                 }
             }
             
-            Statement::While { condition, body } => {
+            StatementKind::While { condition, body } => {
                 let loop_start = self.chunk.instructions.len();
-                self.loop_starts.push(loop_start);
-                self.loop_exits.push(Vec::new());
+                self.begin_loop(loop_start);
                 
                 self.compile_expression(condition)?;
                 let exit_jump = self.emit_jump(Instruction::JumpIfFalse(0));
                 self.emit_instruction(Instruction::Pop, 0);
                 
-                for stmt in body {
-                    self.compile_statement(stmt)?;
-                }
+                self.begin_scope();
+                self.compile_loop_body(body)?;
+                self.end_scope();
                 self.emit_loop(loop_start);
                 
                 self.patch_jump(exit_jump);
                 self.emit_instruction(Instruction::Pop, 0);
                 
-                // Patch break statements
-                if let Some(exits) = self.loop_exits.pop() {
-                    for exit in exits {
-                        self.patch_jump(exit);
-                    }
-                }
-                self.loop_starts.pop();
+                self.end_loop();
             }
             
-            Statement::For { variable, start, end, body } => {
+            StatementKind::For { variable, start, end, step, body } => {
                 self.begin_scope();
                 
-                // Compile start expression and store in loop variable
+                // The loop counts in a hidden local; each iteration gets its
+                // own copy as the loop variable
                 self.compile_expression(start)?;
-                self.add_local(variable.clone());
+                let var_index = self.locals.len();
+                self.add_local("__counter".to_string());
                 
-                // Compile end expression and store as local
+                // Compile end and step expressions and store them as locals
                 self.compile_expression(end)?;
                 let end_local = self.locals.len();
                 self.add_local("__end".to_string());
                 
-                let loop_start = self.chunk.instructions.len();
-                self.loop_starts.push(loop_start);
-                self.loop_exits.push(Vec::new());
-                
-                // Load loop variable and end value for comparison
-                let var_index = self.resolve_local(variable).unwrap();
-                self.emit_instruction(Instruction::LoadLocal(var_index), 0);
-                self.emit_instruction(Instruction::LoadLocal(end_local), 0);
-                
-                // Check if loop variable >= end value
-                self.emit_instruction(Instruction::GreaterEqual, 0);
-                let exit_jump = self.emit_jump(Instruction::JumpIfTrue(0));
-                self.emit_instruction(Instruction::Pop, 0);
-                
-                // Execute loop body
-                for stmt in body {
-                    self.compile_statement(stmt)?;
+                match step {
+                    Some(step) => self.compile_expression(step)?,
+                    None => {
+                        // Without `by`, count towards the end in whichever direction it lies
+                        self.emit_instruction(Instruction::LoadLocal(var_index), 0);
+                        self.emit_instruction(Instruction::LoadLocal(end_local), 0);
+                        self.emit_instruction(Instruction::Greater, 0);
+                        let ascending_jump = self.emit_jump(Instruction::JumpIfFalse(0));
+                        self.emit_instruction(Instruction::Pop, 0);
+                        self.emit_constant(Value::Integer(-1), 0);
+                        let step_done = self.emit_jump(Instruction::Jump(0));
+                        self.patch_jump(ascending_jump);
+                        self.emit_instruction(Instruction::Pop, 0);
+                        self.emit_constant(Value::Integer(1), 0);
+                        self.patch_jump(step_done);
+                    }
                 }
+                let step_local = self.locals.len();
+                self.add_local("__step".to_string());
+                self.emit_instruction(Instruction::CheckRange, 0);
                 
-                // Increment loop variable
+                // The increment comes first so `continue` can jump straight to it
+                let check_jump = self.emit_jump(Instruction::Jump(0));
+                let increment_start = self.chunk.instructions.len();
                 self.emit_instruction(Instruction::LoadLocal(var_index), 0);
-                self.emit_constant(Value::Integer(1), 0);
+                self.emit_instruction(Instruction::LoadLocal(step_local), 0);
                 self.emit_instruction(Instruction::Add, 0);
                 self.emit_instruction(Instruction::StoreLocal(var_index), 0);
                 self.emit_instruction(Instruction::Pop, 0);
                 
-                // Jump back to loop condition
-                self.emit_loop(loop_start);
+                // Keep going while the variable hasn't passed the inclusive end
+                self.patch_jump(check_jump);
+                // The step's sign is only known at runtime: count up for a
+                // positive step and down for a negative one
+                self.emit_instruction(Instruction::LoadLocal(step_local), 0);
+                self.emit_constant(Value::Integer(0), 0);
+                self.emit_instruction(Instruction::Greater, 0);
+                let descending_jump = self.emit_jump(Instruction::JumpIfFalse(0));
+                self.emit_instruction(Instruction::Pop, 0);
+                self.emit_instruction(Instruction::LoadLocal(var_index), 0);
+                self.emit_instruction(Instruction::LoadLocal(end_local), 0);
+                self.emit_instruction(Instruction::LessEqual, 0);
+                let check_done = self.emit_jump(Instruction::Jump(0));
+                self.patch_jump(descending_jump);
+                self.emit_instruction(Instruction::Pop, 0);
+                self.emit_instruction(Instruction::LoadLocal(var_index), 0);
+                self.emit_instruction(Instruction::LoadLocal(end_local), 0);
+                self.emit_instruction(Instruction::GreaterEqual, 0);
+                self.patch_jump(check_done);
+                let exit_jump = self.emit_jump(Instruction::JumpIfFalse(0));
+                self.emit_instruction(Instruction::Pop, 0);
+                
+                // Execute loop body, then jump back to the increment
+                self.begin_loop(increment_start);
+                self.begin_scope();
+                self.emit_instruction(Instruction::LoadLocal(var_index), 0);
+                self.add_local(variable.clone());
+                self.compile_loop_body(body)?;
+                self.end_scope();
+                self.emit_loop(increment_start);
                 
                 // Patch exit jump
                 self.patch_jump(exit_jump);
                 self.emit_instruction(Instruction::Pop, 0);
                 
-                // Patch break statements
-                if let Some(exits) = self.loop_exits.pop() {
-                    for exit in exits {
-                        self.patch_jump(exit);
+                self.end_loop();
+                self.end_scope();
+            }
+            
+            StatementKind::ForIn { variable, value_variable, iterable, body } => {
+                self.begin_scope();
+                
+                // The iterator and its position live in hidden locals for the whole loop
+                self.compile_expression(iterable)?;
+                let variables = if value_variable.is_some() { 2 } else { 1 };
+                self.emit_instruction(Instruction::GetIterator(variables), 0);
+                self.add_local("__iterator".to_string());
+                self.emit_constant(Value::Integer(0), 0);
+                self.add_local("__index".to_string());
+                
+                let loop_start = self.chunk.instructions.len();
+                let exit_jump = self.emit_jump(Instruction::IterNext(0));
+                
+                // Each item is pushed as a fresh local that the body scope pops again
+                self.begin_loop(loop_start);
+                self.begin_scope();
+                match value_variable {
+                    Some(value_variable) => {
+                        let entry_local = self.locals.len();
+                        self.add_local("__entry".to_string());
+                        self.emit_instruction(Instruction::LoadLocal(entry_local), 0);
+                        self.emit_constant(Value::Integer(0), 0);
+                        self.emit_instruction(Instruction::GetIndex, 0);
+                        self.add_local(variable.clone());
+                        self.emit_instruction(Instruction::LoadLocal(entry_local), 0);
+                        self.emit_constant(Value::Integer(1), 0);
+                        self.emit_instruction(Instruction::GetIndex, 0);
+                        self.add_local(value_variable.clone());
                     }
+                    None => self.add_local(variable.clone()),
                 }
-                self.loop_starts.pop();
+                self.compile_loop_body(body)?;
+                self.end_scope();
+                self.emit_loop(loop_start);
                 
+                self.patch_jump(exit_jump);
+                self.end_loop();
                 self.end_scope();
             }
             
-            Statement::Return(expr) => {
+            StatementKind::Break => {
+                self.compile_pending_finally(self.loop_starts.len())?;
+                self.emit_loop_exit_pops()?;
+                let jump = self.emit_jump(Instruction::Jump(0));
+                if let Some(exits) = self.loop_exits.last_mut() {
+                    exits.push(jump);
+                }
+            }
+            
+            StatementKind::Continue => {
+                self.compile_pending_finally(self.loop_starts.len())?;
+                self.emit_loop_exit_pops()?;
+                let loop_start = *self.loop_starts.last().unwrap();
+                self.emit_loop(loop_start);
+            }
+            
+            StatementKind::Return(expr) => {
                 if let Some(value) = expr {
                     self.compile_expression(value)?;
                 } else {
                     self.emit_constant(Value::Null, 0);
                 }
-                self.emit_instruction(Instruction::Return, 0);
+                
+                if self.finally_blocks.is_empty() {
+                    self.emit_instruction(Instruction::ReturnValue, 0);
+                } else {
+                    // Hold the value in a hidden local while the finally blocks run
+                    self.begin_scope();
+                    let return_local = self.locals.len();
+                    self.add_local("__return".to_string());
+                    self.compile_pending_finally(0)?;
+                    self.emit_instruction(Instruction::LoadLocal(return_local), 0);
+                    self.emit_instruction(Instruction::ReturnValue, 0);
+                    self.end_scope();
+                }
             }
             
-            Statement::Show(expr) => {
+            StatementKind::Assignment { target, operator, value } => {
+                self.compile_assignment(target, operator.as_ref(), value)?;
+            }
+
+            StatementKind::Show(expr) => {
                 self.compile_expression(expr)?;
                 self.emit_instruction(Instruction::Print, 0);
             }
             
-            Statement::Export { .. } => {
+            StatementKind::Export { .. } => {
                 // TODO: Implement module system
                 return Err(FlowError::compilation_error("Export statements not yet implemented"));
             }
             
-            Statement::Import { .. } => {
+            StatementKind::Import { .. } => {
                 // TODO: Implement module system
                 return Err(FlowError::compilation_error("Import statements not yet implemented"));
             }
             
-            Statement::TryCatch { .. } => {
-                // TODO: Implement error handling
-                return Err(FlowError::compilation_error("Try-catch statements not yet implemented"));
+            StatementKind::TryCatch { try_block, catch_clause, finally_block } => {
+                self.compile_try_catch(try_block, catch_clause.as_ref(), finally_block.as_deref())?;
+            }
+            
+            StatementKind::Throw(value) => {
+                match value {
+                    Some(value) => self.compile_expression(value)?,
+                    None => {
+                        // Rethrow the error the innermost catch block is handling
+                        let error_local = self.resolve_local("__error").ok_or_else(|| {
+                            FlowError::compilation_error("'throw' without a value can only be used inside 'catch'")
+                        })?;
+                        self.emit_instruction(Instruction::LoadLocal(error_local), 0);
+                    }
+                }
+                self.emit_instruction(Instruction::Throw, 0);
             }
         }
         
@@ -224,8 +365,16 @@ impl Compiler {
     }
     
     fn compile_expression(&mut self, expression: &Expression) -> Result<()> {
+        let line = expression.span.start.line;
+        let enclosing_line = std::mem::replace(&mut self.line, line);
+        let result = self.compile_expression_kind(&expression.kind);
+        self.line = enclosing_line;
+        result.map_err(|error| error.with_line(line))
+    }
+    
+    fn compile_expression_kind(&mut self, expression: &ExpressionKind) -> Result<()> {
         match expression {
-            Expression::Literal(literal) => {
+            ExpressionKind::Literal(literal) => {
                 let value = match literal {
                     Literal::String(s) => Value::String(s.clone()),
                     Literal::Integer(i) => Value::Integer(*i),
@@ -251,9 +400,9 @@ impl Compiler {
                         return Ok(());
                     },
                     Literal::Object(properties) => {
-                        // Compile each property key-value pair
+                        // Build the object one property at a time
+                        self.emit_instruction(Instruction::NewObject, 0);
                         for (key, value) in properties {
-                            self.emit_constant(Value::String(key.clone()), 0);
                             let val = match value {
                                 Literal::String(s) => Value::String(s.clone()),
                                 Literal::Integer(i) => Value::Integer(*i),
@@ -264,23 +413,17 @@ impl Compiler {
                                 _ => return Err(FlowError::compilation_error("Nested arrays/objects in literals not yet supported")),
                             };
                             self.emit_constant(val, 0);
+                            self.emit_instruction(Instruction::SetProperty(key.clone()), 0);
                         }
-                        self.emit_instruction(Instruction::NewObject, 0);
                         return Ok(());
                     },
                 };
                 self.emit_constant(value, 0);
             }
             
-            Expression::Identifier(name) => {
-                if let Some(local_index) = self.resolve_local(name) {
-                    self.emit_instruction(Instruction::LoadLocal(local_index), 0);
-                } else {
-                    self.emit_instruction(Instruction::LoadGlobal(name.clone()), 0);
-                }
-            }
+            ExpressionKind::Identifier(name) => self.emit_load_variable(name),
             
-            Expression::Binary { left, operator, right } => {
+            ExpressionKind::Binary { left, operator, right } => {
                 match operator {
                     BinaryOperator::And => {
                         // Short-circuit evaluation for AND
@@ -311,168 +454,390 @@ impl Compiler {
                         
                         self.patch_jump(end_jump);
                     }
+                    BinaryOperator::NullCoalesce => {
+                        // Keep a non-null left value; otherwise replace it with the right
+                        self.compile_expression(left)?;
+                        let end_jump = self.emit_jump(Instruction::JumpIfNotNull(0));
+                        self.emit_instruction(Instruction::Pop, 0);
+                        self.compile_expression(right)?;
+                        self.patch_jump(end_jump);
+                    }
                     _ => {
                         // For all other operators, compile both operands first
                         self.compile_expression(left)?;
                         self.compile_expression(right)?;
-                        
-                        match operator {
-                            BinaryOperator::Add => self.emit_instruction(Instruction::Add, 0),
-                            BinaryOperator::Subtract => self.emit_instruction(Instruction::Subtract, 0),
-                            BinaryOperator::Multiply => self.emit_instruction(Instruction::Multiply, 0),
-                            BinaryOperator::Divide => self.emit_instruction(Instruction::Divide, 0),
-                            BinaryOperator::Modulo => self.emit_instruction(Instruction::Modulo, 0),
-                            BinaryOperator::Equal => self.emit_instruction(Instruction::Equal, 0),
-                            BinaryOperator::NotEqual => self.emit_instruction(Instruction::NotEqual, 0),
-                            BinaryOperator::Greater => self.emit_instruction(Instruction::Greater, 0),
-                            BinaryOperator::GreaterEqual => self.emit_instruction(Instruction::GreaterEqual, 0),
-                            BinaryOperator::Less => self.emit_instruction(Instruction::Less, 0),
-                            BinaryOperator::LessEqual => self.emit_instruction(Instruction::LessEqual, 0),
-                            BinaryOperator::And | BinaryOperator::Or => unreachable!(), // Handled above
-                        }
+                        self.emit_binary_operator(operator);
                     }
                 }
             }
             
-            Expression::Unary { operator, operand } => {
+            ExpressionKind::Unary { operator, operand } => {
                 self.compile_expression(operand)?;
                 
                 match operator {
                     UnaryOperator::Minus => self.emit_instruction(Instruction::Negate, 0),
                     UnaryOperator::Not => self.emit_instruction(Instruction::Not, 0),
+                    UnaryOperator::BitNot => self.emit_instruction(Instruction::BitNot, 0),
                 }
             }
             
-            Expression::FunctionCall { name, arguments } => {
+            ExpressionKind::FunctionCall { name, arguments, position } if has_spread(arguments) => {
+                // Collect the arguments into one array for the call to unpack
+                self.compile_elements(arguments)?;
+                if self.is_builtin_function(name) {
+                    self.emit_instruction(Instruction::SpreadArguments, 0);
+                    self.emit_instruction(Instruction::CallBuiltin(name.clone()), position.line);
+                } else {
+                    self.emit_load_function(name);
+                    self.emit_instruction(Instruction::CallSpread, position.line);
+                }
+            }
+            
+            ExpressionKind::FunctionCall { name, arguments, position } => {
                 // Compile arguments first
                 for arg in arguments {
                     self.compile_expression(arg)?;
                 }
                 
-                // Check if it's a builtin function
+                // Calls carry their line so the VM can build stack traces
                 if self.is_builtin_function(name) {
                     // Push argument count for builtin call
                     self.emit_constant(Value::Integer(arguments.len() as i64), 0);
-                    self.emit_instruction(Instruction::CallBuiltin(name.clone()), 0);
+                    self.emit_instruction(Instruction::CallBuiltin(name.clone()), position.line);
                 } else {
                     // Load function by name
-                    self.emit_instruction(Instruction::LoadGlobal(name.clone()), 0);
-                    self.emit_instruction(Instruction::Call(arguments.len()), 0);
+                    self.emit_load_function(name);
+                    self.emit_instruction(Instruction::Call(arguments.len()), position.line);
                 }
             }
             
-            Expression::MethodCall { object, method, arguments } => {
-                self.compile_expression(object)?;
-                
-                for arg in arguments {
-                    self.compile_expression(arg)?;
+            ExpressionKind::MethodCall { .. }
+            | ExpressionKind::Index { .. }
+            | ExpressionKind::PropertyAccess { .. } => {
+                // A null before `?.` or `?[` jumps past the rest of the chain
+                let mut null_jumps = Vec::new();
+                self.compile_access(expression, &mut null_jumps)?;
+                for null_jump in null_jumps {
+                    self.patch_jump(null_jump);
+                }
+            }
+            
+            ExpressionKind::Array { elements } => self.compile_elements(elements)?,
+            
+            ExpressionKind::Object { properties } => {
+                self.emit_instruction(Instruction::NewObject, 0);
+                for property in properties {
+                    match property {
+                        ObjectProperty::KeyValue(key, value) => {
+                            self.compile_expression(value)?;
+                            self.emit_instruction(Instruction::SetProperty(key.clone()), 0);
+                        }
+                        ObjectProperty::Spread(source) => {
+                            self.compile_expression(source)?;
+                            self.emit_instruction(Instruction::ExtendObject, 0);
+                        }
+                    }
                 }
+            }
+            
+            ExpressionKind::Spread(_) => {
+                return Err(FlowError::compilation_error(
+                    "'...' can only be used in arrays, objects and call arguments",
+                ));
+            }
+            
+            ExpressionKind::Conditional { condition, then_branch, else_branch } => {
+                self.compile_expression(condition)?;
+                let else_jump = self.emit_jump(Instruction::JumpIfFalse(0));
+                self.emit_instruction(Instruction::Pop, 0); // Pop condition
+                self.compile_expression(then_branch)?;
+                let end_jump = self.emit_jump(Instruction::Jump(0));
                 
-                self.emit_instruction(Instruction::CallMethod(method.clone()), 0);
+                self.patch_jump(else_jump);
+                self.emit_instruction(Instruction::Pop, 0); // Pop condition
+                self.compile_expression(else_branch)?;
+                self.patch_jump(end_jump);
             }
             
-            Expression::Array { elements } => {
-                for element in elements {
+            ExpressionKind::Lambda { parameters, body } => {
+                let body = match body {
+                    LambdaBody::Expression(expression) => {
+                        vec![Statement::new(StatementKind::Return(Some((**expression).clone())), expression.span)]
+                    }
+                    LambdaBody::Block(statements) => statements.clone(),
+                };
+                let function = self.compile_function_body(FunctionType::Lambda, "<lambda>", parameters, &body)?;
+                self.emit_instruction(Instruction::NewClosure(function), 0);
+            }
+            
+            ExpressionKind::InterpolatedString { parts } => {
+                // Start from a string so `Add` concatenates every part
+                self.emit_constant(Value::String(String::new()), 0);
+                for part in parts {
+                    match part {
+                        InterpolationPart::Literal(text) => self.emit_constant(Value::String(text.clone()), 0),
+                        InterpolationPart::Expression(expression) => self.compile_expression(expression)?,
+                    }
+                    self.emit_instruction(Instruction::Add, 0);
+                }
+            }
+        }
+        
+        Ok(())
+    }
+    
+    /// Build an array from array elements or call arguments. Without a
+    /// `...items` this is a single `NewArray`; otherwise each run of plain
+    /// elements and each spread array is appended in turn.
+    fn compile_elements(&mut self, elements: &[Expression]) -> Result<()> {
+        if !has_spread(elements) {
+            for element in elements {
+                self.compile_expression(element)?;
+            }
+            self.emit_instruction(Instruction::NewArray(elements.len()), 0);
+            return Ok(());
+        }
+        
+        self.emit_instruction(Instruction::NewArray(0), 0);
+        for run in elements.split_inclusive(|element| matches!(element.kind, ExpressionKind::Spread(_))) {
+            let (last, plain) = run.split_last().expect("split_inclusive yields non-empty runs");
+            let spread = match &last.kind {
+                ExpressionKind::Spread(items) => Some(items),
+                _ => None,
+            };
+            let plain = if spread.is_some() { plain } else { run };
+            if !plain.is_empty() {
+                for element in plain {
                     self.compile_expression(element)?;
                 }
-                self.emit_instruction(Instruction::NewArray(elements.len()), 0);
+                self.emit_instruction(Instruction::NewArray(plain.len()), 0);
+                self.emit_instruction(Instruction::ExtendArray, 0);
             }
-            
-            Expression::Object { properties } => {
-                for (key, value) in properties {
-                    self.emit_constant(Value::String(key.clone()), 0);
+            if let Some(items) = spread {
+                self.compile_expression(items)?;
+                self.emit_instruction(Instruction::ExtendArray, 0);
+            }
+        }
+        Ok(())
+    }
+    
+    /// Emit the instruction for a non-short-circuiting binary operator
+    fn emit_binary_operator(&mut self, operator: &BinaryOperator) {
+        let instruction = match operator {
+            BinaryOperator::Add => Instruction::Add,
+            BinaryOperator::Subtract => Instruction::Subtract,
+            BinaryOperator::Multiply => Instruction::Multiply,
+            BinaryOperator::Divide => Instruction::Divide,
+            BinaryOperator::Modulo => Instruction::Modulo,
+            BinaryOperator::Power => Instruction::Power,
+            BinaryOperator::IntegerDivide => Instruction::IntegerDivide,
+            BinaryOperator::BitAnd => Instruction::BitAnd,
+            BinaryOperator::BitOr => Instruction::BitOr,
+            BinaryOperator::BitXor => Instruction::BitXor,
+            BinaryOperator::ShiftLeft => Instruction::ShiftLeft,
+            BinaryOperator::ShiftRight => Instruction::ShiftRight,
+            BinaryOperator::Equal => Instruction::Equal,
+            BinaryOperator::NotEqual => Instruction::NotEqual,
+            BinaryOperator::Greater => Instruction::Greater,
+            BinaryOperator::GreaterEqual => Instruction::GreaterEqual,
+            BinaryOperator::Less => Instruction::Less,
+            BinaryOperator::LessEqual => Instruction::LessEqual,
+            BinaryOperator::In => Instruction::In,
+            BinaryOperator::And => Instruction::And,
+            BinaryOperator::Or => Instruction::Or,
+            BinaryOperator::NullCoalesce => unreachable!("`??` is compiled with a jump"),
+        };
+        self.emit_instruction(instruction, 0);
+    }
+    
+    /// Compile an assignment, or a compound assignment when `operator` is set.
+    ///
+    /// Arrays and objects are shared, so index and property targets update
+    /// their container in place with `SetIndex`/`SetProperty`. The container
+    /// and index are evaluated once, even when a compound assignment has to
+    /// read the current value before storing the new one.
+    fn compile_assignment(
+        &mut self,
+        target: &Expression,
+        operator: Option<&BinaryOperator>,
+        value: &Expression,
+    ) -> Result<()> {
+        match &target.kind {
+            ExpressionKind::Identifier(name) => {
+                if let Some(op) = operator {
+                    self.emit_load_variable(name);
+                    self.compile_expression(value)?;
+                    self.emit_binary_operator(op);
+                } else {
                     self.compile_expression(value)?;
                 }
-                self.emit_instruction(Instruction::NewObject, 0);
+                if let Some(local_index) = self.resolve_local(name) {
+                    self.emit_instruction(Instruction::StoreLocal(local_index), 0);
+                    self.emit_instruction(Instruction::Pop, 0);
+                } else if let Some(upvalue_index) = self.resolve_upvalue(name) {
+                    self.emit_instruction(Instruction::StoreUpvalue(upvalue_index), 0);
+                    self.emit_instruction(Instruction::Pop, 0);
+                } else {
+                    self.emit_instruction(Instruction::StoreGlobal(name.clone()), 0);
+                }
             }
-            
-            Expression::Index { object, index } => {
+            ExpressionKind::Index { object, index, .. } => {
+                let Some(op) = operator else {
+                    self.compile_expression(object)?;
+                    self.compile_expression(index)?;
+                    self.compile_expression(value)?;
+                    self.emit_instruction(Instruction::SetIndex, 0);
+                    self.emit_instruction(Instruction::Pop, 0);
+                    return Ok(());
+                };
+                // Keep the container and index in hidden locals to read and
+                // then write the same element
+                self.begin_scope();
                 self.compile_expression(object)?;
+                let object_local = self.locals.len();
+                self.add_local("__object".to_string());
                 self.compile_expression(index)?;
+                let index_local = self.locals.len();
+                self.add_local("__index".to_string());
+                self.emit_instruction(Instruction::LoadLocal(object_local), 0);
+                self.emit_instruction(Instruction::LoadLocal(index_local), 0);
+                self.emit_instruction(Instruction::LoadLocal(object_local), 0);
+                self.emit_instruction(Instruction::LoadLocal(index_local), 0);
                 self.emit_instruction(Instruction::GetIndex, 0);
+                self.compile_expression(value)?;
+                self.emit_binary_operator(op);
+                self.emit_instruction(Instruction::SetIndex, 0);
+                self.emit_instruction(Instruction::Pop, 0);
+                self.end_scope();
             }
-            
-            Expression::PropertyAccess { object, property } => {
+            ExpressionKind::PropertyAccess { object, property, .. } => {
                 self.compile_expression(object)?;
-                self.emit_constant(Value::String(property.clone()), 0);
-                self.emit_instruction(Instruction::GetProperty(property.clone()), 0);
-            }
-            
-            Expression::Lambda { parameters, body } => {
-                // Create a new compiler for the lambda
-                let mut lambda_compiler = Compiler::new();
-                lambda_compiler.function_type = FunctionType::Lambda;
-                
-                // Add parameters as locals
-                for param in parameters {
-                    lambda_compiler.add_local(param.name.clone());
+                if let Some(op) = operator {
+                    self.emit_instruction(Instruction::Duplicate, 0);
+                    self.emit_instruction(Instruction::GetProperty(property.clone()), 0);
+                    self.compile_expression(value)?;
+                    self.emit_binary_operator(op);
+                } else {
+                    self.compile_expression(value)?;
                 }
-                
-                // Compile lambda body (which is an expression)
-                lambda_compiler.compile_expression(body)?;
-                lambda_compiler.emit_instruction(Instruction::Return, 0);
-                
-                let lambda_chunk = lambda_compiler.chunk;
-                
-                // Create a closure value
-                let closure_address = self.chunk.constants.len();
-                let closure_value = Value::BytecodeFunction {
-                    address: closure_address,
-                    arity: parameters.len(),
-                    locals_count: lambda_compiler.locals.len(),
-                };
-                
-                // Store the lambda chunk as a constant
-                self.chunk.constants.push(closure_value.clone());
-                
-                // Emit instruction to create closure
-                self.emit_instruction(Instruction::NewClosure(closure_address), 0);
+                self.emit_instruction(Instruction::SetProperty(property.clone()), 0);
+                self.emit_instruction(Instruction::Pop, 0);
             }
+            _ => return Err(FlowError::compilation_error("Invalid assignment target")),
         }
-        
         Ok(())
     }
     
     fn compile_function(&mut self, name: &str, parameters: &[Parameter], body: &[Statement]) -> Result<()> {
-        let mut function_compiler = Compiler::new();
-        function_compiler.function_type = FunctionType::Function;
+        let function = self.compile_function_body(FunctionType::Function, name, parameters, body)?;
+        self.emit_instruction(Instruction::NewClosure(function), 0);
         
-        // Add parameters as locals
-        for param in parameters {
-            function_compiler.add_local(param.name.clone());
-        }
+        // Top-level functions are global; nested ones are locals of their
+        // function or loop body
+        self.emit_define_variable(name);
         
-        // Compile function body
-        for statement in body {
-            function_compiler.compile_statement(statement)?;
-        }
+        Ok(())
+    }
+    
+    /// Compile a function or lambda body into its own chunk and return the
+    /// constant index of the resulting `BytecodeFunction`
+    fn compile_function_body(
+        &mut self,
+        function_type: FunctionType,
+        name: &str,
+        parameters: &[Parameter],
+        body: &[Statement],
+    ) -> Result<usize> {
+        // Compile the body with a fresh compiler that can still see this one
+        // through `enclosing`, then swap back
+        let mut function_compiler = Compiler::new();
+        function_compiler.function_type = function_type;
+        function_compiler.chunk.name = name.to_string();
+        let enclosing = std::mem::replace(self, function_compiler);
+        self.enclosing = Some(Box::new(enclosing));
         
-        // Implicit return null if no explicit return
-        function_compiler.emit_constant(Value::Null, 0);
-        function_compiler.emit_instruction(Instruction::Return, 0);
+        let result = self.compile_function_chunk(parameters, body);
         
-        let function_chunk = function_compiler.chunk;
+        let enclosing = self.enclosing.take().expect("function compiler has an enclosing compiler");
+        let mut function_compiler = std::mem::replace(self, *enclosing);
+        result?;
         
-        // Store function in constants and create function value
-        let function_address = self.chunk.constants.len();
-        let function_value = Value::BytecodeFunction {
-            address: function_address,
+        function_compiler.chunk.upvalues = function_compiler.upvalues;
+        let function = Value::BytecodeFunction {
+            address: self.chunk.functions.len(),
             arity: parameters.len(),
             locals_count: function_compiler.locals.len(),
+            captured: Vec::new(),
         };
+        self.chunk.functions.push(function_compiler.chunk);
+        
+        Ok(self.make_constant(function))
+    }
+    
+    /// Emit a function's parameter handling and body into this compiler's chunk
+    fn compile_function_chunk(&mut self, parameters: &[Parameter], body: &[Statement]) -> Result<()> {
+        // Arguments arrive in the first local slots, with any `...rest` as an
+        // array in the last
+        for param in parameters {
+            self.add_local(param.name.clone());
+        }
+        self.chunk.variadic = parameters.last().is_some_and(|param| param.is_variadic);
+        
+        // Missing arguments are passed as null; replace them with their defaults
+        for (index, param) in parameters.iter().enumerate() {
+            if let Some(default) = &param.default_value {
+                self.emit_instruction(Instruction::LoadLocal(index), 0);
+                self.emit_constant(Value::Null, 0);
+                self.emit_instruction(Instruction::Equal, 0);
+                let skip_jump = self.emit_jump(Instruction::JumpIfFalse(0));
+                self.emit_instruction(Instruction::Pop, 0);
+                self.compile_expression(default)?;
+                self.emit_instruction(Instruction::StoreLocal(index), 0);
+                self.emit_instruction(Instruction::Pop, 0);
+                let end_jump = self.emit_jump(Instruction::Jump(0));
+                self.patch_jump(skip_jump);
+                self.emit_instruction(Instruction::Pop, 0);
+                self.patch_jump(end_jump);
+            }
+        }
+        
+        // Blocks other than loop bodies don't introduce scopes, so every
+        // variable the body declares gets its slot up front, whichever branch
+        // ends up defining it
+        let mut declared: Vec<String> = parameters
+            .iter()
+            .filter_map(|param| param.pattern.as_ref())
+            .flat_map(Pattern::names)
+            .collect();
+        collect_declarations(body, &mut declared);
+        for name in declared {
+            if self.resolve_local(&name).is_none() {
+                self.emit_constant(Value::Null, 0);
+                self.add_local(name);
+            }
+        }
         
-        // For now, store the chunk as a constant (in a real implementation,
-        // we'd have a separate function table)
-        self.chunk.constants.push(function_value.clone());
+        // Destructured parameters bind their variables before the body runs
+        for (index, param) in parameters.iter().enumerate() {
+            if let Some(pattern) = &param.pattern {
+                self.emit_instruction(Instruction::LoadLocal(index), 0);
+                self.compile_pattern(pattern)?;
+            }
+        }
         
-        // Define the function globally
-        self.emit_constant(function_value, 0);
-        self.emit_instruction(Instruction::StoreGlobal(name.to_string()), 0);
+        for statement in body {
+            self.compile_statement(statement)?;
+        }
         
+        // Implicit return null if no explicit return
+        self.emit_constant(Value::Null, 0);
+        self.emit_instruction(Instruction::ReturnValue, 0);
         Ok(())
     }
     
+    /// Emit an instruction at `line`, or at the line being compiled when 0
     fn emit_instruction(&mut self, instruction: Instruction, line: usize) {
+        let line = if line == 0 { self.line } else { line };
         self.chunk.write_instruction(instruction, line);
     }
     
@@ -497,14 +862,213 @@ impl Compiler {
             Instruction::Jump(ref mut target) => *target = jump_target,
             Instruction::JumpIfFalse(ref mut target) => *target = jump_target,
             Instruction::JumpIfTrue(ref mut target) => *target = jump_target,
+            Instruction::JumpIfNotNull(ref mut target) => *target = jump_target,
+            Instruction::IterNext(ref mut target) => *target = jump_target,
             _ => panic!("Invalid jump instruction to patch"),
         }
     }
     
+    /// Compile a chain of method calls, indexing and property accesses. Each
+    /// `?.` or `?[` adds a jump, taken when the value on the stack is null,
+    /// that the caller patches to the end of the whole chain so the null is
+    /// its result.
+    fn compile_access(&mut self, expression: &ExpressionKind, null_jumps: &mut Vec<usize>) -> Result<()> {
+        let (object, optional) = match expression {
+            ExpressionKind::MethodCall { arguments, .. } if has_spread(arguments) => {
+                return Err(FlowError::compilation_error(
+                    "Spreading arguments into a method call is not supported by the bytecode compiler yet",
+                ));
+            }
+            ExpressionKind::MethodCall { object, optional, .. }
+            | ExpressionKind::Index { object, optional, .. }
+            | ExpressionKind::PropertyAccess { object, optional, .. } => (object, *optional),
+            _ => return self.compile_expression_kind(expression),
+        };
+        
+        let line = object.span.start.line;
+        let enclosing_line = std::mem::replace(&mut self.line, line);
+        let result = self.compile_access(&object.kind, null_jumps);
+        self.line = enclosing_line;
+        result.map_err(|error| error.with_line(line))?;
+        
+        if optional {
+            let not_null_jump = self.emit_jump(Instruction::JumpIfNotNull(0));
+            null_jumps.push(self.emit_jump(Instruction::Jump(0)));
+            self.patch_jump(not_null_jump);
+        }
+        
+        match expression {
+            ExpressionKind::MethodCall { method, arguments, position, .. } => {
+                for arg in arguments {
+                    self.compile_expression(arg)?;
+                }
+                self.emit_instruction(Instruction::CallMethod(method.clone(), arguments.len()), position.line);
+            }
+            ExpressionKind::Index { index, .. } => {
+                self.compile_expression(index)?;
+                self.emit_instruction(Instruction::GetIndex, 0);
+            }
+            ExpressionKind::PropertyAccess { property, .. } => {
+                self.emit_instruction(Instruction::GetProperty(property.clone()), 0);
+            }
+            _ => unreachable!("only accesses reach here"),
+        }
+        Ok(())
+    }
+    
     fn emit_loop(&mut self, loop_start: usize) {
         self.emit_instruction(Instruction::Jump(loop_start), 0);
     }
     
+    /// Start tracking `break`/`continue` targets for a loop whose
+    /// continue target is `loop_start`
+    fn begin_loop(&mut self, loop_start: usize) {
+        self.loop_starts.push(loop_start);
+        self.loop_exits.push(Vec::new());
+        self.loop_scope_depths.push(self.scope_depth);
+    }
+    
+    /// Patch pending `break` jumps to the current position
+    fn end_loop(&mut self) {
+        if let Some(exits) = self.loop_exits.pop() {
+            for exit in exits {
+                self.patch_jump(exit);
+            }
+        }
+        self.loop_starts.pop();
+        self.loop_scope_depths.pop();
+    }
+    
+    /// Compile a loop body into the scope the caller opened for it, after
+    /// any loop variables. The variables the body declares get slots in that
+    /// scope, so each iteration has its own for closures to capture, unless
+    /// they already exist outside the loop.
+    fn compile_loop_body(&mut self, body: &[Statement]) -> Result<()> {
+        let mut declared = Vec::new();
+        collect_declarations(body, &mut declared);
+        for name in declared {
+            if self.resolve_local(&name).is_none() && !self.globals.contains(&name) {
+                self.emit_constant(Value::Null, 0);
+                self.add_local(name);
+            }
+        }
+        
+        for stmt in body {
+            self.compile_statement(stmt)?;
+        }
+        Ok(())
+    }
+    
+    /// Pop locals declared inside the innermost loop body before jumping out of it
+    fn emit_loop_exit_pops(&mut self) -> Result<()> {
+        let loop_depth = *self.loop_scope_depths.last()
+            .ok_or_else(|| FlowError::compilation_error("'break' or 'continue' outside of a loop"))?;
+        let captured: Vec<bool> = self.locals.iter().rev()
+            .take_while(|local| local.depth > loop_depth)
+            .map(|local| local.is_captured)
+            .collect();
+        for is_captured in captured {
+            self.emit_pop_local(is_captured);
+        }
+        Ok(())
+    }
+    
+    /// Compile `try`/`catch`/`finally`. The VM jumps to the catch code with
+    /// the error on the stack, which becomes the hidden `__error` local.
+    fn compile_try_catch(
+        &mut self,
+        try_block: &[Statement],
+        catch_clause: Option<&CatchClause>,
+        finally_block: Option<&[Statement]>,
+    ) -> Result<()> {
+        if let Some(statements) = finally_block {
+            self.finally_blocks.push(FinallyBlock {
+                statements: statements.to_vec(),
+                loop_depth: self.loop_starts.len(),
+            });
+        }
+        let stack_depth = self.locals.len();
+        
+        let try_start = self.chunk.instructions.len();
+        for stmt in try_block {
+            self.compile_statement(stmt)?;
+        }
+        let try_end = self.chunk.instructions.len();
+        let skip_catch = self.emit_jump(Instruction::Jump(0));
+        
+        // An error escaping the catch block, or the try block when there is
+        // no catch, runs the finally block, then is raised again
+        let mut rethrown = (try_start, try_end);
+        if let Some(catch_clause) = catch_clause {
+            let catch_start = self.chunk.instructions.len();
+            self.chunk.handlers.push(ExceptionHandler {
+                start: try_start,
+                end: try_end,
+                handler: catch_start,
+                stack_depth,
+            });
+            self.begin_scope();
+            let error_local = self.locals.len();
+            self.add_local("__error".to_string());
+            // Function and loop bodies reserve a slot for the catch variable up front
+            self.emit_instruction(Instruction::LoadLocal(error_local), 0);
+            self.emit_define_variable(&catch_clause.variable);
+            for stmt in &catch_clause.block {
+                self.compile_statement(stmt)?;
+            }
+            self.end_scope();
+            rethrown = (catch_start, self.chunk.instructions.len());
+        }
+        
+        let Some(statements) = finally_block else {
+            self.patch_jump(skip_catch);
+            return Ok(());
+        };
+        self.finally_blocks.pop();
+        
+        let skip_rethrow = self.emit_jump(Instruction::Jump(0));
+        let rethrow_start = self.chunk.instructions.len();
+        self.chunk.handlers.push(ExceptionHandler {
+            start: rethrown.0,
+            end: rethrown.1,
+            handler: rethrow_start,
+            stack_depth,
+        });
+        self.begin_scope();
+        let pending_local = self.locals.len();
+        self.add_local("__pending".to_string());
+        for stmt in statements {
+            self.compile_statement(stmt)?;
+        }
+        self.emit_instruction(Instruction::LoadLocal(pending_local), 0);
+        self.emit_instruction(Instruction::Throw, 0);
+        self.end_scope();
+        
+        self.patch_jump(skip_catch);
+        self.patch_jump(skip_rethrow);
+        for stmt in statements {
+            self.compile_statement(stmt)?;
+        }
+        Ok(())
+    }
+    
+    /// Inline the finally blocks that a jump out of the `try` statements
+    /// nested in the innermost `loop_depth` loops would skip, innermost first
+    fn compile_pending_finally(&mut self, loop_depth: usize) -> Result<()> {
+        let blocks = self.finally_blocks.clone();
+        for (index, block) in blocks.iter().enumerate().rev() {
+            if block.loop_depth < loop_depth {
+                break;
+            }
+            // A finally block no longer applies while it runs
+            let enclosing = self.finally_blocks.split_off(index);
+            let result = block.statements.iter().try_for_each(|stmt| self.compile_statement(stmt));
+            self.finally_blocks.extend(enclosing);
+            result?;
+        }
+        Ok(())
+    }
+    
     fn begin_scope(&mut self) {
         self.scope_depth += 1;
     }
@@ -514,7 +1078,16 @@ impl Compiler {
         
         // Remove locals from this scope
         while !self.locals.is_empty() && self.locals.last().unwrap().depth > self.scope_depth {
-            self.locals.pop();
+            let local = self.locals.pop().unwrap();
+            self.emit_pop_local(local.is_captured);
+        }
+    }
+    
+    /// Pop a local going out of scope, first closing its upvalue if a closure captured it
+    fn emit_pop_local(&mut self, is_captured: bool) {
+        if is_captured {
+            self.emit_instruction(Instruction::CloseUpvalue, 0);
+        } else {
             self.emit_instruction(Instruction::Pop, 0);
         }
     }
@@ -536,6 +1109,148 @@ impl Compiler {
         }
         None
     }
+    
+    /// Resolve a variable declared in an enclosing function, adding it to this
+    /// function's upvalues (and to those of every function in between)
+    fn resolve_upvalue(&mut self, name: &str) -> Option<usize> {
+        let enclosing = self.enclosing.as_mut()?;
+        if let Some(local_index) = enclosing.resolve_local(name) {
+            enclosing.locals[local_index].is_captured = true;
+            return Some(self.add_upvalue(true, local_index));
+        }
+        let upvalue_index = enclosing.resolve_upvalue(name)?;
+        Some(self.add_upvalue(false, upvalue_index))
+    }
+    
+    fn add_upvalue(&mut self, is_local: bool, index: usize) -> usize {
+        let descriptor = UpvalueDescriptor { is_local, index };
+        if let Some(existing) = self.upvalues.iter().position(|upvalue| *upvalue == descriptor) {
+            return existing;
+        }
+        self.upvalues.push(descriptor);
+        self.upvalues.len() - 1
+    }
+    
+    /// Define a variable holding the value on top of the stack
+    fn emit_define_variable(&mut self, name: &str) {
+        // Like the interpreter, only loop bodies introduce scopes, and
+        // re-declaring a visible local overwrites it
+        if let Some(local_index) = self.resolve_local(name) {
+            self.emit_instruction(Instruction::StoreLocal(local_index), 0);
+            self.emit_instruction(Instruction::Pop, 0);
+        } else if self.function_type == FunctionType::Script {
+            self.emit_instruction(Instruction::StoreGlobal(name.to_string()), 0);
+            self.globals.insert(name.to_string());
+        } else {
+            self.add_local(name.to_string());
+        }
+    }
+    
+    /// Define the variables of `pattern` from the parts of the value on top
+    /// of the stack
+    fn compile_pattern(&mut self, pattern: &Pattern) -> Result<()> {
+        match pattern {
+            Pattern::Identifier(name) => self.emit_define_variable(name),
+            Pattern::Array { elements, rest } => {
+                let required = destructure::required_elements(elements);
+                self.emit_instruction(Instruction::UnpackArray(required, elements.len(), rest.is_some()), 0);
+                for element in elements {
+                    self.compile_pattern_element(element)?;
+                }
+                if let Some(rest) = rest {
+                    self.emit_define_variable(rest);
+                }
+            }
+            Pattern::Object { properties } => {
+                self.emit_instruction(Instruction::UnpackObject(destructure::pattern_keys(properties)), 0);
+                for (_, element) in properties {
+                    self.compile_pattern_element(element)?;
+                }
+            }
+        }
+        Ok(())
+    }
+    
+    fn compile_pattern_element(&mut self, element: &PatternElement) -> Result<()> {
+        if let Some(default) = &element.default_value {
+            // A missing or null part takes the default
+            let end_jump = self.emit_jump(Instruction::JumpIfNotNull(0));
+            self.emit_instruction(Instruction::Pop, 0);
+            self.compile_expression(default)?;
+            self.patch_jump(end_jump);
+        }
+        self.compile_pattern(&element.pattern)
+    }
+    
+    /// Load a variable, looking in locals, then enclosing functions, then globals
+    fn emit_load_variable(&mut self, name: &str) {
+        if let Some(local_index) = self.resolve_local(name) {
+            self.emit_instruction(Instruction::LoadLocal(local_index), 0);
+        } else if let Some(upvalue_index) = self.resolve_upvalue(name) {
+            self.emit_instruction(Instruction::LoadUpvalue(upvalue_index), 0);
+        } else {
+            self.emit_instruction(Instruction::LoadGlobal(name.to_string()), 0);
+        }
+    }
+    
+    /// Load the function a call names, which like `emit_load_variable` may
+    /// be any variable, but is reported as an undefined function when missing
+    fn emit_load_function(&mut self, name: &str) {
+        if let Some(local_index) = self.resolve_local(name) {
+            self.emit_instruction(Instruction::LoadLocal(local_index), 0);
+        } else if let Some(upvalue_index) = self.resolve_upvalue(name) {
+            self.emit_instruction(Instruction::LoadUpvalue(upvalue_index), 0);
+        } else {
+            self.emit_instruction(Instruction::LoadFunction(name.to_string()), 0);
+        }
+    }
+}
+
+/// Whether any of the elements or arguments is a `...items`
+fn has_spread(elements: &[Expression]) -> bool {
+    elements.iter().any(|element| matches!(element.kind, ExpressionKind::Spread(_)))
+}
+
+/// Names declared by `let`, `def` or `catch` in a function or loop body, including
+/// inside its blocks but not inside nested loops, which declare their own, or
+/// nested functions
+fn collect_declarations(statements: &[Statement], names: &mut Vec<String>) {
+    for statement in statements {
+        match &statement.kind {
+            StatementKind::VariableDeclaration { name, .. } | StatementKind::FunctionDeclaration { name, .. }
+                if !names.contains(name) =>
+            {
+                names.push(name.clone());
+            }
+            StatementKind::Destructuring { pattern, .. } => {
+                for name in pattern.names() {
+                    if !names.contains(&name) {
+                        names.push(name);
+                    }
+                }
+            }
+            StatementKind::If { then_branch, else_branch, .. } => {
+                collect_declarations(then_branch, names);
+                if let Some(else_branch) = else_branch {
+                    collect_declarations(else_branch, names);
+                }
+            }
+            StatementKind::TryCatch { try_block, catch_clause, finally_block } => {
+                collect_declarations(try_block, names);
+                if let Some(catch_clause) = catch_clause {
+                    if !names.contains(&catch_clause.variable) {
+                        names.push(catch_clause.variable.clone());
+                    }
+                    collect_declarations(&catch_clause.block, names);
+                }
+                if let Some(finally_block) = finally_block {
+                    collect_declarations(finally_block, names);
+                }
+            }
+            StatementKind::Export(statement) => collect_declarations(std::slice::from_ref(statement.as_ref()), names),
+            _ => {}
+        }
+    }
 }
 
 /// Compile a list of statements into bytecode
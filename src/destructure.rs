@@ -0,0 +1,91 @@
+//! Destructuring shared by the interpreter and the VM
+//!
+//! Taking apart the value a pattern is matched against, with the checks and
+//! error messages both engines report. Binding the parts to variables, and
+//! filling in defaults, is left to each engine.
+
+use crate::ast::PatternElement;
+use crate::error::{FlowError, Result};
+use crate::value::{FlowArray, Value};
+
+/// How many elements an array needs for the pattern: every element up to the
+/// last one without a default
+pub fn required_elements(elements: &[PatternElement]) -> usize {
+    elements
+        .iter()
+        .rposition(|element| element.default_value.is_none())
+        .map_or(0, |index| index + 1)
+}
+
+/// The keys an object pattern reads, each with whether it has to be present:
+/// keys without a default do
+pub fn pattern_keys(properties: &[(String, PatternElement)]) -> Vec<(String, bool)> {
+    properties
+        .iter()
+        .map(|(key, element)| (key.clone(), element.default_value.is_none()))
+        .collect()
+}
+
+/// The first `count` elements of an array, null where the array is shorter,
+/// and the elements after them for a `...rest`
+pub fn unpack_array(value: &Value, required: usize, count: usize, has_rest: bool) -> Result<(Vec<Value>, Option<Value>)> {
+    let array = match value {
+        Value::Array(array) => array,
+        other => {
+            return Err(FlowError::type_error(format!(
+                "Cannot destructure {} with an array pattern",
+                other.type_name()
+            )))
+        }
+    };
+
+    let mut elements = array.to_vec();
+    if elements.len() < required {
+        return Err(FlowError::runtime_error(format!(
+            "Cannot destructure an array of {}: the pattern needs at least {}",
+            elements_count(elements.len()),
+            required
+        )));
+    }
+    if !has_rest && elements.len() > count {
+        return Err(FlowError::runtime_error(format!(
+            "Cannot destructure an array of {} into {} name{}; add `...rest` to collect the others",
+            elements_count(elements.len()),
+            count,
+            if count == 1 { "" } else { "s" }
+        )));
+    }
+
+    let rest = has_rest.then(|| Value::Array(FlowArray::from_values(elements.split_off(count.min(elements.len())))));
+    elements.resize(count, Value::Null);
+    Ok((elements, rest))
+}
+
+/// The value of each key of an object. A key that is `required` has to be
+/// present; an optional one that is missing gives null.
+pub fn unpack_object(value: &Value, keys: &[(String, bool)]) -> Result<Vec<Value>> {
+    let object = match value {
+        Value::Object(object) => object,
+        other => {
+            return Err(FlowError::type_error(format!(
+                "Cannot destructure {} with an object pattern",
+                other.type_name()
+            )))
+        }
+    };
+
+    keys.iter()
+        .map(|(key, required)| match object.get(key) {
+            Some(value) => Ok(value),
+            None if *required => Err(FlowError::runtime_error(format!(
+                "Cannot destructure missing key '{}'; give it a default with `{} = ...`",
+                key, key
+            ))),
+            None => Ok(Value::Null),
+        })
+        .collect()
+}
+
+fn elements_count(count: usize) -> String {
+    format!("{} element{}", count, if count == 1 { "" } else { "s" })
+}
@@ -0,0 +1,184 @@
+//! Diagnostics for FlowLang
+//!
+//! Compiler-style error reports: the error's code and message, the source line
+//! it points at with a caret under the column, and hints for fixing it.
+//! Rendered as plain text for terminals or as JSON for tools.
+
+use serde::Serialize;
+
+use crate::ast::Span;
+use crate::error::FlowError;
+
+/// An error report. Lines and columns start at 1; 0 means unknown.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Diagnostic {
+    pub code: String,
+    pub message: String,
+    pub file: Option<String>,
+    pub line: usize,
+    pub column: usize,
+    /// Where the underlined source ends, just past its last character
+    pub end_line: usize,
+    pub end_column: usize,
+    /// Suggested fixes, such as "did you mean `count`?"
+    pub hints: Vec<String>,
+    /// Further context, such as the calls the error escaped from
+    pub notes: Vec<String>,
+}
+
+impl Diagnostic {
+    /// A report of the error at whatever line and column it carries
+    pub fn from_error(error: &FlowError) -> Self {
+        let message = match error {
+            FlowError::LexerError { .. }
+            | FlowError::ParserError { .. }
+            | FlowError::RuntimeError { .. } => error.message(),
+            other => other.to_string(),
+        };
+        let line = error.line().unwrap_or(0);
+        let column = error.column().unwrap_or(0);
+        Diagnostic {
+            code: error.code().to_string(),
+            message,
+            file: None,
+            line,
+            column,
+            end_line: line,
+            end_column: column,
+            hints: error.hint().map(str::to_string).into_iter().collect(),
+            notes: Vec::new(),
+        }
+    }
+
+    pub fn in_file(mut self, file: impl Into<String>) -> Self {
+        self.file = Some(file.into());
+        self
+    }
+
+    /// Point the report at the source a node was parsed from
+    pub fn at_span(mut self, span: Span) -> Self {
+        self.line = span.start.line;
+        self.column = span.start.column;
+        self.end_line = span.end.line;
+        self.end_column = span.end.column;
+        self
+    }
+
+    /// Point the report at a line when it has no better location
+    pub fn at_line(mut self, line: usize) -> Self {
+        if self.line == 0 {
+            self.line = line;
+            self.end_line = line;
+        }
+        self
+    }
+
+    pub fn with_hint(mut self, hint: impl Into<String>) -> Self {
+        self.hints.push(hint.into());
+        self
+    }
+
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.notes.push(note.into());
+        self
+    }
+
+    /// The report as plain text, quoting the line it points at from `source`:
+    ///
+    /// ```text
+    /// error[E0002]: Expected 'then' after if condition
+    ///  --> main.flow:1:9
+    ///   |
+    /// 1 | if x > 1
+    ///   |         ^
+    ///   = help: an `if` condition is followed by `then`
+    /// ```
+    pub fn render(&self, source: &str) -> String {
+        let mut out = format!("error[{}]: {}\n", self.code, self.message);
+        let source_line = match self.line {
+            0 => None,
+            line => source.lines().nth(line - 1),
+        };
+        let gutter = " ".repeat(self.line.to_string().len());
+
+        if self.line > 0 {
+            let location = match (&self.file, self.column) {
+                (Some(file), 0) => format!("{}:{}", file, self.line),
+                (Some(file), column) => format!("{}:{}:{}", file, self.line, column),
+                (None, 0) => format!("line {}", self.line),
+                (None, column) => format!("line {}, column {}", self.line, column),
+            };
+            out.push_str(&format!("{}--> {}\n", gutter, location));
+        }
+
+        if let Some(text) = source_line {
+            out.push_str(&format!("{} |\n", gutter));
+            out.push_str(&format!("{} | {}\n", self.line, text));
+            if self.column > 0 {
+                // Underline to the end of the span if it stays on this line
+                let width = if self.end_line == self.line && self.end_column > self.column {
+                    self.end_column - self.column
+                } else {
+                    1
+                };
+                let indent: String = text.chars()
+                    .take(self.column - 1)
+                    .map(|ch| if ch == '\t' { '\t' } else { ' ' })
+                    .collect();
+                out.push_str(&format!("{} | {}{}\n", gutter, indent, "^".repeat(width)));
+            }
+        }
+
+        for hint in &self.hints {
+            out.push_str(&format!("{} = help: {}\n", gutter, hint));
+        }
+        for note in &self.notes {
+            out.push_str(&format!("{} = note: {}\n", gutter, note));
+        }
+        out
+    }
+
+    /// The report as a single line of JSON
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap_or_default()
+    }
+}
+
+/// The candidate most like `word`, if one is close enough to be a likely typo
+pub fn closest_match<'a>(word: &str, candidates: impl IntoIterator<Item = &'a str>) -> Option<&'a str> {
+    // Allow one edit for short words and roughly one per three characters after that
+    let limit = (word.chars().count() / 3).max(1);
+    candidates
+        .into_iter()
+        .filter(|candidate| *candidate != word)
+        .map(|candidate| (edit_distance(word, candidate), candidate))
+        .filter(|(distance, _)| *distance <= limit)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+/// Levenshtein distance, counting a swap of two neighbouring characters as one edit
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut rows = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in rows.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in rows[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            let mut distance = (rows[i - 1][j] + 1)
+                .min(rows[i][j - 1] + 1)
+                .min(rows[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                distance = distance.min(rows[i - 2][j - 2] + 1);
+            }
+            rows[i][j] = distance;
+        }
+    }
+    rows[a.len()][b.len()]
+}
@@ -1,5 +1,5 @@
 use thiserror::Error;
-use crate::value::Value;
+use crate::value::{Value, FlowArray, FlowObject};
 
 #[derive(Error, Debug, Clone)]
 pub enum FlowError {
@@ -10,10 +10,16 @@ pub enum FlowError {
         message: String,
     },
 
-    #[error("Parser error at line {line}: {message}")]
-    ParserError { line: usize, message: String },
+    /// `column` is 0 when unknown; `hint` suggests a fix
+    #[error("Parser error{}: {message}", at_line(.line))]
+    ParserError {
+        line: usize,
+        column: usize,
+        message: String,
+        hint: Option<String>,
+    },
 
-    #[error("Runtime error at line {line}: {message}")]
+    #[error("Runtime error{}: {message}", at_line(.line))]
     RuntimeError { line: usize, message: String },
 
     #[error("Type error: {message}")]
@@ -28,6 +34,10 @@ pub enum FlowError {
     #[error("Invalid operation: {message}")]
     InvalidOperation { message: String },
 
+    /// A function or method was called with too few or too many arguments
+    #[error("Wrong number of arguments: {message}")]
+    ArgumentCountError { message: String },
+
     #[error("Division by zero")]
     DivisionByZero,
 
@@ -39,6 +49,16 @@ pub enum FlowError {
     
     #[error("Return: {value:?}")]
     Return { value: Value },
+
+    #[error("'break' used outside of a loop")]
+    Break,
+
+    #[error("'continue' used outside of a loop")]
+    Continue,
+
+    /// A value raised by a script's `throw`, already in error-object form
+    #[error("Uncaught {}", describe_thrown(.value))]
+    Thrown { value: Value },
 }
 
 impl FlowError {
@@ -51,16 +71,19 @@ impl FlowError {
     }
 
     pub fn parser_error(message: impl Into<String>) -> Self {
-        FlowError::ParserError {
-            line: 0, // Default to 0 when line info not available
-            message: message.into(),
-        }
+        Self::parser_error_at(0, 0, message) // Default to 0 when line info not available
     }
 
     pub fn parser_error_at_line(line: usize, message: impl Into<String>) -> Self {
+        Self::parser_error_at(line, 0, message)
+    }
+
+    pub fn parser_error_at(line: usize, column: usize, message: impl Into<String>) -> Self {
         FlowError::ParserError {
             line,
+            column,
             message: message.into(),
+            hint: None,
         }
     }
 
@@ -102,6 +125,12 @@ impl FlowError {
         }
     }
 
+    pub fn argument_count_error(message: impl Into<String>) -> Self {
+        FlowError::ArgumentCountError {
+            message: message.into(),
+        }
+    }
+
     pub fn compilation_error(message: &str) -> Self {
         FlowError::RuntimeError {
             line: 0,
@@ -112,6 +141,215 @@ impl FlowError {
     pub fn return_value(value: Value) -> Self {
         FlowError::Return { value }
     }
+
+    /// Raise a script value as an error. Anything other than an object is
+    /// wrapped as `{kind: "Error", message: ...}`; objects keep their own
+    /// fields and gain any of kind, message, line and stack they lack.
+    pub fn thrown(value: Value, stack: &[StackFrame]) -> Self {
+        let value = match value {
+            Value::Object(object) => {
+                let defaults = [
+                    ("kind", Value::String("Error".to_string())),
+                    ("message", Value::String(String::new())),
+                    ("line", Value::Null),
+                    ("stack", stack_value(stack)),
+                ];
+                for (key, default) in defaults {
+                    if !object.contains_key(key) {
+                        object.set(key.to_string(), default);
+                    }
+                }
+                Value::Object(object)
+            }
+            other => error_object("Error", other.to_string(), None, stack),
+        };
+        FlowError::Thrown { value }
+    }
+
+    /// Attach a suggestion for fixing a syntax error, unless it has one already.
+    /// Other errors are returned unchanged.
+    pub fn with_hint(mut self, new_hint: impl Into<String>) -> Self {
+        if let FlowError::ParserError { hint: hint @ None, .. } = &mut self {
+            *hint = Some(new_hint.into());
+        }
+        self
+    }
+
+    /// The suggestion for fixing the error, if there is one
+    pub fn hint(&self) -> Option<&str> {
+        match self {
+            FlowError::ParserError { hint, .. } => hint.as_deref(),
+            _ => None,
+        }
+    }
+
+    /// The column the error was reported at, when known
+    pub fn column(&self) -> Option<usize> {
+        match self {
+            FlowError::LexerError { column, .. }
+            | FlowError::ParserError { column, .. } if *column > 0 => Some(*column),
+            _ => None,
+        }
+    }
+
+    /// A stable code identifying the kind of error, such as `E0002`.
+    /// `return`, `break` and `continue` only unwind to the function or loop
+    /// they leave and never reach the user, so they share the placeholder
+    /// `E0000`, which has no catalog entry.
+    pub fn code(&self) -> &'static str {
+        match self {
+            FlowError::LexerError { .. } => "E0001",
+            FlowError::ParserError { .. } => "E0002",
+            FlowError::RuntimeError { .. } => "E0003",
+            FlowError::TypeError { .. } => "E0004",
+            FlowError::UndefinedVariable { .. } => "E0005",
+            FlowError::UndefinedFunction { .. } => "E0006",
+            FlowError::InvalidOperation { .. } => "E0007",
+            FlowError::DivisionByZero => "E0008",
+            FlowError::IndexOutOfBounds { .. } => "E0009",
+            FlowError::IoError { .. } => "E0010",
+            FlowError::Thrown { .. } => "E0011",
+            FlowError::ArgumentCountError { .. } => "E0012",
+            FlowError::Break | FlowError::Continue | FlowError::Return { .. } => "E0000",
+        }
+    }
+
+    /// The kind of error as seen by a script's `catch` binding
+    pub fn kind(&self) -> String {
+        let kind = match self {
+            FlowError::LexerError { .. } => "LexerError",
+            FlowError::ParserError { .. } => "ParserError",
+            FlowError::RuntimeError { .. } => "RuntimeError",
+            FlowError::TypeError { .. } => "TypeError",
+            FlowError::UndefinedVariable { .. } => "UndefinedVariable",
+            FlowError::UndefinedFunction { .. } => "UndefinedFunction",
+            FlowError::InvalidOperation { .. } => "InvalidOperation",
+            FlowError::ArgumentCountError { .. } => "ArgumentCountError",
+            FlowError::DivisionByZero => "DivisionByZero",
+            FlowError::IndexOutOfBounds { .. } => "IndexOutOfBounds",
+            FlowError::IoError { .. } => "IoError",
+            FlowError::Return { .. } => "Return",
+            FlowError::Break => "Break",
+            FlowError::Continue => "Continue",
+            FlowError::Thrown { value } => return thrown_field(value, "kind"),
+        };
+        kind.to_string()
+    }
+
+    /// The error message without the kind or line prefix
+    pub fn message(&self) -> String {
+        match self {
+            FlowError::LexerError { message, .. }
+            | FlowError::ParserError { message, .. }
+            | FlowError::RuntimeError { message, .. }
+            | FlowError::TypeError { message }
+            | FlowError::InvalidOperation { message }
+            | FlowError::ArgumentCountError { message }
+            | FlowError::IoError { message } => message.clone(),
+            FlowError::Thrown { value } => thrown_field(value, "message"),
+            other => other.to_string(),
+        }
+    }
+
+    /// The source line the error was reported at, when known
+    pub fn line(&self) -> Option<usize> {
+        match self {
+            FlowError::LexerError { line, .. }
+            | FlowError::ParserError { line, .. }
+            | FlowError::RuntimeError { line, .. } if *line > 0 => Some(*line),
+            FlowError::Thrown { value: Value::Object(object) } => match object.get("line") {
+                Some(Value::Integer(line)) if line > 0 => Some(line as usize),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// Give the error a source line if it can carry one and has none yet.
+    /// Thrown objects get the line when their `line` field is null.
+    pub fn with_line(mut self, line: usize) -> Self {
+        match &mut self {
+            FlowError::ParserError { line: current, .. }
+            | FlowError::RuntimeError { line: current, .. } if *current == 0 => *current = line,
+            FlowError::Thrown { value: Value::Object(object) } if line > 0 => {
+                if matches!(object.get("line"), Some(Value::Null)) {
+                    object.set("line".to_string(), Value::Integer(line as i64));
+                }
+            }
+            _ => {}
+        }
+        self
+    }
+
+    /// The value a `catch` clause binds: an object with `kind`, `message`,
+    /// `line` and `stack` fields, where `line` falls back to the given one.
+    /// Thrown values are passed through as-is.
+    pub fn to_value(&self, stack: &[StackFrame], line: Option<usize>) -> Value {
+        match self {
+            FlowError::Thrown { value } => value.clone(),
+            other => error_object(&other.kind(), other.message(), other.line().or(line), stack),
+        }
+    }
+
+    /// Whether this error is a control-flow signal (`return`, `break`, `continue`)
+    /// unwinding the interpreter rather than a real failure
+    pub fn is_control_flow(&self) -> bool {
+        matches!(self, FlowError::Return { .. } | FlowError::Break | FlowError::Continue)
+    }
+}
+
+/// One call in a stack trace: the function called and where it was called from
+#[derive(Debug, Clone, PartialEq)]
+pub struct StackFrame {
+    pub function: String,
+    pub line: usize,
+    /// Unknown when the trace comes from the bytecode VM
+    pub column: Option<usize>,
+}
+
+impl std::fmt::Display for StackFrame {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.column {
+            Some(column) => write!(f, "in {} (called at line {}, column {})", self.function, self.line, column),
+            None => write!(f, "in {} (called at line {})", self.function, self.line),
+        }
+    }
+}
+
+/// Line 0 means the line isn't known
+fn at_line(line: &usize) -> String {
+    if *line > 0 {
+        format!(" at line {}", line)
+    } else {
+        String::new()
+    }
+}
+
+fn error_object(kind: &str, message: String, line: Option<usize>, stack: &[StackFrame]) -> Value {
+    let object = FlowObject::new();
+    object.set("kind".to_string(), Value::String(kind.to_string()));
+    object.set("message".to_string(), Value::String(message));
+    object.set("line".to_string(), line.map_or(Value::Null, |line| Value::Integer(line as i64)));
+    object.set("stack".to_string(), stack_value(stack));
+    Value::Object(object)
+}
+
+/// The `stack` field of an error object: the called function names, innermost first
+fn stack_value(stack: &[StackFrame]) -> Value {
+    Value::Array(FlowArray::from_values(
+        stack.iter().map(|frame| Value::String(frame.function.clone())).collect(),
+    ))
+}
+
+fn thrown_field(value: &Value, field: &str) -> String {
+    match value {
+        Value::Object(object) => object.get(field).map(|v| v.to_string()).unwrap_or_default(),
+        other => other.to_string(),
+    }
+}
+
+fn describe_thrown(value: &Value) -> String {
+    format!("{}: {}", thrown_field(value, "kind"), thrown_field(value, "message"))
 }
 
 impl From<std::io::Error> for FlowError {
@@ -0,0 +1,167 @@
+//! Error code catalog
+//!
+//! A long-form explanation of every code `FlowError::code` can return, with an
+//! example that raises it and a corrected version. `flowlang explain E0008`
+//! prints an entry.
+
+use std::fmt;
+
+/// The catalog entry for one error code
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Explanation {
+    pub code: &'static str,
+    pub title: &'static str,
+    pub description: &'static str,
+    /// A script that raises the error
+    pub wrong: &'static str,
+    /// The same script, fixed
+    pub right: &'static str,
+}
+
+/// Every error code, in order
+pub const EXPLANATIONS: &[Explanation] = &[
+    Explanation {
+        code: "E0001",
+        title: "Unreadable source text",
+        description: "The lexer found text it can't turn into tokens, before any of the \
+script was parsed. Common causes are a string missing its closing quote, a character \
+that isn't part of the language such as `@` or `$`, or a malformed number literal \
+like `0x` or `0b102`.",
+        wrong: "let name be \"Ada\nshow name",
+        right: "let name be \"Ada\"\nshow name",
+    },
+    Explanation {
+        code: "E0002",
+        title: "Syntax error",
+        description: "The script isn't valid FlowLang. The message names what the parser \
+expected; look at the marked token and the end of the line before it. Frequent \
+mistakes are leaving out `then` after an `if` condition or `do` after a loop header, \
+a missing `end`, and declaring variables with `=` instead of `be`. `break` and \
+`continue` outside a loop are also reported here.",
+        wrong: "let count be 3\nif count > 1\n    show \"many\"\nend",
+        right: "let count be 3\nif count > 1 then\n    show \"many\"\nend",
+    },
+    Explanation {
+        code: "E0003",
+        title: "Runtime error",
+        description: "Something went wrong while the script ran that has no more specific \
+code. The message says what: typically calling a value that isn't a function, \
+destructuring an array or object that doesn't fit the pattern, or importing a module \
+that can't be loaded, for example because the imports form a cycle.",
+        wrong: "let total be 10\nshow total(2)",
+        right: "let total be 10\nshow total * 2",
+    },
+    Explanation {
+        code: "E0004",
+        title: "Wrong type of value",
+        description: "An operator or built-in function was given a value of a type it \
+doesn't work with, such as subtracting from a string or looping over a number. \
+Values are never converted implicitly, except that `+` joins anything onto a string. \
+Convert explicitly with `to_int`, `to_float` or `to_string`.",
+        wrong: "let input be \"10\"\nshow input - 1",
+        right: "let input be \"10\"\nshow to_int(input) - 1",
+    },
+    Explanation {
+        code: "E0005",
+        title: "Undefined variable",
+        description: "A name was used that no `let`, parameter or import has defined in \
+scope. Check the spelling: the error suggests a defined name when one is close. \
+Variables declared inside a function are not visible outside it.",
+        wrong: "let total be 10\nshow totl + 1",
+        right: "let total be 10\nshow total + 1",
+    },
+    Explanation {
+        code: "E0006",
+        title: "Undefined function",
+        description: "A call named a function that isn't defined with `def`, held in a \
+variable, imported, or provided by the standard library. Check the spelling, and \
+that the module defining it is imported and exports it.",
+        wrong: "def square with n do\n    return n * n\nend\nshow squre(4)",
+        right: "def square with n do\n    return n * n\nend\nshow square(4)",
+    },
+    Explanation {
+        code: "E0007",
+        title: "Unsupported operation",
+        description: "A value was asked to do something it doesn't support, such as \
+calling a method that arrays or objects don't have. The message names the method; \
+see the README for the methods each type provides.",
+        wrong: "let items be [1, 2]\nitems.add(3)",
+        right: "let items be [1, 2]\nitems.push(3)",
+    },
+    Explanation {
+        code: "E0008",
+        title: "Division by zero",
+        description: "A `/`, `//` or `%` had zero on its right-hand side. Check the divisor \
+before dividing when it can be zero, for example when averaging an empty list.",
+        wrong: "def average with total, count do\n    return total / count\nend\nshow average(10, 0)",
+        right: "def average with total, count do\n    if count == 0 then\n        return 0\n    end\n    return total / count\nend\nshow average(10, 0)",
+    },
+    Explanation {
+        code: "E0009",
+        title: "Index out of bounds",
+        description: "An array was read or written at a position it doesn't have. \
+Indexes start at 0, so the last element of an array is at `array.length - 1`, and \
+negative indexes are not allowed. Reading a missing object key is not an error; it \
+gives `null`.",
+        wrong: "let items be [1, 2, 3]\nshow items[3]",
+        right: "let items be [1, 2, 3]\nshow items[items.length - 1]",
+    },
+    Explanation {
+        code: "E0010",
+        title: "Input or output failed",
+        description: "Reading or writing a file failed, either in `read_file` and \
+`write_file` or when loading an imported module. The message includes the reason \
+from the operating system, such as a missing file or a permissions problem. Catch \
+the error with `try` when the file may legitimately be absent.",
+        wrong: "show read_file(\"missing.txt\")",
+        right: "try\n    show read_file(\"missing.txt\")\ncatch err\n    show \"could not read: \" + err.message\nend",
+    },
+    Explanation {
+        code: "E0011",
+        title: "Uncaught thrown value",
+        description: "A value raised with `throw` reached the top of the script without \
+a `catch`. Wrap the code that can throw in `try` ... `catch`; the caught error has \
+`kind`, `message`, `line` and `stack` fields.",
+        wrong: "def withdraw with amount do\n    if amount < 0 then\n        throw \"amount must be positive\"\n    end\n    return amount\nend\nwithdraw(-5)",
+        right: "def withdraw with amount do\n    if amount < 0 then\n        throw \"amount must be positive\"\n    end\n    return amount\nend\ntry\n    withdraw(-5)\ncatch err\n    show err.message\nend",
+    },
+    Explanation {
+        code: "E0012",
+        title: "Wrong number of arguments",
+        description: "A function, lambda, method or built-in function was called with \
+more arguments than it takes, or without one it requires. Parameters with a default \
+value may be left out, and a `...rest` parameter collects any number of extra \
+arguments. The message says how many were expected.",
+        wrong: "def add with a, b do\n    return a + b\nend\nshow add(1, 2, 3)",
+        right: "def add with a, b do\n    return a + b\nend\nshow add(1, 2)",
+    },
+];
+
+/// The entry for a code, written as `E0008`, `e0008` or `8`
+pub fn explain(code: &str) -> Option<&'static Explanation> {
+    let digits = code.trim().trim_start_matches(['E', 'e']);
+    let number: u32 = digits.parse().ok()?;
+    let code = format!("E{:04}", number);
+    EXPLANATIONS.iter().find(|entry| entry.code == code)
+}
+
+impl fmt::Display for Explanation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{}: {}", self.code, self.title)?;
+        writeln!(f)?;
+        writeln!(f, "{}", self.description)?;
+        writeln!(f)?;
+        writeln!(f, "Erroneous code example:")?;
+        writeln!(f)?;
+        for line in self.wrong.lines() {
+            writeln!(f, "    {}", line)?;
+        }
+        writeln!(f)?;
+        writeln!(f, "Corrected:")?;
+        writeln!(f)?;
+        for line in self.right.lines() {
+            writeln!(f, "    {}", line)?;
+        }
+        Ok(())
+    }
+}
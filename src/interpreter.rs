@@ -1,8 +1,15 @@
 use crate::ast::*;
-use crate::error::{FlowError, Result};
+use crate::error::{FlowError, Result, StackFrame};
+use crate::diagnostic::closest_match;
+use crate::destructure;
+use crate::methods;
+use crate::operators;
 use crate::value::{Value, FlowArray, FlowObject, Environment};
 use crate::stdlib::StandardLibrary;
+use crate::lexer::Lexer;
+use crate::parser::Parser;
 use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 
 impl Value {
     pub fn is_truthy(&self) -> bool {
@@ -42,10 +49,50 @@ impl Value {
 
 
 
+/// Where the error now unwinding was raised: the innermost statement or
+/// expression it came out of, a suggested fix if there is one, and the
+/// imported module's file when it wasn't raised in the main script
+struct ErrorOrigin {
+    span: Span,
+    hint: Option<String>,
+    file: Option<PathBuf>,
+}
+
+/// The target of an assignment, with its container and index already evaluated
+enum Place {
+    Variable(String),
+    Index(Value, Value),
+    Property(Value, String),
+}
+
 pub struct Interpreter {
     environment: Environment,
     return_value: Option<Value>,
     stdlib: StandardLibrary,
+    /// Directory of the file being run; relative imports resolve against it
+    current_dir: Option<PathBuf>,
+    /// Directory of the main script, searched first for bare module names
+    project_dir: Option<PathBuf>,
+    /// Library directories searched for bare module names, in order
+    module_paths: Vec<PathBuf>,
+    /// Loaded file modules by canonical path, so each runs only once
+    modules: HashMap<PathBuf, FlowObject>,
+    /// Files currently being loaded, outermost first, for cycle detection
+    loading: Vec<PathBuf>,
+    /// Names exported so far by the file being run
+    exports: Vec<String>,
+    /// The functions being called and where from, outermost first
+    call_stack: Vec<StackFrame>,
+    /// The call stack where the error now unwinding was raised
+    error_stack: Option<Vec<StackFrame>>,
+    /// Where the unwinding error was raised
+    error_origin: Option<ErrorOrigin>,
+    /// `error_origin` of the last error to escape `execute`
+    last_error_origin: Option<ErrorOrigin>,
+    /// Stack trace of the last error to escape `execute`, innermost call first
+    last_trace: Vec<StackFrame>,
+    /// Errors bound by the `catch` blocks being run, for bare `throw`
+    caught_errors: Vec<Value>,
 }
 
 impl Interpreter {
@@ -54,6 +101,18 @@ impl Interpreter {
             environment: Environment::new(),
             return_value: None,
             stdlib: StandardLibrary::new(),
+            current_dir: None,
+            project_dir: None,
+            module_paths: Vec::new(),
+            modules: HashMap::new(),
+            loading: Vec::new(),
+            exports: Vec::new(),
+            call_stack: Vec::new(),
+            error_stack: None,
+            error_origin: None,
+            last_error_origin: None,
+            last_trace: Vec::new(),
+            caught_errors: Vec::new(),
         };
         
         // Add built-in functions
@@ -66,7 +125,50 @@ impl Interpreter {
         // No need to pre-register them in the environment
     }
     
+    /// Tell the interpreter which file it is running, so that its imports
+    /// resolve relative to it and importing it back is caught as a cycle
+    pub fn set_script_path(&mut self, path: impl AsRef<Path>) {
+        let path = path.as_ref();
+        let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+        self.current_dir = path.parent().map(Path::to_path_buf);
+        self.project_dir = self.current_dir.clone();
+        self.loading = vec![path];
+    }
+    
+    /// Add a library directory to search for `import name`, after the
+    /// project directory and any directories added before it
+    pub fn add_module_path(&mut self, dir: impl Into<PathBuf>) {
+        self.module_paths.push(dir.into());
+    }
+    
+    /// Add the directories listed in the `FLOW_PATH` environment variable
+    pub fn add_module_paths_from_env(&mut self) {
+        if let Some(flow_path) = std::env::var_os("FLOW_PATH") {
+            for dir in std::env::split_paths(&flow_path) {
+                if !dir.as_os_str().is_empty() {
+                    self.add_module_path(dir);
+                }
+            }
+        }
+    }
+    
+    /// Every directory searched for bare module names: the project
+    /// directory (or the working directory) then the library paths
+    pub fn module_search_path(&self) -> Vec<PathBuf> {
+        let project_dir = self.project_dir.clone()
+            .or_else(|| std::env::current_dir().ok())
+            .unwrap_or_else(|| PathBuf::from("."));
+        std::iter::once(project_dir)
+            .chain(self.module_paths.iter().cloned())
+            .collect()
+    }
+    
     fn handle_import(&mut self, module_path: &str, imports: &ImportType) -> Result<()> {
+        if is_file_module(module_path) {
+            let path = self.resolve_relative_module(module_path)?;
+            return self.handle_file_import(module_path, path, imports);
+        }
+        
         match module_path {
             "std" => self.handle_std_import(imports),
             "io" => self.handle_io_import(imports),
@@ -78,7 +180,8 @@ impl Interpreter {
             "dir" => self.handle_dir_import(imports),
             "http" => self.handle_http_import(imports),
             _ => {
-                return Err(FlowError::runtime_error(format!("Unknown module: {}", module_path)));
+                let path = self.find_module_on_search_path(module_path)?;
+                self.handle_file_import(module_path, path, imports)
             }
         }
     }
@@ -87,12 +190,13 @@ impl Interpreter {
         match imports {
             ImportType::All => {
                 // Create a module object with all std functions
-                let mut module_obj = FlowObject::new();
+                let module_obj = FlowObject::new();
                 for func_name in self.get_std_function_names() {
                     let func = Value::Function {
                         name: func_name.clone(),
                         parameters: vec![],
                         body: vec![],
+                        closure: None,
                     };
                     module_obj.set(func_name, func);
                 }
@@ -107,6 +211,7 @@ impl Interpreter {
                             name: func_name.clone(),
                             parameters: vec![], // Will be handled dynamically
                             body: vec![], // Stdlib functions don't have AST bodies
+                            closure: None,
                         };
                         self.environment.define_variable(func_name.clone(), func);
                     } else {
@@ -120,12 +225,13 @@ impl Interpreter {
             }
             ImportType::Aliased(_, alias) => {
                 // Create a module object with all std functions
-                let mut module_obj = FlowObject::new();
+                let module_obj = FlowObject::new();
                 for func_name in self.get_std_function_names() {
                     let func = Value::Function {
                         name: func_name.clone(),
                         parameters: vec![],
                         body: vec![],
+                        closure: None,
                     };
                     module_obj.set(func_name, func);
                 }
@@ -139,6 +245,7 @@ impl Interpreter {
                             name: func_name.clone(),
                             parameters: vec![],
                             body: vec![],
+                            closure: None,
                         };
                         self.environment.define_variable(alias.clone(), func);
                     } else {
@@ -201,13 +308,14 @@ impl Interpreter {
     ) -> Result<()> {
         match imports {
             ImportType::All => {
-                let mut module_obj = FlowObject::new();
+                let module_obj = FlowObject::new();
                 for func_name in available_functions {
                     if self.stdlib.has_function(func_name) {
                         let func = Value::Function {
                             name: func_name.to_string(),
                             parameters: vec![],
                             body: vec![],
+                            closure: None,
                         };
                         module_obj.set(func_name.to_string(), func);
                     }
@@ -223,6 +331,7 @@ impl Interpreter {
                                 name: func_name.clone(),
                                 parameters: vec![],
                                 body: vec![],
+                                closure: None,
                             };
                             self.environment.define_variable(func_name.clone(), func);
                         }
@@ -237,13 +346,14 @@ impl Interpreter {
                 Ok(())
             }
             ImportType::Aliased(_, alias) => {
-                let mut module_obj = FlowObject::new();
+                let module_obj = FlowObject::new();
                 for func_name in available_functions {
                     if self.stdlib.has_function(func_name) {
                         let func = Value::Function {
                             name: func_name.to_string(),
                             parameters: vec![],
                             body: vec![],
+                            closure: None,
                         };
                         module_obj.set(func_name.to_string(), func);
                     }
@@ -259,6 +369,7 @@ impl Interpreter {
                                 name: func_name.clone(),
                                 parameters: vec![],
                                 body: vec![],
+                                closure: None,
                             };
                             self.environment.define_variable(alias.clone(), func);
                         }
@@ -275,6 +386,139 @@ impl Interpreter {
         }
     }
     
+    fn handle_file_import(&mut self, module_path: &str, path: PathBuf, imports: &ImportType) -> Result<()> {
+        let module = self.load_file_module(path)?;
+        let exported = |name: &str| module.get(name).ok_or_else(|| {
+            FlowError::runtime_error(format!("'{}' is not exported by module '{}'", name, module_path))
+        });
+        
+        match imports {
+            ImportType::All => {
+                let name = Path::new(module_path)
+                    .file_stem()
+                    .map(|stem| stem.to_string_lossy().into_owned())
+                    .unwrap_or_else(|| module_path.to_string());
+                self.environment.define_variable(name, Value::Object(module));
+            }
+            ImportType::Aliased(_, alias) => {
+                self.environment.define_variable(alias.clone(), Value::Object(module));
+            }
+            ImportType::Specific(names) => {
+                for name in names {
+                    let value = exported(name)?;
+                    self.environment.define_variable(name.clone(), value);
+                }
+            }
+            ImportType::SpecificAliased(names) => {
+                for (name, alias) in names {
+                    let value = exported(name)?;
+                    self.environment.define_variable(alias.clone(), value);
+                }
+            }
+        }
+        Ok(())
+    }
+    
+    /// Resolve a quoted import path against the importing file's directory
+    fn resolve_relative_module(&self, module_path: &str) -> Result<PathBuf> {
+        let mut path = match &self.current_dir {
+            Some(dir) => dir.join(module_path),
+            None => PathBuf::from(module_path),
+        };
+        if path.extension().is_none() {
+            path.set_extension("flow");
+        }
+        path.canonicalize().map_err(|_| {
+            FlowError::runtime_error(format!("Module '{}' not found at {}", module_path, path.display()))
+        })
+    }
+    
+    /// Find `<name>.flow` in the first search path directory that has it
+    fn find_module_on_search_path(&self, name: &str) -> Result<PathBuf> {
+        let search_path = self.module_search_path();
+        let file_name = format!("{}.flow", name);
+        
+        for dir in &search_path {
+            let candidate = dir.join(&file_name);
+            if candidate.is_file() {
+                return candidate.canonicalize().map_err(FlowError::from);
+            }
+        }
+        
+        let searched: Vec<String> = search_path.iter().map(|dir| dir.display().to_string()).collect();
+        Err(FlowError::runtime_error(format!(
+            "Unknown module: {} (no {} in {})",
+            name,
+            file_name,
+            searched.join(", ")
+        )))
+    }
+    
+    /// Load a file module, running it in a fresh global scope the first time
+    /// it is imported, and return an object holding its exported names
+    fn load_file_module(&mut self, path: PathBuf) -> Result<FlowObject> {
+        if let Some(module) = self.modules.get(&path) {
+            return Ok(module.clone());
+        }
+        
+        if let Some(start) = self.loading.iter().position(|loading| *loading == path) {
+            let cycle: Vec<String> = self.loading[start..]
+                .iter()
+                .chain(std::iter::once(&path))
+                .map(|path| display_module_path(path))
+                .collect();
+            return Err(FlowError::runtime_error(format!("Circular import: {}", cycle.join(" -> "))));
+        }
+        
+        let source = std::fs::read_to_string(&path).map_err(|err| FlowError::IoError {
+            message: format!("Failed to read module '{}': {}", path.display(), err),
+        })?;
+        let program = match Lexer::new(&source).tokenize().and_then(|tokens| Parser::new(tokens).parse()) {
+            Ok(program) => program,
+            Err(error) => {
+                // Point at the mistake in the module rather than at the import
+                let position = Position {
+                    line: error.line().unwrap_or(0),
+                    column: error.column().unwrap_or(0),
+                    offset: 0,
+                };
+                self.error_origin = Some(ErrorOrigin {
+                    span: Span { start: position, end: position },
+                    hint: None,
+                    file: Some(path),
+                });
+                return Err(error);
+            }
+        };
+        
+        let saved_env = std::mem::replace(&mut self.environment, Environment::for_file(path.clone()));
+        let saved_dir = std::mem::replace(&mut self.current_dir, path.parent().map(Path::to_path_buf));
+        let saved_exports = std::mem::take(&mut self.exports);
+        self.loading.push(path.clone());
+        
+        let result = self.execute(&program);
+        
+        self.loading.pop();
+        let module_env = std::mem::replace(&mut self.environment, saved_env);
+        self.current_dir = saved_dir;
+        let exports = std::mem::replace(&mut self.exports, saved_exports);
+        if result.is_err() {
+            // `execute` has set the error's origin aside; keep it as where
+            // the import failed
+            self.error_origin = self.last_error_origin.take();
+        }
+        result?;
+        
+        let module = FlowObject::new();
+        for name in exports {
+            if let Some(value) = module_env.get_function(&name).or_else(|| module_env.get_variable(&name)) {
+                module.set(name, value);
+            }
+        }
+        self.modules.insert(path, module.clone());
+        Ok(module)
+    }
+    
     fn get_std_function_names(&self) -> Vec<String> {
         // Return all available standard library function names
         vec![
@@ -298,66 +542,162 @@ impl Interpreter {
     }
     
     pub fn execute(&mut self, program: &Program) -> Result<()> {
+        self.error_stack = None;
+        self.error_origin = None;
         for statement in &program.statements {
             match self.execute_statement(statement) {
                 Ok(_) => {},
                 Err(FlowError::Return { value }) => {
                     return Ok(());
                 }
-                Err(e) => return Err(e),
+                Err(e) => {
+                    self.last_trace = self.error_stack.clone().unwrap_or_default();
+                    self.last_error_origin = self.error_origin.take();
+                    return Err(e);
+                }
             }
         }
         Ok(())
     }
     
+    /// The calls that were active when the last error escaped `execute`,
+    /// innermost first. Empty if the error was raised outside any function.
+    pub fn stack_trace(&self) -> &[StackFrame] {
+        &self.last_trace
+    }
+    
+    /// The source of the innermost expression or statement the last error to
+    /// escape `execute` was raised in
+    pub fn error_span(&self) -> Option<Span> {
+        self.last_error_origin.as_ref().map(|origin| origin.span)
+    }
+    
+    /// The file of the imported module the last error to escape `execute`
+    /// was raised in, or None if it was raised in the main script
+    pub fn error_file(&self) -> Option<&Path> {
+        self.last_error_origin.as_ref().and_then(|origin| origin.file.as_deref())
+    }
+    
+    /// A suggested fix for the last error to escape `execute`, such as the
+    /// name an undefined one is probably a misspelling of
+    pub fn error_hint(&self) -> Option<&str> {
+        self.last_error_origin.as_ref().and_then(|origin| origin.hint.as_deref())
+    }
+    
     pub fn execute_statement(&mut self, statement: &Statement) -> Result<()> {
+        let result = self.execute_statement_kind(&statement.kind);
+        self.locate_error(result, statement.span)
+    }
+    
+    /// Note where an error was raised if nothing inside `span` has already,
+    /// and give the error that line if it has none
+    fn locate_error<T>(&mut self, result: Result<T>, span: Span) -> Result<T> {
+        match result {
+            Err(error) if !error.is_control_flow() => {
+                if self.error_origin.is_none() {
+                    self.error_origin = Some(ErrorOrigin {
+                        span,
+                        hint: self.hint_for(&error),
+                        file: self.environment.file(),
+                    });
+                }
+                Err(error.with_line(span.start.line))
+            }
+            result => result,
+        }
+    }
+    
+    /// Suggest a defined name close to an undefined one, from the scope the
+    /// error was raised in
+    fn hint_for(&self, error: &FlowError) -> Option<String> {
+        let name = match error {
+            FlowError::UndefinedVariable { name } | FlowError::UndefinedFunction { name } => name,
+            _ => return None,
+        };
+        let mut candidates = self.environment.names();
+        candidates.extend(self.stdlib.functions.keys().cloned());
+        closest_match(name, candidates.iter().map(String::as_str))
+            .map(|candidate| format!("did you mean `{}`?", candidate))
+    }
+    
+    fn execute_statement_kind(&mut self, statement: &StatementKind) -> Result<()> {
         match statement {
-            Statement::VariableDeclaration { name, value } => {
+            StatementKind::VariableDeclaration { name, value } => {
                 let val = self.evaluate_expression(value)?;
-                self.environment.define_variable(name.clone(), val);
+                self.environment.declare_variable(name.clone(), val);
                 Ok(())
             }
             
-            Statement::FunctionDeclaration { name, parameters, body } => {
+            StatementKind::Destructuring { pattern, value } => {
+                let val = self.evaluate_expression(value)?;
+                self.bind_pattern(pattern, val)
+            }
+            
+            StatementKind::FunctionDeclaration { name, parameters, body } => {
+                // Like lambdas, functions share the scope they are declared in,
+                // which also lets them see themselves and later declarations
                 let func = Value::Function {
                     name: name.clone(),
                     parameters: parameters.clone(),
                     body: body.clone(),
+                    closure: Some(self.environment.clone()),
                 };
                 self.environment.define_function(name.clone(), func);
                 Ok(())
             }
             
-            Statement::Export(statement) => {
-                // For now, just execute the statement
-                // In a full implementation, this would register the export
-                self.execute_statement(statement)
+            StatementKind::Export(statement) => {
+                self.execute_statement(statement)?;
+                match &statement.kind {
+                    StatementKind::VariableDeclaration { name, .. }
+                    | StatementKind::FunctionDeclaration { name, .. } => self.exports.push(name.clone()),
+                    StatementKind::Destructuring { pattern, .. } => self.exports.extend(pattern.names()),
+                    _ => {}
+                }
+                Ok(())
             }
             
-            Statement::TryCatch { try_block, catch_variable, catch_block } => {
-                // Execute try block
-                for stmt in try_block {
-                    match self.execute_statement(stmt) {
-                        Ok(_) => continue,
-                        Err(error) => {
-                            // Catch the error and execute catch block
-                            self.environment.define_variable(
-                                catch_variable.clone(),
-                                Value::String(error.to_string())
-                            );
-                            
-                            for catch_stmt in catch_block {
-                                self.execute_statement(catch_stmt)?;
-                            }
-                            
-                            return Ok(());
-                        }
+            StatementKind::TryCatch { try_block, catch_clause, finally_block } => {
+                self.error_stack = None;
+                self.error_origin = None;
+                let result = match (self.execute_block(try_block), catch_clause) {
+                    (Err(error), Some(catch_clause)) if !error.is_control_flow() => {
+                        // Bind the error as an object and run the catch block
+                        let stack = self.error_stack.take().unwrap_or_else(|| self.current_stack());
+                        let line = self.error_origin.take().map(|origin| origin.span.start.line);
+                        let error_value = error.to_value(&stack, line);
+                        self.environment.declare_variable(catch_clause.variable.clone(), error_value.clone());
+                        
+                        self.caught_errors.push(error_value);
+                        let result = self.execute_block(&catch_clause.block);
+                        self.caught_errors.pop();
+                        result
                     }
+                    (result, _) => result,
+                };
+                
+                // `finally` runs on every way out, and its own errors win
+                if let Some(finally_block) = finally_block {
+                    self.execute_block(finally_block)?;
                 }
-                Ok(())
+                result
             }
             
-            Statement::If { condition, then_branch, else_branch } => {
+            StatementKind::Throw(value) => {
+                let error = match value {
+                    Some(value) => {
+                        let value = self.evaluate_expression(value)?;
+                        FlowError::thrown(value, &self.current_stack())
+                    }
+                    None => match self.caught_errors.last() {
+                        Some(caught) => FlowError::Thrown { value: caught.clone() },
+                        None => return Err(FlowError::runtime_error("'throw' without a value can only be used inside 'catch'")),
+                    },
+                };
+                Err(error)
+            }
+            
+            StatementKind::If { condition, then_branch, else_branch } => {
                 let condition_value = self.evaluate_expression(condition)?;
                 
                 if condition_value.is_truthy() {
@@ -378,47 +718,97 @@ impl Interpreter {
                 Ok(())
             }
             
-            Statement::While { condition, body } => {
+            StatementKind::While { condition, body } => {
                 while self.evaluate_expression(condition)?.is_truthy() {
-                    for stmt in body {
-                        self.execute_statement(stmt)?;
-                        if self.return_value.is_some() {
-                            return Ok(());
-                        }
+                    if !self.execute_loop_body(Vec::new(), body)? {
+                        break;
                     }
                 }
                 Ok(())
             }
             
-            Statement::For { variable, start, end, body } => {
+            StatementKind::For { variable, start, end, step, body } => {
                 let start_val = self.evaluate_expression(start)?;
                 let end_val = self.evaluate_expression(end)?;
-                
                 let (start_int, end_int) = match (start_val, end_val) {
                     (Value::Integer(s), Value::Integer(e)) => (s, e),
                     _ => return Err(FlowError::type_error("For loop bounds must be integers")),
                 };
+                // Without `by`, count towards the end in whichever direction it lies
+                let step_val = match step {
+                    Some(step) => self.evaluate_expression(step)?,
+                    None if start_int > end_int => Value::Integer(-1),
+                    None => Value::Integer(1),
+                };
+                let step_int = match step_val {
+                    Value::Integer(0) => return Err(FlowError::runtime_error("For loop step cannot be zero")),
+                    Value::Integer(s) => s,
+                    _ => return Err(FlowError::type_error("For loop step must be an integer")),
+                };
                 
-                for i in start_int..=end_int {
-                    self.environment.define_variable(variable.clone(), Value::Integer(i));
+                let mut i = start_int;
+                while (step_int > 0 && i <= end_int) || (step_int < 0 && i >= end_int) {
+                    if !self.execute_loop_body(vec![(variable, Value::Integer(i))], body)? {
+                        break;
+                    }
                     
-                    for stmt in body {
-                        self.execute_statement(stmt)?;
-                        if self.return_value.is_some() {
-                            return Ok(());
+                    i = match i.checked_add(step_int) {
+                        Some(next) => next,
+                        None => break,
+                    };
+                }
+                Ok(())
+            }
+            
+            StatementKind::ForIn { variable, value_variable, iterable, body } => {
+                let collection = self.evaluate_expression(iterable)?;
+                let not_iterable = || FlowError::type_error(format!("Cannot iterate over a value of type {}", collection.type_name()));
+                
+                match value_variable {
+                    Some(value_variable) => {
+                        let entries = collection.iteration_entries().ok_or_else(not_iterable)?;
+                        for (key, item) in entries {
+                            if !self.execute_loop_body(vec![(variable, key), (value_variable, item)], body)? {
+                                break;
+                            }
+                        }
+                    }
+                    None => {
+                        let items = collection.iteration_items().ok_or_else(not_iterable)?;
+                        for item in items {
+                            if !self.execute_loop_body(vec![(variable, item)], body)? {
+                                break;
+                            }
                         }
                     }
                 }
                 Ok(())
             }
             
-            Statement::Show(expression) => {
+            StatementKind::Assignment { target, operator, value } => {
+                let place = self.evaluate_place(target)?;
+                let new_value = match operator {
+                    Some(op) => {
+                        let current = match &place {
+                            Place::Variable(_) => self.evaluate_expression(target)?,
+                            Place::Index(container, index) => self.get_index(container, index)?,
+                            Place::Property(container, property) => operators::get_property(container, property)?,
+                        };
+                        let rhs = self.evaluate_expression(value)?;
+                        self.apply_binary_operator(&current, op, &rhs)?
+                    }
+                    None => self.evaluate_expression(value)?,
+                };
+                self.assign_to(place, new_value)
+            }
+
+            StatementKind::Show(expression) => {
                 let value = self.evaluate_expression(expression)?;
                 println!("{}", value);
                 Ok(())
             }
             
-            Statement::Return(expression) => {
+            StatementKind::Return(expression) => {
                 let value = if let Some(expr) = expression {
                     self.evaluate_expression(expr)?
                 } else {
@@ -427,83 +817,166 @@ impl Interpreter {
                 return Err(FlowError::return_value(value));
             }
             
-            Statement::Expression(expression) => {
+            StatementKind::Break => Err(FlowError::Break),
+            
+            StatementKind::Continue => Err(FlowError::Continue),
+            
+            StatementKind::Expression(expression) => {
                 self.evaluate_expression(expression)?;
                 Ok(())
             }
             
-            Statement::Import { module_path, imports } => {
+            StatementKind::Import { module_path, imports } => {
                 self.handle_import(module_path, imports)
             }
         }
     }
     
+    /// Execute statements in the current scope, stopping at the first error
+    fn execute_block(&mut self, statements: &[Statement]) -> Result<()> {
+        for statement in statements {
+            self.execute_statement(statement)?;
+        }
+        Ok(())
+    }
+    
+    /// The functions being called, innermost first
+    fn current_stack(&self) -> Vec<StackFrame> {
+        self.call_stack.iter().rev().cloned().collect()
+    }
+    
+    /// Record where an error was raised before the frames it passes through are left
+    fn note_error_stack<T>(&mut self, result: &Result<T>) {
+        if let Err(error) = result {
+            if !error.is_control_flow() && self.error_stack.is_none() {
+                self.error_stack = Some(self.current_stack());
+            }
+        }
+    }
+    
+    /// Execute one iteration of a loop body.
+    ///
+    /// `break` and `continue` unwind as `FlowError::Break`/`FlowError::Continue`
+    /// the same way `return` does; returns `false` when the loop should stop.
+    fn execute_loop_body(&mut self, bindings: Vec<(&String, Value)>, body: &[Statement]) -> Result<bool> {
+        // Each iteration gets its own scope, so closures created in it keep
+        // that iteration's loop variables and `let` declarations
+        self.environment.push_block_scope();
+        for (name, value) in bindings {
+            self.environment.define_variable(name.clone(), value);
+        }
+        
+        let mut result = Ok(true);
+        for stmt in body {
+            result = match self.execute_statement(stmt) {
+                Ok(()) if self.return_value.is_some() => Ok(false),
+                Ok(()) => continue,
+                Err(FlowError::Break) => Ok(false),
+                Err(FlowError::Continue) => Ok(true),
+                Err(e) => Err(e),
+            };
+            break;
+        }
+        
+        self.environment.pop_scope();
+        result
+    }
+    
     pub fn evaluate_expression(&mut self, expression: &Expression) -> Result<Value> {
+        let result = self.evaluate_expression_kind(&expression.kind);
+        self.locate_error(result, expression.span)
+    }
+    
+    fn evaluate_expression_kind(&mut self, expression: &ExpressionKind) -> Result<Value> {
         match expression {
-            Expression::Literal(literal) => Ok(self.literal_to_value(literal)),
+            ExpressionKind::Literal(literal) => Ok(self.literal_to_value(literal)),
             
-            Expression::Identifier(name) => {
+            ExpressionKind::Identifier(name) => {
+                // A `def` name used as a value evaluates to the function itself
                 self.environment.get_variable(name)
-                    .cloned()
+                    .or_else(|| self.environment.get_function(name))
                     .ok_or_else(|| FlowError::undefined_variable(name.to_string()))
             }
             
-            Expression::Binary { left, operator, right } => {
+            ExpressionKind::Binary { left, operator, right } => {
                 let left_val = self.evaluate_expression(left)?;
+                if *operator == BinaryOperator::NullCoalesce && !matches!(left_val, Value::Null) {
+                    return Ok(left_val);
+                }
                 let right_val = self.evaluate_expression(right)?;
                 self.apply_binary_operator(&left_val, operator, &right_val)
             }
             
-            Expression::Unary { operator, operand } => {
+            ExpressionKind::Unary { operator, operand } => {
                 let operand_val = self.evaluate_expression(operand)?;
                 self.apply_unary_operator(operator, &operand_val)
             }
             
-            Expression::FunctionCall { name, arguments } => {
-                self.call_function(name, arguments)
+            ExpressionKind::FunctionCall { name, arguments, position } => {
+                self.call_function(name, arguments, *position)
             }
             
-            Expression::MethodCall { object, method, arguments } => {
-                let obj_val = self.evaluate_expression(object)?;
-                self.call_method(&obj_val, method, arguments)
+            ExpressionKind::MethodCall { .. }
+            | ExpressionKind::Index { .. }
+            | ExpressionKind::PropertyAccess { .. } => {
+                Ok(self.evaluate_access(expression)?.unwrap_or(Value::Null))
             }
             
-            Expression::Array { elements } => {
-                let mut array_elements = Vec::new();
-                for element in elements {
-                    let value = self.evaluate_expression(element)?;
-                    array_elements.push(value);
-                }
+            ExpressionKind::Array { elements } => {
+                let array_elements = self.evaluate_arguments(elements)?;
                 Ok(Value::Array(FlowArray::from_values(array_elements)))
             }
             
-            Expression::Object { properties } => {
+            ExpressionKind::Object { properties } => {
                 let mut object_properties = HashMap::new();
-                for (key, value_expr) in properties {
-                    let value = self.evaluate_expression(value_expr)?;
-                    object_properties.insert(key.clone(), value);
+                for property in properties {
+                    match property {
+                        ObjectProperty::KeyValue(key, value_expr) => {
+                            let value = self.evaluate_expression(value_expr)?;
+                            object_properties.insert(key.clone(), value);
+                        }
+                        ObjectProperty::Spread(source) => {
+                            let source = self.evaluate_expression(source)?;
+                            object_properties.extend(operators::spread_entries(&source)?);
+                        }
+                    }
                 }
                 Ok(Value::Object(FlowObject::from_map(object_properties)))
             }
             
-            Expression::Index { object, index } => {
-                let obj_val = self.evaluate_expression(object)?;
-                let index_val = self.evaluate_expression(index)?;
-                self.get_index(&obj_val, &index_val)
-            }
+            ExpressionKind::Spread(_) => Err(FlowError::runtime_error(
+                "'...' can only be used in arrays, objects and call arguments",
+            )),
             
-            Expression::PropertyAccess { object, property } => {
-                let obj_val = self.evaluate_expression(object)?;
-                self.get_property(&obj_val, property)
+            ExpressionKind::Conditional { condition, then_branch, else_branch } => {
+                if self.evaluate_expression(condition)?.is_truthy() {
+                    self.evaluate_expression(then_branch)
+                } else {
+                    self.evaluate_expression(else_branch)
+                }
             }
             
-            Expression::Lambda { parameters, body } => {
+            ExpressionKind::Lambda { parameters, body } => {
                 Ok(Value::Lambda {
                     parameters: parameters.clone(),
                     body: body.clone(),
                     closure: self.environment.clone(),
                 })
             }
+            
+            ExpressionKind::InterpolatedString { parts } => {
+                let mut result = String::new();
+                for part in parts {
+                    match part {
+                        InterpolationPart::Literal(text) => result.push_str(text),
+                        InterpolationPart::Expression(expression) => {
+                            let value = self.evaluate_expression(expression)?;
+                            result.push_str(&value.to_string());
+                        }
+                    }
+                }
+                Ok(Value::String(result))
+            }
         }
     }
     
@@ -633,51 +1106,33 @@ impl Interpreter {
                 _ => Err(FlowError::type_error(format!("Cannot divide {} and {}", left.type_name(), right.type_name()))),
             },
             
-            BinaryOperator::Modulo => match (left, right) {
-                (Value::Integer(a), Value::Integer(b)) => {
-                    if *b == 0 {
-                        Err(FlowError::DivisionByZero)
-                    } else {
-                        Ok(Value::Integer(a % b))
-                    }
-                },
-                _ => Err(FlowError::type_error(format!("Cannot modulo {} and {}", left.type_name(), right.type_name()))),
-            },
-            
-            BinaryOperator::Equal => Ok(Value::Boolean(self.values_equal(left, right))),
-            BinaryOperator::NotEqual => Ok(Value::Boolean(!self.values_equal(left, right))),
-            
-            BinaryOperator::Greater => match (left, right) {
-                (Value::Integer(a), Value::Integer(b)) => Ok(Value::Boolean(a > b)),
-                (Value::Float(a), Value::Float(b)) => Ok(Value::Boolean(a > b)),
-                (Value::Integer(a), Value::Float(b)) => Ok(Value::Boolean(*a as f64 > *b)),
-                (Value::Float(a), Value::Integer(b)) => Ok(Value::Boolean(*a > *b as f64)),
-                _ => Err(FlowError::type_error(format!("Cannot compare {} and {}", left.type_name(), right.type_name()))),
-            },
-            
-            BinaryOperator::GreaterEqual => match (left, right) {
-                (Value::Integer(a), Value::Integer(b)) => Ok(Value::Boolean(a >= b)),
-                (Value::Float(a), Value::Float(b)) => Ok(Value::Boolean(a >= b)),
-                (Value::Integer(a), Value::Float(b)) => Ok(Value::Boolean(*a as f64 >= *b)),
-                (Value::Float(a), Value::Integer(b)) => Ok(Value::Boolean(*a >= *b as f64)),
-                _ => Err(FlowError::type_error(format!("Cannot compare {} and {}", left.type_name(), right.type_name()))),
-            },
+            BinaryOperator::Modulo => operators::modulo(left, right),
+            BinaryOperator::Power => operators::power(left, right),
+            BinaryOperator::IntegerDivide => operators::floor_divide(left, right),
+            BinaryOperator::BitAnd | BinaryOperator::BitOr | BinaryOperator::BitXor => {
+                operators::bitwise(operator, left, right)
+            }
+            BinaryOperator::ShiftLeft => operators::shift_left(left, right),
+            BinaryOperator::ShiftRight => operators::shift_right(left, right),
+            BinaryOperator::In => Ok(Value::Boolean(operators::contains(left, right)?)),
             
-            BinaryOperator::Less => match (left, right) {
-                (Value::Integer(a), Value::Integer(b)) => Ok(Value::Boolean(a < b)),
-                (Value::Float(a), Value::Float(b)) => Ok(Value::Boolean(a < b)),
-                (Value::Integer(a), Value::Float(b)) => Ok(Value::Boolean((*a as f64) < *b)),
-                (Value::Float(a), Value::Integer(b)) => Ok(Value::Boolean(*a < (*b as f64))),
-                _ => Err(FlowError::type_error(format!("Cannot compare {} and {}", left.type_name(), right.type_name()))),
-            },
+            BinaryOperator::Equal => Ok(Value::Boolean(left.equals(right))),
+            BinaryOperator::NotEqual => Ok(Value::Boolean(!left.equals(right))),
             
-            BinaryOperator::LessEqual => match (left, right) {
-                (Value::Integer(a), Value::Integer(b)) => Ok(Value::Boolean(a <= b)),
-                (Value::Float(a), Value::Float(b)) => Ok(Value::Boolean(a <= b)),
-                (Value::Integer(a), Value::Float(b)) => Ok(Value::Boolean((*a as f64) <= *b)),
-                (Value::Float(a), Value::Integer(b)) => Ok(Value::Boolean(*a <= (*b as f64))),
-                _ => Err(FlowError::type_error(format!("Cannot compare {} and {}", left.type_name(), right.type_name()))),
-            },
+            BinaryOperator::Greater
+            | BinaryOperator::GreaterEqual
+            | BinaryOperator::Less
+            | BinaryOperator::LessEqual => {
+                let ordering = left.partial_compare(right).ok_or_else(|| {
+                    FlowError::type_error(format!("Cannot compare {} and {}", left.type_name(), right.type_name()))
+                })?;
+                Ok(Value::Boolean(match operator {
+                    BinaryOperator::Greater => ordering.is_gt(),
+                    BinaryOperator::GreaterEqual => ordering.is_ge(),
+                    BinaryOperator::Less => ordering.is_lt(),
+                    _ => ordering.is_le(),
+                }))
+            }
             
             BinaryOperator::And => {
                 let left_truthy = left.is_truthy();
@@ -696,6 +1151,11 @@ impl Interpreter {
                     Ok(right.clone())
                 }
             },
+            
+            BinaryOperator::NullCoalesce => match left {
+                Value::Null => Ok(right.clone()),
+                _ => Ok(left.clone()),
+            },
         }
     }
     
@@ -707,67 +1167,131 @@ impl Interpreter {
                 Value::Float(f) => Ok(Value::Float(-f)),
                 _ => Err(FlowError::type_error(format!("Cannot negate {}", operand.type_name()))),
             },
+            UnaryOperator::BitNot => operators::bit_not(operand),
         }
     }
     
-    fn values_equal(&self, left: &Value, right: &Value) -> bool {
-        match (left, right) {
-            (Value::String(a), Value::String(b)) => a == b,
-            (Value::Integer(a), Value::Integer(b)) => a == b,
-            (Value::Float(a), Value::Float(b)) => (a - b).abs() < f64::EPSILON,
-            (Value::Integer(a), Value::Float(b)) => (*a as f64 - b).abs() < f64::EPSILON,
-            (Value::Float(a), Value::Integer(b)) => (a - *b as f64).abs() < f64::EPSILON,
-            (Value::Boolean(a), Value::Boolean(b)) => a == b,
-            (Value::Null, Value::Null) => true,
-            _ => false,
-        }
-    }
-    
-    fn call_function(&mut self, name: &str, arguments: &[Expression]) -> Result<Value> {
+    fn call_function(&mut self, name: &str, arguments: &[Expression], position: Position) -> Result<Value> {
         // First check functions and variables for lambda values
         let function = if let Some(func) = self.environment.get_function(name) {
-            func.clone()
+            func
         } else if let Some(var) = self.environment.get_variable(name) {
-            var.clone()
+            var
         } else {
             // If not found in environment, check if it's a stdlib function
             if self.stdlib.has_function(name) {
                 // Evaluate arguments for stdlib function
-                let mut args = Vec::new();
-                for arg in arguments {
-                    args.push(self.evaluate_expression(arg)?);
-                }
+                let args = self.evaluate_arguments(arguments)?;
                 return self.stdlib.call_function(name, &args);
             }
             return Err(FlowError::undefined_function(name));
         };
         
+        let args = self.evaluate_arguments(arguments)?;
+        
         match function {
-            Value::Function { name: func_name, parameters, body } => {
-                // If this is a stdlib function wrapper, call the stdlib
-                if self.stdlib.has_function(&func_name) {
-                    let mut args = Vec::new();
-                    for arg in arguments {
-                        args.push(self.evaluate_expression(arg)?);
-                    }
-                    return self.stdlib.call_function(&func_name, &args);
+            Value::Function { .. } | Value::Lambda { .. } => self.call_value(&function, args, position),
+            _ => Err(FlowError::runtime_error(format!("'{}' is not a function", name))),
+        }
+    }
+    
+    /// Evaluate a chain of method calls, indexing and property accesses. A
+    /// null before `?.` or `?[` gives None, which skips the rest of the chain
+    /// (`null?.x.y` is null), including any index or argument expressions.
+    fn evaluate_access(&mut self, expression: &ExpressionKind) -> Result<Option<Value>> {
+        let (object, optional) = match expression {
+            ExpressionKind::MethodCall { object, optional, .. }
+            | ExpressionKind::Index { object, optional, .. }
+            | ExpressionKind::PropertyAccess { object, optional, .. } => (object, *optional),
+            _ => return self.evaluate_expression_kind(expression).map(Some),
+        };
+        let obj_val = self.evaluate_access(&object.kind);
+        let obj_val = match self.locate_error(obj_val, object.span)? {
+            Some(Value::Null) if optional => return Ok(None),
+            Some(value) => value,
+            None => return Ok(None),
+        };
+        
+        match expression {
+            ExpressionKind::MethodCall { method, arguments, position, .. } => {
+                let args = self.evaluate_arguments(arguments)?;
+                methods::call_method(&obj_val, method, args, |callee, args| self.call_value(callee, args, *position))
+                    .map(Some)
+            }
+            ExpressionKind::Index { index, .. } => {
+                let index_val = self.evaluate_expression(index)?;
+                self.get_index(&obj_val, &index_val).map(Some)
+            }
+            ExpressionKind::PropertyAccess { property, .. } => {
+                operators::get_property(&obj_val, property).map(Some)
+            }
+            _ => unreachable!("only accesses reach here"),
+        }
+    }
+    
+    /// Evaluate call arguments or array elements in order, expanding each
+    /// `...items` into the elements of the array
+    fn evaluate_arguments(&mut self, arguments: &[Expression]) -> Result<Vec<Value>> {
+        let mut values = Vec::with_capacity(arguments.len());
+        for argument in arguments {
+            match &argument.kind {
+                ExpressionKind::Spread(items) => {
+                    let items = self.evaluate_expression(items)?;
+                    values.extend(operators::spread_elements(&items)?);
                 }
-                // Otherwise call as user-defined function
-                self.call_function_with_params(&parameters, &body, arguments)
+                _ => values.push(self.evaluate_expression(argument)?),
             }
-            
-            Value::Lambda { parameters, body, closure } => {
-                // Save current environment and switch to closure
-                let saved_env = self.environment.clone();
-                self.environment = closure.clone();
+        }
+        Ok(values)
+    }
+    
+    /// Call a function or lambda value with already-evaluated arguments,
+    /// recording `call_site` in the call stack
+    fn call_value(&mut self, callee: &Value, args: Vec<Value>, call_site: Position) -> Result<Value> {
+        match callee {
+            Value::Function { name, parameters, body, closure } => {
+                // Stdlib wrappers have no body of their own
+                if body.is_empty() && self.stdlib.has_function(name) {
+                    return self.stdlib.call_function(name, &args);
+                }
+                
+                let saved_env = closure.as_ref()
+                    .map(|closure| std::mem::replace(&mut self.environment, closure.clone()));
+                self.environment.push_scope();
+                self.call_stack.push(StackFrame {
+                    function: name.clone(),
+                    line: call_site.line,
+                    column: Some(call_site.column),
+                });
                 
-                let result = self.call_lambda_with_params(&parameters, &body, arguments);
+                let result = self.bind_parameters(parameters, args)
+                    .and_then(|_| self.execute_function_body(body));
                 
-                // Restore environment
+                self.note_error_stack(&result);
+                self.call_stack.pop();
+                self.environment.pop_scope();
+                if let Some(saved_env) = saved_env {
+                    self.environment = saved_env;
+                }
+                result
+            }
+            
+            Value::Lambda { parameters, body, closure } => {
+                // Switch to the closure's scope, then restore the caller's
+                let saved_env = std::mem::replace(&mut self.environment, closure.clone());
+                self.call_stack.push(StackFrame {
+                    function: "<lambda>".to_string(),
+                    line: call_site.line,
+                    column: Some(call_site.column),
+                });
+                let result = self.call_lambda_with_params(parameters, body, args);
+                self.note_error_stack(&result);
+                self.call_stack.pop();
                 self.environment = saved_env;
                 result
             }
-            _ => Err(FlowError::runtime_error(format!("'{}' is not a function", name))),
+            
+            _ => Err(FlowError::type_error(format!("Cannot call a value of type {}", callee.type_name()))),
         }
     }
     
@@ -778,7 +1302,7 @@ impl Interpreter {
                 if let Some(value) = arr.get(idx) {
                     Ok(value.clone())
                 } else {
-                    Err(FlowError::runtime_error(format!("Array index {} out of bounds", i)))
+                    Err(FlowError::IndexOutOfBounds { index: *i })
                 }
             }
             (Value::Object(obj), Value::String(key)) => {
@@ -796,412 +1320,181 @@ impl Interpreter {
         }
     }
     
-    fn get_property(&self, object: &Value, property: &str) -> Result<Value> {
-        match object {
-            Value::Object(obj) => {
-                if let Some(value) = obj.get(property) {
-                    Ok(value.clone())
-                } else {
-                    Ok(Value::Null)
-                }
+    /// Evaluate the container and index of an assignment target, so that a
+    /// compound assignment reads and writes the same place
+    fn evaluate_place(&mut self, target: &Expression) -> Result<Place> {
+        match &target.kind {
+            ExpressionKind::Identifier(name) => Ok(Place::Variable(name.clone())),
+            ExpressionKind::Index { object, index, .. } => {
+                let container = self.evaluate_expression(object)?;
+                let index_val = self.evaluate_expression(index)?;
+                Ok(Place::Index(container, index_val))
             }
-            Value::Array(arr) => {
-                // Built-in array properties
-                match property {
-                    "length" => Ok(Value::Integer(arr.len() as i64)),
-                    _ => Err(FlowError::runtime_error(format!("Array has no property '{}'", property)))
-                }
+            ExpressionKind::PropertyAccess { object, property, .. } => {
+                let container = self.evaluate_expression(object)?;
+                Ok(Place::Property(container, property.clone()))
             }
-            _ => Err(FlowError::type_error(format!(
-                "Cannot access property '{}' on {}",
-                property,
-                object.type_name()
-            )))
+            _ => Err(FlowError::runtime_error("Invalid assignment target")),
         }
     }
     
-    fn call_method(&mut self, object: &Value, method: &str, arguments: &[Expression]) -> Result<Value> {
-        match object {
-            Value::Array(arr) => {
-                let mut arr_copy = arr.clone();
-                match method {
-                    "push" => {
-                        if arguments.len() != 1 {
-                            return Err(FlowError::runtime_error("push() expects exactly 1 argument".to_string()));
-                        }
-                        let value = self.evaluate_expression(&arguments[0])?;
-                        arr_copy.push(value);
-                        Ok(Value::Array(arr_copy))
-                    }
-                    "pop" => {
-                        if !arguments.is_empty() {
-                            return Err(FlowError::runtime_error("pop() expects no arguments".to_string()));
-                        }
-                        if let Some(value) = arr_copy.pop() {
-                            Ok(value)
-                        } else {
-                            Ok(Value::Null)
-                        }
-                    }
-                    "length" => {
-                        if !arguments.is_empty() {
-                            return Err(FlowError::runtime_error("length() expects no arguments".to_string()));
-                        }
-                        Ok(Value::Integer(arr.len() as i64))
-                    }
-                    "slice" => {
-                        if arguments.len() != 2 {
-                            return Err(FlowError::runtime_error("slice() expects exactly 2 arguments (start, end)".to_string()));
-                        }
-                        let start_val = self.evaluate_expression(&arguments[0])?;
-                        let end_val = self.evaluate_expression(&arguments[1])?;
-                        
-                        if let (Value::Integer(start), Value::Integer(end)) = (start_val, end_val) {
-                            match arr.slice(start as usize, end as usize) {
-                                Ok(sliced_arr) => Ok(Value::Array(sliced_arr)),
-                                Err(err) => Err(FlowError::runtime_error(err))
-                            }
-                        } else {
-                            Err(FlowError::type_error("slice() arguments must be integers".to_string()))
-                        }
-                    }
-                    "map" => {
-                        if arguments.len() != 1 {
-                            return Err(FlowError::runtime_error("map() expects exactly 1 argument (function)".to_string()));
-                        }
-                        let func = self.evaluate_expression(&arguments[0])?;
-                        let mut result = Vec::new();
-                        
-                        for element in &arr.elements {
-                            let mapped_value = match &func {
-                                Value::Lambda { parameters, body, closure } => {
-                                    // Create new scope with closure
-                                    self.environment.push_scope();
-                                    
-                                    // Bind parameter
-                                    if !parameters.is_empty() {
-                                        self.environment.define_variable(parameters[0].name.clone(), element.clone());
-                                    }
-                                    
-                                    let result = self.evaluate_expression(body);
-                                    self.environment.pop_scope();
-                                    result?
-                                }
-                                _ => return Err(FlowError::type_error("map() argument must be a function".to_string()))
-                            };
-                            result.push(mapped_value);
-                        }
-                        
-                        Ok(Value::Array(FlowArray::from_values(result)))
-                    }
-                    "filter" => {
-                        if arguments.len() != 1 {
-                            return Err(FlowError::runtime_error("filter() expects exactly 1 argument (function)".to_string()));
-                        }
-                        let func = self.evaluate_expression(&arguments[0])?;
-                        let mut result = Vec::new();
-                        
-                        for element in &arr.elements {
-                            let should_include = match &func {
-                                Value::Lambda { parameters, body, closure } => {
-                                    // Create new scope with closure
-                                    self.environment.push_scope();
-                                    
-                                    // Bind parameter
-                                    if !parameters.is_empty() {
-                                        self.environment.define_variable(parameters[0].name.clone(), element.clone());
-                                    }
-                                    
-                                    let result = self.evaluate_expression(body);
-                                    self.environment.pop_scope();
-                                    result?
-                                }
-                                _ => return Err(FlowError::type_error("filter() argument must be a function".to_string()))
-                            };
-                            
-                            if should_include.is_truthy() {
-                                result.push(element.clone());
-                            }
-                        }
-                        
-                        Ok(Value::Array(FlowArray::from_values(result)))
-                    }
-                    "forEach" => {
-                        if arguments.len() != 1 {
-                            return Err(FlowError::runtime_error("forEach() expects exactly 1 argument (function)".to_string()));
-                        }
-                        let func = self.evaluate_expression(&arguments[0])?;
-                        
-                        for element in &arr.elements {
-                            match &func {
-                                Value::Lambda { parameters, body, closure } => {
-                                    // Create new scope with closure
-                                    self.environment.push_scope();
-                                    
-                                    // Bind parameter
-                                    if !parameters.is_empty() {
-                                        self.environment.define_variable(parameters[0].name.clone(), element.clone());
-                                    }
-                                    
-                                    let _ = self.evaluate_expression(body);
-                                    self.environment.pop_scope();
-                                }
-                                _ => return Err(FlowError::type_error("forEach() argument must be a function".to_string()))
-                            };
-                        }
-                        
-                        Ok(Value::Null)
-                    }
-                    "find" => {
-                        if arguments.len() != 1 {
-                            return Err(FlowError::runtime_error("find() expects exactly 1 argument (function)".to_string()));
-                        }
-                        let func = self.evaluate_expression(&arguments[0])?;
-                        
-                        for element in &arr.elements {
-                            let matches = match &func {
-                                Value::Lambda { parameters, body, closure } => {
-                                    // Create new scope with closure
-                                    self.environment.push_scope();
-                                    
-                                    // Bind parameter
-                                    if !parameters.is_empty() {
-                                        self.environment.define_variable(parameters[0].name.clone(), element.clone());
-                                    }
-                                    
-                                    let result = self.evaluate_expression(body);
-                                    self.environment.pop_scope();
-                                    result?
-                                }
-                                _ => return Err(FlowError::type_error("find() argument must be a function".to_string()))
-                            };
-                            
-                            if matches.is_truthy() {
-                                return Ok(element.clone());
-                            }
-                        }
-                        
-                        Ok(Value::Null)
-                    }
-                    "includes" => {
-                        if arguments.len() != 1 {
-                            return Err(FlowError::runtime_error("includes() expects exactly 1 argument".to_string()));
-                        }
-                        let value = self.evaluate_expression(&arguments[0])?;
-                        Ok(Value::Boolean(arr.contains(&value)))
-                    }
-                    "indexOf" => {
-                        if arguments.len() != 1 {
-                            return Err(FlowError::runtime_error("indexOf() expects exactly 1 argument".to_string()));
-                        }
-                        let value = self.evaluate_expression(&arguments[0])?;
-                        if let Some(index) = arr.index_of(&value) {
-                            Ok(Value::Integer(index as i64))
-                        } else {
-                            Ok(Value::Integer(-1))
-                        }
-                    }
-                    "join" => {
-                        let separator = if arguments.is_empty() {
-                            ",".to_string()
-                        } else if arguments.len() == 1 {
-                            let sep_val = self.evaluate_expression(&arguments[0])?;
-                            if let Value::String(s) = sep_val {
-                                s
-                            } else {
-                                return Err(FlowError::type_error("join() separator must be a string".to_string()));
-                            }
-                        } else {
-                            return Err(FlowError::runtime_error("join() expects 0 or 1 arguments".to_string()));
-                        };
-                        
-                        let strings: Vec<String> = arr.elements.iter().map(|v| v.to_string()).collect();
-                        Ok(Value::String(strings.join(&separator)))
-                    }
-                    "reverse" => {
-                        if !arguments.is_empty() {
-                            return Err(FlowError::runtime_error("reverse() expects no arguments".to_string()));
-                        }
-                        arr_copy.reverse();
-                        Ok(Value::Array(arr_copy))
-                    }
-                    "sort" => {
-                        if !arguments.is_empty() {
-                            return Err(FlowError::runtime_error("sort() expects no arguments".to_string()));
-                        }
-                        arr_copy.sort();
-                        Ok(Value::Array(arr_copy))
-                    }
-                    _ => Err(FlowError::runtime_error(format!("Array has no method '{}'", method)))
+    /// Store a value into an assignment target. Arrays and objects are
+    /// shared, so index and property stores update the container in place.
+    fn assign_to(&mut self, place: Place, value: Value) -> Result<()> {
+        match place {
+            Place::Variable(name) => {
+                self.environment.set_variable(&name, value)
+                    .map_err(|_| FlowError::undefined_variable(name))
+            }
+            Place::Index(container, index) => self.set_index(&container, &index, value),
+            Place::Property(container, property) => self.set_property(&container, &property, value),
+        }
+    }
+
+    fn set_index(&self, object: &Value, index: &Value, value: Value) -> Result<()> {
+        match (object, index) {
+            (Value::Array(arr), Value::Integer(i)) => {
+                if *i < 0 {
+                    return Err(FlowError::IndexOutOfBounds { index: *i });
                 }
+                arr.set(*i as usize, value).map_err(|_| FlowError::IndexOutOfBounds { index: *i })
+            }
+            (Value::Object(obj), Value::String(key)) => {
+                obj.set(key.clone(), value);
+                Ok(())
             }
+            (object, index) => Err(FlowError::type_error(format!(
+                "Cannot index {} with {}",
+                object.type_name(),
+                index.type_name()
+            )))
+        }
+    }
+
+    fn set_property(&self, object: &Value, property: &str, value: Value) -> Result<()> {
+        match object {
             Value::Object(obj) => {
-                // First check if the method exists as a property in the object
-                if let Some(func_value) = obj.get(method) {
-                    if let Value::Function { name, .. } = func_value {
-                        // Evaluate arguments and call the stdlib function
-                        let mut args = Vec::new();
-                        for arg in arguments {
-                            args.push(self.evaluate_expression(arg)?);
-                        }
-                        return self.stdlib.call_function(name, &args);
-                    }
-                }
-                
-                let mut obj_copy = obj.clone();
-                match method {
-                    "keys" => {
-                        if !arguments.is_empty() {
-                            return Err(FlowError::runtime_error("keys() expects no arguments".to_string()));
-                        }
-                        let keys: Vec<Value> = obj.keys()
-                            .into_iter()
-                            .map(|k| Value::String(k))
-                            .collect();
-                        Ok(Value::Array(FlowArray::from_values(keys)))
-                    }
-                    "has" => {
-                        if arguments.len() != 1 {
-                            return Err(FlowError::runtime_error("has() expects exactly 1 argument".to_string()));
-                        }
-                        let key_val = self.evaluate_expression(&arguments[0])?;
-                        if let Value::String(key) = key_val {
-                            Ok(Value::Boolean(obj.contains_key(&key)))
-                        } else {
-                            Err(FlowError::type_error("Object key must be a string".to_string()))
-                        }
-                    }
-                    "remove" => {
-                        if arguments.len() != 1 {
-                            return Err(FlowError::runtime_error("remove() expects exactly 1 argument".to_string()));
-                        }
-                        let key_val = self.evaluate_expression(&arguments[0])?;
-                        if let Value::String(key) = key_val {
-                            if let Some(value) = obj_copy.remove(&key) {
-                                Ok(value)
-                            } else {
-                                Ok(Value::Null)
-                            }
-                        } else {
-                            Err(FlowError::type_error("Object key must be a string".to_string()))
-                        }
-                    }
-                    _ => Err(FlowError::runtime_error(format!("Object has no method '{}'", method)))
-                }
+                obj.set(property.to_string(), value);
+                Ok(())
             }
             _ => Err(FlowError::type_error(format!(
-                "Cannot call method '{}' on {}",
-                method, object.type_name()
+                "Cannot set property '{}' on {}",
+                property,
+                object.type_name()
             )))
         }
     }
-    
-    fn call_function_with_params(
-        &mut self,
-        parameters: &[Parameter],
-        body: &[Statement],
-        arguments: &[Expression],
-    ) -> Result<Value> {
-        // Create new scope for function
-        self.environment.push_scope();
-        
-        let mut arg_index = 0;
-        let mut variadic_args = Vec::new();
+
+    /// Bind evaluated arguments to parameters in the current (fresh) scope
+    fn bind_parameters(&mut self, parameters: &[Parameter], args: Vec<Value>) -> Result<()> {
+        let is_variadic = parameters.last().is_some_and(|param| param.is_variadic);
+        if !is_variadic && args.len() > parameters.len() {
+            return Err(FlowError::argument_count_error(format!(
+                "Expected at most {} arguments but got {}",
+                parameters.len(), args.len()
+            )));
+        }
+        let mut args = args.into_iter();
         
-        // Bind parameters
         for param in parameters {
-            if param.is_variadic {
+            let value = if param.is_variadic {
                 // Collect remaining arguments into an array
-                while arg_index < arguments.len() {
-                    let arg_value = self.evaluate_expression(&arguments[arg_index])?;
-                    variadic_args.push(arg_value);
-                    arg_index += 1;
-                }
-                
-                let array = crate::value::FlowArray::from_values(variadic_args.clone());
-                self.environment.define_variable(param.name.clone(), Value::Array(array));
-            } else if arg_index < arguments.len() {
+                Value::Array(crate::value::FlowArray::from_values(args.by_ref().collect()))
+            } else if let Some(arg_value) = args.next() {
                 // Regular parameter with provided argument
-                let arg_value = self.evaluate_expression(&arguments[arg_index])?;
-                self.environment.define_variable(param.name.clone(), arg_value);
-                arg_index += 1;
+                arg_value
             } else if let Some(default) = &param.default_value {
                 // Use default value
-                let default_value = self.evaluate_expression(default)?;
-                self.environment.define_variable(param.name.clone(), default_value);
+                self.evaluate_expression(default)?
             } else {
                 // Missing required parameter
-                self.environment.pop_scope();
-                return Err(FlowError::runtime_error(format!(
+                return Err(FlowError::argument_count_error(format!(
                     "Missing required parameter '{}'",
                     param.name
                 )));
+            };
+            
+            if let Some(pattern) = &param.pattern {
+                self.bind_pattern(pattern, value.clone())?;
             }
+            self.environment.define_variable(param.name.clone(), value);
         }
         
-        // Execute function body
-        let mut result = Value::Null;
+        Ok(())
+    }
+    
+    /// Define the variables of a destructuring pattern from the parts of `value`
+    fn bind_pattern(&mut self, pattern: &Pattern, value: Value) -> Result<()> {
+        match pattern {
+            Pattern::Identifier(name) => {
+                self.environment.declare_variable(name.clone(), value);
+            }
+            Pattern::Array { elements, rest } => {
+                let required = destructure::required_elements(elements);
+                let (values, rest_value) = destructure::unpack_array(&value, required, elements.len(), rest.is_some())?;
+                for (element, value) in elements.iter().zip(values) {
+                    self.bind_pattern_element(element, value)?;
+                }
+                if let (Some(name), Some(rest_value)) = (rest, rest_value) {
+                    self.environment.declare_variable(name.clone(), rest_value);
+                }
+            }
+            Pattern::Object { properties } => {
+                let values = destructure::unpack_object(&value, &destructure::pattern_keys(properties))?;
+                for ((_, element), value) in properties.iter().zip(values) {
+                    self.bind_pattern_element(element, value)?;
+                }
+            }
+        }
+        Ok(())
+    }
+    
+    fn bind_pattern_element(&mut self, element: &PatternElement, value: Value) -> Result<()> {
+        let value = match (&element.default_value, value) {
+            (Some(default), Value::Null) => self.evaluate_expression(default)?,
+            (_, value) => value,
+        };
+        self.bind_pattern(&element.pattern, value)
+    }
+    
+    /// Run the statements of a function body, yielding its `return` value
+    fn execute_function_body(&mut self, body: &[Statement]) -> Result<Value> {
         for statement in body {
             match self.execute_statement(statement) {
                 Ok(()) => {},
-                Err(FlowError::Return { value }) => {
-                    result = value;
-                    break;
-                },
-                Err(e) => {
-                    self.environment.pop_scope();
-                    return Err(e);
-                }
+                Err(FlowError::Return { value }) => return Ok(value),
+                Err(e) => return Err(e),
             }
         }
-        
-        self.environment.pop_scope();
-        Ok(result)
+        Ok(Value::Null)
     }
     
     fn call_lambda_with_params(
         &mut self,
         parameters: &[Parameter],
-        body: &Expression,
-        arguments: &[Expression],
+        body: &LambdaBody,
+        args: Vec<Value>,
     ) -> Result<Value> {
         // Create new scope for lambda
         self.environment.push_scope();
         
-        let mut arg_index = 0;
-        let mut variadic_args = Vec::new();
-        
-        // Bind parameters (same logic as functions)
-        for param in parameters {
-            if param.is_variadic {
-                while arg_index < arguments.len() {
-                    let arg_value = self.evaluate_expression(&arguments[arg_index])?;
-                    variadic_args.push(arg_value);
-                    arg_index += 1;
-                }
-                
-                let array = crate::value::FlowArray::from_values(variadic_args.clone());
-                self.environment.define_variable(param.name.clone(), Value::Array(array));
-            } else if arg_index < arguments.len() {
-                let arg_value = self.evaluate_expression(&arguments[arg_index])?;
-                self.environment.define_variable(param.name.clone(), arg_value);
-                arg_index += 1;
-            } else if let Some(default) = &param.default_value {
-                let default_value = self.evaluate_expression(default)?;
-                self.environment.define_variable(param.name.clone(), default_value);
-            } else {
-                self.environment.pop_scope();
-                return Err(FlowError::runtime_error(format!(
-                    "Missing required parameter '{}'",
-                    param.name
-                )));
-            }
-        }
-        
-        // Evaluate lambda body
-        let result = self.evaluate_expression(body);
+        let result = self.bind_parameters(parameters, args).and_then(|_| match body {
+            LambdaBody::Expression(expression) => self.evaluate_expression(expression),
+            LambdaBody::Block(statements) => self.execute_function_body(statements),
+        });
         
         self.environment.pop_scope();
         result
     }
-}
\ No newline at end of file
+}
+
+/// Quoted import paths name files; bare identifiers name built-in modules
+fn is_file_module(module_path: &str) -> bool {
+    module_path.ends_with(".flow") || module_path.contains('/') || module_path.contains('\\')
+}
+
+fn display_module_path(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| path.display().to_string())
+}
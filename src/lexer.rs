@@ -1,16 +1,22 @@
+use crate::ast::Position;
 use crate::error::{FlowError, Result};
 
+/// A token and where it is in the source: `line`, `column` and `offset` (in
+/// bytes) are where it starts, and `end` is just past its last character
 #[derive(Debug, Clone, PartialEq)]
 pub struct TokenWithPos {
     pub token: Token,
     pub line: usize,
     pub column: usize,
+    pub offset: usize,
+    pub end: Position,
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum Token {
     // Literals
     String(String),
+    InterpolatedString(Vec<StringPart>),
     Integer(i64),
     BigInteger(crate::bigint::BigInt),
     Float(f64),
@@ -32,8 +38,12 @@ pub enum Token {
     ElseIf,
     While,
     For,
+    Break,
+    Continue,
     From,
     To,
+    By,
+    In,
     Show,
     Return,
     Import,
@@ -41,6 +51,8 @@ pub enum Token {
     As,
     Try,
     Catch,
+    Finally,
+    Throw,
     True,
     False,
     Null,
@@ -51,6 +63,16 @@ pub enum Token {
     Multiply,
     Divide,
     Modulo,
+    Power,
+    IntegerDivide,
+    BitAnd,
+    BitOr,
+    BitXor,
+    BitNot,
+    ShiftLeft,
+    ShiftRight,
+    NullCoalesce,
+    Pipe,
     Equal,
     NotEqual,
     Greater,
@@ -70,33 +92,108 @@ pub enum Token {
     RightBrace,
     Comma,
     Dot,
+    OptionalDot,
+    OptionalBracket,
     Colon,
     Arrow,
     Ellipsis,
     Assign,
+    PlusAssign,
+    MinusAssign,
+    MultiplyAssign,
+    DivideAssign,
+    ModuloAssign,
     
     // Special
     Newline,
     Eof,
 }
 
+/// Words the lexer reads as keywords rather than identifiers
+pub const KEYWORDS: &[&str] = &[
+    "let", "be", "def", "with", "do", "end", "if", "then", "else", "while", "for",
+    "break", "continue", "from", "to", "by", "in", "show", "return", "import",
+    "export", "as", "try", "catch", "finally", "throw", "true", "false", "null", "and",
+    "or", "not",
+];
+
+/// The token a keyword is read as, or `None` for other words
+pub fn keyword_token(word: &str) -> Option<Token> {
+    let token = match word {
+        "let" => Token::Let,
+        "be" => Token::Be,
+        "def" => Token::Def,
+        "with" => Token::With,
+        "do" => Token::Do,
+        "end" => Token::End,
+        "if" => Token::If,
+        "then" => Token::Then,
+        "else" => Token::Else,
+        "while" => Token::While,
+        "for" => Token::For,
+        "break" => Token::Break,
+        "continue" => Token::Continue,
+        "from" => Token::From,
+        "to" => Token::To,
+        "by" => Token::By,
+        "in" => Token::In,
+        "show" => Token::Show,
+        "return" => Token::Return,
+        "import" => Token::Import,
+        "export" => Token::Export,
+        "as" => Token::As,
+        "try" => Token::Try,
+        "catch" => Token::Catch,
+        "finally" => Token::Finally,
+        "throw" => Token::Throw,
+        "true" => Token::Boolean(true),
+        "false" => Token::Boolean(false),
+        "null" => Token::Null,
+        "and" => Token::And,
+        "or" => Token::Or,
+        "not" => Token::Not,
+        _ => return None,
+    };
+    Some(token)
+}
+
+/// A piece of an interpolated string literal such as `"Hello, {name}!"`
+#[derive(Debug, Clone, PartialEq)]
+pub enum StringPart {
+    Literal(String),
+    /// Tokens of an embedded `{expression}`, positioned in the original source
+    Expression(Vec<TokenWithPos>),
+}
+
 pub struct Lexer {
     input: Vec<char>,
     position: usize,
     line: usize,
     column: usize,
+    /// Byte offset of `position` in the source
+    offset: usize,
 }
 
 impl Lexer {
     pub fn new(input: &str) -> Self {
+        Self::at_position(input, Position { line: 1, column: 1, offset: 0 })
+    }
+    
+    /// Create a lexer for a fragment of a larger source that starts at `start`
+    fn at_position(input: &str, start: Position) -> Self {
         Self {
             input: input.chars().collect(),
             position: 0,
-            line: 1,
-            column: 1,
+            line: start.line,
+            column: start.column,
+            offset: start.offset,
         }
     }
     
+    fn current_position(&self) -> Position {
+        Position { line: self.line, column: self.column, offset: self.offset }
+    }
+    
     pub fn tokenize(&mut self) -> Result<Vec<TokenWithPos>> {
         let mut tokens = Vec::new();
         
@@ -113,16 +210,24 @@ impl Lexer {
                 continue;
             }
             
-            let line = self.line;
-            let column = self.column;
+            let start = self.current_position();
             let token = self.next_token()?;
-            tokens.push(TokenWithPos { token, line, column });
+            tokens.push(TokenWithPos {
+                token,
+                line: start.line,
+                column: start.column,
+                offset: start.offset,
+                end: self.current_position(),
+            });
         }
         
+        let end = self.current_position();
         tokens.push(TokenWithPos { 
             token: Token::Eof, 
-            line: self.line, 
-            column: self.column 
+            line: end.line, 
+            column: end.column,
+            offset: end.offset,
+            end,
         });
         Ok(tokens)
     }
@@ -133,29 +238,101 @@ impl Lexer {
         match ch {
             '\n' => {
                 self.advance();
-                self.line += 1;
-                self.column = 1;
                 Ok(Token::Newline)
             }
             '+' => {
                 self.advance();
-                Ok(Token::Plus)
+                if self.current_char() == '=' {
+                    self.advance();
+                    Ok(Token::PlusAssign)
+                } else {
+                    Ok(Token::Plus)
+                }
             }
             '-' => {
                 self.advance();
-                Ok(Token::Minus)
+                if self.current_char() == '=' {
+                    self.advance();
+                    Ok(Token::MinusAssign)
+                } else {
+                    Ok(Token::Minus)
+                }
             }
             '*' => {
                 self.advance();
-                Ok(Token::Multiply)
+                if self.current_char() == '*' {
+                    self.advance();
+                    Ok(Token::Power)
+                } else if self.current_char() == '=' {
+                    self.advance();
+                    Ok(Token::MultiplyAssign)
+                } else {
+                    Ok(Token::Multiply)
+                }
             }
             '/' => {
                 self.advance();
-                Ok(Token::Divide)
+                if self.current_char() == '/' {
+                    self.advance();
+                    Ok(Token::IntegerDivide)
+                } else if self.current_char() == '=' {
+                    self.advance();
+                    Ok(Token::DivideAssign)
+                } else {
+                    Ok(Token::Divide)
+                }
             }
             '%' => {
                 self.advance();
-                Ok(Token::Modulo)
+                if self.current_char() == '=' {
+                    self.advance();
+                    Ok(Token::ModuloAssign)
+                } else {
+                    Ok(Token::Modulo)
+                }
+            }
+            '&' => {
+                self.advance();
+                Ok(Token::BitAnd)
+            }
+            '|' => {
+                self.advance();
+                if self.current_char() == '>' {
+                    self.advance();
+                    Ok(Token::Pipe)
+                } else {
+                    Ok(Token::BitOr)
+                }
+            }
+            '^' => {
+                self.advance();
+                Ok(Token::BitXor)
+            }
+            '~' => {
+                self.advance();
+                Ok(Token::BitNot)
+            }
+            '?' => {
+                self.advance();
+                match self.current_char() {
+                    '?' => {
+                        self.advance();
+                        Ok(Token::NullCoalesce)
+                    }
+                    '.' => {
+                        self.advance();
+                        Ok(Token::OptionalDot)
+                    }
+                    '[' => {
+                        self.advance();
+                        Ok(Token::OptionalBracket)
+                    }
+                    _ => Err(FlowError::lexer_error(
+                        self.line,
+                        self.column,
+                        "Unexpected character '?'. Did you mean '??' or '?.'?",
+                    )),
+                }
             }
             '(' => {
                 self.advance();
@@ -227,7 +404,10 @@ impl Lexer {
             }
             '>' => {
                 self.advance();
-                if self.current_char() == '=' {
+                if self.current_char() == '>' {
+                    self.advance();
+                    Ok(Token::ShiftRight)
+                } else if self.current_char() == '=' {
                     self.advance();
                     Ok(Token::GreaterEqual)
                 } else {
@@ -236,14 +416,26 @@ impl Lexer {
             }
             '<' => {
                 self.advance();
-                if self.current_char() == '=' {
+                if self.current_char() == '<' {
+                    self.advance();
+                    Ok(Token::ShiftLeft)
+                } else if self.current_char() == '=' {
                     self.advance();
                     Ok(Token::LessEqual)
                 } else {
                     Ok(Token::Less)
                 }
             }
-            '"' => self.read_string(),
+            '"' if self.peek() == '"' && self.peek_next() == '"' => self.read_multiline_string(false),
+            '"' => self.read_string(false),
+            'r' if self.peek() == '"' => {
+                self.advance(); // Skip 'r'
+                if self.peek() == '"' && self.peek_next() == '"' {
+                    self.read_multiline_string(true)
+                } else {
+                    self.read_string(true)
+                }
+            }
             ch if ch.is_ascii_digit() => self.read_number(),
             ch if ch.is_ascii_alphabetic() || ch == '_' => self.read_identifier(),
             _ => Err(FlowError::lexer_error(
@@ -254,74 +446,313 @@ impl Lexer {
         }
     }
     
-    fn read_string(&mut self) -> Result<Token> {
+    /// Read a `"..."` string literal. Raw strings (`r"..."`) keep backslashes
+    /// and braces as written.
+    fn read_string(&mut self, raw: bool) -> Result<Token> {
+        let start_line = self.line;
+        let start_column = self.column;
         self.advance(); // Skip opening quote
+        let mut parts = Vec::new();
         let mut value = String::new();
         
         while !self.is_at_end() && self.current_char() != '"' {
-            if self.current_char() == '\\' {
+            self.read_string_char(raw, &mut value, &mut parts)?;
+        }
+        
+        if self.is_at_end() {
+            return Err(FlowError::lexer_error(
+                start_line,
+                start_column,
+                "Unterminated string literal",
+            ));
+        }
+        
+        self.advance(); // Skip closing quote
+        Ok(Self::string_token(value, parts))
+    }
+    
+    /// Read a `"""..."""` string literal.
+    ///
+    /// A line break right after the opening quotes and a whitespace-only line
+    /// before the closing quotes are dropped, and the indentation shared by all
+    /// non-blank lines is stripped.
+    fn read_multiline_string(&mut self, raw: bool) -> Result<Token> {
+        let start_line = self.line;
+        let start_column = self.column;
+        for _ in 0..3 {
+            self.advance(); // Skip opening quotes
+        }
+        
+        let close = self.find_multiline_close(raw).ok_or_else(|| FlowError::lexer_error(
+            start_line,
+            start_column,
+            "Unterminated multi-line string literal",
+        ))?;
+        
+        let body: String = self.input[self.position..close].iter().collect();
+        let lines: Vec<&str> = body.split('\n').collect();
+        
+        // Work out which part of the body is content
+        let mut content_start = self.position;
+        let mut content_end = close;
+        if lines.len() > 1 {
+            if lines[0].trim().is_empty() {
+                content_start += lines[0].chars().count() + 1;
+            }
+            let last = lines[lines.len() - 1];
+            if last.trim().is_empty() {
+                content_end -= last.chars().count() + 1;
+            }
+        }
+        let indent = lines.iter()
+            .skip(1)
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| line.chars().take_while(|c| *c == ' ' || *c == '\t').count())
+            .min()
+            .unwrap_or(0);
+        
+        let mut at_line_start = content_start != self.position;
+        while self.position < content_start {
+            self.advance();
+        }
+        
+        let mut parts = Vec::new();
+        let mut value = String::new();
+        while self.position < content_end {
+            if at_line_start {
+                at_line_start = false;
+                let mut skipped = 0;
+                while skipped < indent && self.position < content_end && matches!(self.current_char(), ' ' | '\t') {
+                    self.advance();
+                    skipped += 1;
+                }
+                continue;
+            }
+            if self.current_char() == '\n' {
+                at_line_start = true;
+            }
+            self.read_string_char(raw, &mut value, &mut parts)?;
+        }
+        
+        while self.position < close + 3 {
+            self.advance(); // Skip any dropped last line and the closing quotes
+        }
+        Ok(Self::string_token(value, parts))
+    }
+    
+    /// Position of the `"""` that closes a multi-line string starting at the cursor
+    fn find_multiline_close(&self, raw: bool) -> Option<usize> {
+        let mut i = self.position;
+        while i + 2 < self.input.len() {
+            match self.input[i] {
+                '\\' if !raw => i += 2,
+                '"' if self.input.get(i + 1) == Some(&'"') && self.input.get(i + 2) == Some(&'"') => return Some(i),
+                _ => i += 1,
+            }
+        }
+        None
+    }
+    
+    /// Consume one character (or escape sequence, or `{expression}`) of a string body
+    fn read_string_char(&mut self, raw: bool, value: &mut String, parts: &mut Vec<StringPart>) -> Result<()> {
+        match self.current_char() {
+            '\\' if !raw => {
+                let ch = self.read_escape()?;
+                value.push(ch);
+            }
+            '{' if !raw => {
+                if !value.is_empty() {
+                    parts.push(StringPart::Literal(std::mem::take(value)));
+                }
+                parts.push(StringPart::Expression(self.read_interpolation()?));
+            }
+            ch => {
+                value.push(ch);
                 self.advance();
-                if self.is_at_end() {
-                    return Err(FlowError::lexer_error(
-                        self.line,
-                        self.column,
-                        "Unterminated string literal",
-                    ));
+            }
+        }
+        Ok(())
+    }
+    
+    /// Read an escape sequence starting at the backslash
+    fn read_escape(&mut self) -> Result<char> {
+        let line = self.line;
+        let column = self.column;
+        self.advance(); // Skip '\'
+        if self.is_at_end() {
+            return Err(FlowError::lexer_error(line, column, "Unterminated string literal"));
+        }
+        
+        let ch = self.current_char();
+        self.advance();
+        let escaped = match ch {
+            'n' => '\n',
+            't' => '\t',
+            'r' => '\r',
+            '0' => '\0',
+            '\\' => '\\',
+            '"' => '"',
+            '{' => '{',
+            '}' => '}',
+            'x' => {
+                let mut digits = String::new();
+                while digits.len() < 2 && self.current_char().is_ascii_hexdigit() {
+                    digits.push(self.current_char());
+                    self.advance();
                 }
-                
-                match self.current_char() {
-                    'n' => value.push('\n'),
-                    't' => value.push('\t'),
-                    'r' => value.push('\r'),
-                    '\\' => value.push('\\'),
-                    '"' => value.push('"'),
-                    ch => {
-                        return Err(FlowError::lexer_error(
-                            self.line,
-                            self.column,
-                            format!("Invalid escape sequence: \\{}", ch),
-                        ))
-                    }
+                if digits.len() != 2 {
+                    return Err(FlowError::lexer_error(line, column, "Expected two hex digits in escape, as in \\x41"));
+                }
+                char::from(u8::from_str_radix(&digits, 16).unwrap_or(0))
+            }
+            'u' => {
+                if self.current_char() != '{' {
+                    return Err(FlowError::lexer_error(line, column, "Expected '{' in unicode escape, as in \\u{1F600}"));
+                }
+                self.advance();
+                let mut digits = String::new();
+                while !self.is_at_end() && self.current_char().is_ascii_hexdigit() {
+                    digits.push(self.current_char());
+                    self.advance();
+                }
+                if self.current_char() != '}' {
+                    return Err(FlowError::lexer_error(line, column, "Unclosed unicode escape, expected '}'"));
+                }
+                self.advance();
+                u32::from_str_radix(&digits, 16)
+                    .ok()
+                    .filter(|_| digits.len() <= 6)
+                    .and_then(char::from_u32)
+                    .ok_or_else(|| FlowError::lexer_error(line, column, format!("Invalid unicode escape: \\u{{{}}}", digits)))?
+            }
+            ch => {
+                return Err(FlowError::lexer_error(
+                    line,
+                    column,
+                    format!("Invalid escape sequence: \\{}", ch),
+                ))
+            }
+        };
+        Ok(escaped)
+    }
+    
+    fn string_token(value: String, mut parts: Vec<StringPart>) -> Token {
+        if parts.is_empty() {
+            return Token::String(value);
+        }
+        if !value.is_empty() {
+            parts.push(StringPart::Literal(value));
+        }
+        Token::InterpolatedString(parts)
+    }
+    
+    /// Read a `{expression}` inside a string literal and tokenize the expression.
+    /// Braces and string literals may nest inside it; `\{` escapes a literal brace.
+    fn read_interpolation(&mut self) -> Result<Vec<TokenWithPos>> {
+        let brace_line = self.line;
+        let brace_column = self.column;
+        self.advance(); // Skip '{'
+        
+        let start = self.current_position();
+        let mut source = String::new();
+        let mut depth = 0;
+        let mut in_string = false;
+        
+        loop {
+            if self.is_at_end() || self.current_char() == '\n' {
+                return Err(FlowError::lexer_error(
+                    brace_line,
+                    brace_column,
+                    "Unclosed '{' in string interpolation",
+                ));
+            }
+            
+            let ch = self.current_char();
+            if in_string {
+                if ch == '\\' {
+                    source.push(ch);
+                    self.advance();
+                } else if ch == '"' {
+                    in_string = false;
                 }
             } else {
-                value.push(self.current_char());
+                match ch {
+                    '"' => in_string = true,
+                    '{' => depth += 1,
+                    '}' if depth == 0 => break,
+                    '}' => depth -= 1,
+                    _ => {}
+                }
             }
+            source.push(self.current_char());
             self.advance();
         }
+        self.advance(); // Skip '}'
         
-        if self.is_at_end() {
+        if source.trim().is_empty() {
             return Err(FlowError::lexer_error(
-                self.line,
-                self.column,
-                "Unterminated string literal",
+                brace_line,
+                brace_column,
+                "Empty expression in string interpolation",
             ));
         }
         
-        self.advance(); // Skip closing quote
-        Ok(Token::String(value))
+        Lexer::at_position(&source, start).tokenize()
     }
     
     fn read_number(&mut self) -> Result<Token> {
+        let line = self.line;
+        let column = self.column;
+        
+        if self.current_char() == '0' {
+            let radix = match self.peek() {
+                'x' | 'X' => Some((16, "hexadecimal")),
+                'b' | 'B' => Some((2, "binary")),
+                'o' | 'O' => Some((8, "octal")),
+                _ => None,
+            };
+            if let Some((radix, kind)) = radix {
+                self.advance();
+                self.advance();
+                return self.read_radix_number(radix, kind, line, column);
+            }
+        }
+        
         let mut value = String::new();
         let mut is_float = false;
         
-        while !self.is_at_end() && (self.current_char().is_ascii_digit() || self.current_char() == '.') {
-            if self.current_char() == '.' {
-                if is_float {
-                    break; // Second dot, stop parsing
-                }
+        self.read_digits(&mut value, line, column)?;
+        
+        // A fractional part needs a digit after the dot
+        if self.current_char() == '.' && self.peek().is_ascii_digit() {
+            is_float = true;
+            value.push('.');
+            self.advance();
+            self.read_digits(&mut value, line, column)?;
+        }
+        
+        // Exponent: 1e9, 2.5E-3
+        if matches!(self.current_char(), 'e' | 'E') {
+            let has_sign = matches!(self.peek(), '+' | '-');
+            let first_digit = if has_sign { self.peek_next() } else { self.peek() };
+            if first_digit.is_ascii_digit() {
                 is_float = true;
+                value.push('e');
+                self.advance();
+                if has_sign {
+                    value.push(self.current_char());
+                    self.advance();
+                }
+                self.read_digits(&mut value, line, column)?;
             }
-            value.push(self.current_char());
-            self.advance();
         }
         
         if is_float {
             match value.parse::<f64>() {
                 Ok(f) => Ok(Token::Float(f)),
                 Err(_) => Err(FlowError::lexer_error(
-                    self.line,
-                    self.column,
+                    line,
+                    column,
                     format!("Invalid float literal: {}", value),
                 )),
             }
@@ -333,8 +764,8 @@ impl Lexer {
                     match crate::bigint::BigInt::from_string(&value) {
                         Ok(big_int) => Ok(Token::BigInteger(big_int)),
                         Err(_) => Err(FlowError::lexer_error(
-                            self.line,
-                            self.column,
+                            line,
+                            column,
                             format!("Invalid integer literal: {}", value),
                         )),
                     }
@@ -343,6 +774,85 @@ impl Lexer {
         }
     }
     
+    /// Read a run of decimal digits, dropping `_` separators. A separator must
+    /// sit between two digits.
+    fn read_digits(&mut self, value: &mut String, line: usize, column: usize) -> Result<()> {
+        while self.current_char().is_ascii_digit() || self.current_char() == '_' {
+            if self.current_char() == '_' {
+                if !self.peek().is_ascii_digit() || !value.ends_with(|c: char| c.is_ascii_digit()) {
+                    return Err(FlowError::lexer_error(
+                        line,
+                        column,
+                        "Digit separator '_' must be between two digits",
+                    ));
+                }
+            } else {
+                value.push(self.current_char());
+            }
+            self.advance();
+        }
+        Ok(())
+    }
+    
+    /// Read the digits of a `0x`, `0b` or `0o` literal. Values that don't fit
+    /// in an i64 become a `Token::BigInteger`.
+    fn read_radix_number(&mut self, radix: u32, kind: &str, line: usize, column: usize) -> Result<Token> {
+        let mut digits = Vec::new();
+        let mut last_was_digit = false;
+        
+        while self.current_char().is_ascii_alphanumeric() || self.current_char() == '_' {
+            let ch = self.current_char();
+            if ch == '_' {
+                if !last_was_digit || !self.peek().is_ascii_alphanumeric() {
+                    return Err(FlowError::lexer_error(
+                        line,
+                        column,
+                        "Digit separator '_' must be between two digits",
+                    ));
+                }
+                last_was_digit = false;
+            } else {
+                match ch.to_digit(radix) {
+                    Some(digit) => digits.push(digit),
+                    None => {
+                        return Err(FlowError::lexer_error(
+                            self.line,
+                            self.column,
+                            format!("Invalid digit '{}' in {} literal", ch, kind),
+                        ))
+                    }
+                }
+                last_was_digit = true;
+            }
+            self.advance();
+        }
+        
+        if digits.is_empty() {
+            return Err(FlowError::lexer_error(
+                line,
+                column,
+                format!("Expected digits in {} literal", kind),
+            ));
+        }
+        
+        let mut small: Option<i64> = Some(0);
+        for digit in &digits {
+            small = small
+                .and_then(|v| v.checked_mul(radix as i64))
+                .and_then(|v| v.checked_add(*digit as i64));
+        }
+        if let Some(i) = small {
+            return Ok(Token::Integer(i));
+        }
+        
+        let big_radix = crate::bigint::BigInt::from_i64(radix as i64);
+        let mut big = crate::bigint::BigInt::new();
+        for digit in digits {
+            big = big * big_radix.clone() + crate::bigint::BigInt::from_i64(digit as i64);
+        }
+        Ok(Token::BigInteger(big))
+    }
+    
     fn read_identifier(&mut self) -> Result<Token> {
         let mut value = String::new();
         
@@ -351,35 +861,7 @@ impl Lexer {
             self.advance();
         }
         
-        let token = match value.as_str() {
-            "let" => Token::Let,
-            "be" => Token::Be,
-            "def" => Token::Def,
-            "with" => Token::With,
-            "do" => Token::Do,
-            "end" => Token::End,
-            "if" => Token::If,
-            "then" => Token::Then,
-            "else" => Token::Else,
-            "while" => Token::While,
-            "for" => Token::For,
-            "from" => Token::From,
-            "to" => Token::To,
-            "show" => Token::Show,
-            "return" => Token::Return,
-            "import" => Token::Import,
-            "export" => Token::Export,
-            "as" => Token::As,
-            "try" => Token::Try,
-            "catch" => Token::Catch,
-            "true" => Token::Boolean(true),
-            "false" => Token::Boolean(false),
-            "null" => Token::Null,
-            "and" => Token::And,
-            "or" => Token::Or,
-            "not" => Token::Not,
-            _ => Token::Identifier(value),
-        };
+        let token = keyword_token(&value).unwrap_or(Token::Identifier(value));
         
         Ok(token)
     }
@@ -406,8 +888,15 @@ impl Lexer {
     
     fn advance(&mut self) {
         if !self.is_at_end() {
+            let ch = self.input[self.position];
+            if ch == '\n' {
+                self.line += 1;
+                self.column = 1;
+            } else {
+                self.column += 1;
+            }
+            self.offset += ch.len_utf8();
             self.position += 1;
-            self.column += 1;
         }
     }
     
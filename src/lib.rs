@@ -33,6 +33,7 @@
 pub mod ast;
 pub mod collections;
 pub mod error;
+pub mod error_codes;
 pub mod interpreter;
 pub mod lexer;
 pub mod parser;
@@ -46,6 +47,10 @@ pub mod specialized_instructions;
 pub mod stdlib;
 pub mod stdlib_modules;
 pub mod compiler;
+pub mod diagnostic;
+pub mod operators;
+pub mod methods;
+pub mod destructure;
 
 pub use ast::*;
 pub use error::*;
@@ -3,13 +3,23 @@ use colored::*;
 use std::fs;
 use std::path::Path;
 
+use flowlang::ast::Program;
 use flowlang::lexer::Lexer;
 use flowlang::parser::Parser;
 use flowlang::interpreter::Interpreter;
 use flowlang::bytecode::VirtualMachine;
 use flowlang::compiler::compile_program;
 use flowlang::stdlib::StandardLibrary;
-use flowlang::error::FlowError;
+use flowlang::error::{FlowError, StackFrame};
+use flowlang::diagnostic::Diagnostic;
+use flowlang::error_codes;
+
+/// How errors are printed: quoted source for terminals, or JSON lines for tools
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ErrorFormat {
+    Human,
+    Json,
+}
 
 fn main() {
     let matches = Command::new("FlowLang")
@@ -35,89 +45,151 @@ fn main() {
                 .help("Use bytecode virtual machine (default: tree-walking interpreter)")
                 .action(clap::ArgAction::SetTrue),
         )
+        .arg(
+            Arg::new("check")
+                .long("check")
+                .help("Report every syntax error in the file without running it")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("error-format")
+                .long("error-format")
+                .value_name("FORMAT")
+                .help("How to print errors: 'human' (default) or 'json', one object per line")
+                .value_parser(["human", "json"])
+                .default_value("human"),
+        )
+        .arg(
+            Arg::new("lib-path")
+                .long("lib-path")
+                .value_name("DIR")
+                .help("Add a directory to search for imported modules (repeatable; FLOW_PATH is searched after these)")
+                .action(clap::ArgAction::Append),
+        )
+        .subcommand(
+            Command::new("explain")
+                .about("Print the long-form explanation of an error code, such as E0008")
+                .arg(Arg::new("code").required(true).help("The error code to explain")),
+        )
+        .args_conflicts_with_subcommands(true)
         .get_matches();
 
+    if let Some(explain) = matches.subcommand_matches("explain") {
+        let code = explain.get_one::<String>("code").expect("code is required");
+        explain_code(code);
+        return;
+    }
+
     let use_vm = matches.get_flag("vm");
+    let format = match matches.get_one::<String>("error-format").map(String::as_str) {
+        Some("json") => ErrorFormat::Json,
+        _ => ErrorFormat::Human,
+    };
+    let lib_paths: Vec<String> = matches
+        .get_many::<String>("lib-path")
+        .map(|paths| paths.cloned().collect())
+        .unwrap_or_default();
 
     if matches.get_flag("repl") {
-        start_repl();
+        start_repl(&lib_paths);
     } else if let Some(file_path) = matches.get_one::<String>("file") {
-        execute_file(file_path, use_vm);
+        if matches.get_flag("check") {
+            check_file(file_path, format);
+        } else {
+            execute_file(file_path, use_vm, &lib_paths, format);
+        }
     } else {
         println!("{}", "Welcome to FlowLang!".bright_blue().bold());
         println!("Usage: flowlang <file.flow> [--vm] or flowlang --repl");
     }
 }
 
-fn execute_file(file_path: &str, use_vm: bool) {
-    if !Path::new(file_path).exists() {
-        eprintln!("{}: File '{}' not found", "Error".red().bold(), file_path);
-        return;
+/// An interpreter whose module search path is the project directory, then
+/// any `--lib-path` directories, then `FLOW_PATH`
+fn new_interpreter(script_path: Option<&str>, lib_paths: &[String]) -> Interpreter {
+    let mut interpreter = Interpreter::new();
+    if let Some(script_path) = script_path {
+        interpreter.set_script_path(script_path);
+    }
+    for dir in lib_paths {
+        interpreter.add_module_path(dir);
     }
+    interpreter.add_module_paths_from_env();
+    interpreter
+}
 
-    let source = match fs::read_to_string(file_path) {
-        Ok(content) => content,
-        Err(err) => {
-            eprintln!("{}: Failed to read file '{}': {}", "Error".red().bold(), file_path, err);
-            return;
+/// Print the catalog entry for an error code; exits with status 1 if there is none
+fn explain_code(code: &str) {
+    match error_codes::explain(code) {
+        Some(explanation) => print!("{}", explanation),
+        None => {
+            eprintln!("{}: no error code '{}'; codes run from {} to {}",
+                "Error".red().bold(),
+                code,
+                error_codes::EXPLANATIONS[0].code,
+                error_codes::EXPLANATIONS[error_codes::EXPLANATIONS.len() - 1].code);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn read_source(file_path: &str, format: ErrorFormat) -> Option<String> {
+    let message = if !Path::new(file_path).exists() {
+        format!("File '{}' not found", file_path)
+    } else {
+        match fs::read_to_string(file_path) {
+            Ok(content) => return Some(content),
+            Err(err) => format!("Failed to read file '{}': {}", file_path, err),
         }
     };
+    report(&Diagnostic::from_error(&FlowError::IoError { message }), "", format);
+    None
+}
+
+fn execute_file(file_path: &str, use_vm: bool, lib_paths: &[String], format: ErrorFormat) {
+    let Some(source) = read_source(file_path, format) else {
+        return;
+    };
 
     if use_vm {
-        execute_source_vm(&source, file_path);
+        execute_source_vm(&source, file_path, format);
     } else {
-        execute_source(&source, file_path);
+        execute_source(&source, file_path, lib_paths, format);
     }
 }
 
-fn execute_source(source: &str, file_name: &str) {
-    let mut lexer = Lexer::new(source);
-    let tokens = match lexer.tokenize() {
-        Ok(tokens) => tokens,
+/// Lex and parse a source file, reporting the first error
+fn parse_source(source: &str, file_name: &str, format: ErrorFormat) -> Option<Program> {
+    let parsed = Lexer::new(source).tokenize().and_then(|tokens| Parser::new(tokens).parse());
+    match parsed {
+        Ok(program) => Some(program),
         Err(err) => {
-            eprintln!("{}: {}", "Lexer Error".red().bold(), err);
-            return;
+            report(&Diagnostic::from_error(&err).in_file(file_name), source, format);
+            None
         }
-    };
+    }
+}
 
-    let mut parser = Parser::new(tokens);
-    let ast = match parser.parse() {
-        Ok(ast) => ast,
-        Err(err) => {
-            eprintln!("{}: {}", "Parser Error".red().bold(), err);
-            return;
-        }
+fn execute_source(source: &str, file_name: &str, lib_paths: &[String], format: ErrorFormat) {
+    let Some(ast) = parse_source(source, file_name, format) else {
+        return;
     };
 
-    let mut interpreter = Interpreter::new();
+    let mut interpreter = new_interpreter(Some(file_name), lib_paths);
     if let Err(err) = interpreter.execute(&ast) {
-        eprintln!("{}: {}", "Runtime Error".red().bold(), err);
+        report_runtime_error(&err, &interpreter, file_name, source, format);
     }
 }
 
-fn execute_source_vm(source: &str, file_name: &str) {
-    let mut lexer = Lexer::new(source);
-    let tokens = match lexer.tokenize() {
-        Ok(tokens) => tokens,
-        Err(err) => {
-            eprintln!("{}: {}", "Lexer Error".red().bold(), err);
-            return;
-        }
-    };
-
-    let mut parser = Parser::new(tokens);
-    let ast = match parser.parse() {
-        Ok(ast) => ast,
-        Err(err) => {
-            eprintln!("{}: {}", "Parser Error".red().bold(), err);
-            return;
-        }
+fn execute_source_vm(source: &str, file_name: &str, format: ErrorFormat) {
+    let Some(ast) = parse_source(source, file_name, format) else {
+        return;
     };
 
     let chunk = match compile_program(&ast.statements) {
         Ok(chunk) => chunk,
         Err(err) => {
-            eprintln!("{}: {}", "Compiler Error".red().bold(), err);
+            report(&Diagnostic::from_error(&err).in_file(file_name), source, format);
             return;
         }
     };
@@ -125,15 +197,125 @@ fn execute_source_vm(source: &str, file_name: &str) {
     let mut vm = VirtualMachine::new();
     vm.load_chunk(chunk);
     if let Err(err) = vm.run() {
-        eprintln!("{}: {}", "VM Error".red().bold(), err);
+        let diagnostic = Diagnostic::from_error(&err)
+            .in_file(file_name)
+            .at_line(vm.error_line().unwrap_or(0));
+        report(&with_stack_trace(diagnostic, vm.stack_trace()), source, format);
+    }
+}
+
+/// Parse a file and list all of its syntax errors; exits with status 1 if there are any
+fn check_file(file_path: &str, format: ErrorFormat) {
+    let Some(source) = read_source(file_path, format) else {
+        std::process::exit(1);
+    };
+
+    let errors = match Lexer::new(&source).tokenize() {
+        Ok(tokens) => Parser::new(tokens).parse_recovering().1,
+        Err(err) => vec![err],
+    };
+    let diagnostics: Vec<Diagnostic> = errors.iter()
+        .map(|err| Diagnostic::from_error(err).in_file(file_path))
+        .collect();
+    report_all(&diagnostics, &source, format);
+
+    if format == ErrorFormat::Human {
+        if errors.is_empty() {
+            println!("{}: no syntax errors found", file_path);
+        } else {
+            eprintln!("{}: {} syntax error(s) found", file_path, errors.len());
+        }
+    }
+    if !errors.is_empty() {
+        std::process::exit(1);
+    }
+}
+
+/// Print a diagnostic to stderr, quoting the line it points at from `source`
+fn report(diagnostic: &Diagnostic, source: &str, format: ErrorFormat) {
+    report_all(std::slice::from_ref(diagnostic), source, format);
+}
+
+/// Print diagnostics to stderr, followed in human format by where to read
+/// more about their codes
+fn report_all(diagnostics: &[Diagnostic], source: &str, format: ErrorFormat) {
+    for diagnostic in diagnostics {
+        match format {
+            ErrorFormat::Json => eprintln!("{}", diagnostic.to_json()),
+            ErrorFormat::Human => {
+                let text = diagnostic.render(source);
+                let (header, body) = text.split_once('\n').unwrap_or((&text, ""));
+                eprintln!("{}", header.red().bold());
+                eprintln!("{}", body);
+            }
+        }
+    }
+
+    if format == ErrorFormat::Human {
+        let mut codes: Vec<&str> = diagnostics.iter()
+            .map(|diagnostic| diagnostic.code.as_str())
+            .filter(|code| error_codes::explain(code).is_some())
+            .collect();
+        codes.sort();
+        codes.dedup();
+        match codes.as_slice() {
+            [] => {}
+            [code] => eprintln!("For more information about this error, try `flowlang explain {}`.", code),
+            codes => {
+                eprintln!("Some errors have detailed explanations: {}.", codes.join(", "));
+                eprintln!("For more information about an error, try `flowlang explain {}`.", codes[0]);
+            }
+        }
     }
 }
 
-fn start_repl() {
+/// Print an error that escaped the interpreter. One raised inside an imported
+/// module quotes that module's file rather than `source`.
+fn report_runtime_error(err: &FlowError, interpreter: &Interpreter, file_name: &str, source: &str, format: ErrorFormat) {
+    let module_source = interpreter.error_file().and_then(|path| fs::read_to_string(path).ok());
+    let source = module_source.as_deref().unwrap_or(source);
+    report(&runtime_diagnostic(err, interpreter, file_name), source, format);
+}
+
+/// A report of an error that escaped the interpreter, at the expression it was
+/// raised in and with any fix the interpreter suggests
+fn runtime_diagnostic(err: &FlowError, interpreter: &Interpreter, file_name: &str) -> Diagnostic {
+    let file = match interpreter.error_file() {
+        Some(path) => display_path(path),
+        None => file_name.to_string(),
+    };
+    let mut diagnostic = Diagnostic::from_error(err).in_file(file);
+    if let Some(span) = interpreter.error_span() {
+        diagnostic = diagnostic.at_span(span);
+    }
+    if let Some(hint) = interpreter.error_hint() {
+        diagnostic = diagnostic.with_hint(hint);
+    }
+    with_stack_trace(diagnostic, interpreter.stack_trace())
+}
+
+/// `path` relative to the working directory when it is inside it, as module
+/// paths are stored in full
+fn display_path(path: &Path) -> String {
+    std::env::current_dir()
+        .and_then(|dir| dir.canonicalize())
+        .ok()
+        .and_then(|dir| path.strip_prefix(dir).ok())
+        .unwrap_or(path)
+        .display()
+        .to_string()
+}
+
+/// Add the calls an error escaped from, innermost first, as notes
+fn with_stack_trace(diagnostic: Diagnostic, trace: &[StackFrame]) -> Diagnostic {
+    trace.iter().fold(diagnostic, |diagnostic, frame| diagnostic.with_note(frame.to_string()))
+}
+
+fn start_repl(lib_paths: &[String]) {
     println!("{}", "FlowLang REPL v0.1.0".bright_blue().bold());
     println!("Type 'exit' to quit\n");
 
-    let mut interpreter = Interpreter::new();
+    let mut interpreter = new_interpreter(None, lib_paths);
     
     loop {
         print!("{} ", "flow>".bright_green().bold());
@@ -163,25 +345,11 @@ fn start_repl() {
 }
 
 fn execute_repl_line(input: &str, interpreter: &mut Interpreter) {
-    let mut lexer = Lexer::new(input);
-    let tokens = match lexer.tokenize() {
-        Ok(tokens) => tokens,
-        Err(err) => {
-            eprintln!("{}: {}", "Error".red().bold(), err);
-            return;
-        }
-    };
-
-    let mut parser = Parser::new(tokens);
-    let ast = match parser.parse() {
-        Ok(ast) => ast,
-        Err(err) => {
-            eprintln!("{}: {}", "Error".red().bold(), err);
-            return;
-        }
+    let Some(ast) = parse_source(input, "<repl>", ErrorFormat::Human) else {
+        return;
     };
 
     if let Err(err) = interpreter.execute(&ast) {
-        eprintln!("{}: {}", "Error".red().bold(), err);
+        report_runtime_error(&err, interpreter, "<repl>", input, ErrorFormat::Human);
     }
-}
\ No newline at end of file
+}
@@ -10,7 +10,7 @@
 use crate::value::{Value, FlowArray, FlowObject};
 use crate::error::{FlowError, Result};
 use std::collections::{HashMap, HashSet, VecDeque};
-use std::sync::{Arc, Weak, Mutex};
+use std::rc::{Rc, Weak};
 use std::time::{Instant, Duration};
 use std::ptr::NonNull;
 use std::alloc::{alloc, dealloc, Layout};
@@ -26,11 +26,11 @@ pub struct MemoryPool<T> {
 
 /// Value interning system for memory deduplication
 pub struct ValueInterner {
-    integers: HashMap<i64, Arc<Value>>,
-    floats: HashMap<u64, Arc<Value>>, // Using bits representation for float keys
-    strings: HashMap<String, Arc<Value>>,
-    booleans: [Arc<Value>; 2], // true and false
-    null: Arc<Value>,
+    integers: HashMap<i64, Rc<Value>>,
+    floats: HashMap<u64, Rc<Value>>, // Using bits representation for float keys
+    strings: HashMap<String, Rc<Value>>,
+    booleans: [Rc<Value>; 2], // true and false
+    null: Rc<Value>,
     intern_threshold: usize,
     access_counts: HashMap<*const Value, u32>,
 }
@@ -49,7 +49,7 @@ pub struct GenerationalGC {
 
 #[derive(Debug, Clone)]
 pub struct Generation {
-    objects: Vec<Arc<Value>>,
+    objects: Vec<Rc<Value>>,
     size: usize,
     capacity: usize,
     collection_count: u32,
@@ -207,37 +207,37 @@ impl ValueInterner {
             floats: HashMap::new(),
             strings: HashMap::new(),
             booleans: [
-                Arc::new(Value::Boolean(true)),
-                Arc::new(Value::Boolean(false)),
+                Rc::new(Value::Boolean(true)),
+                Rc::new(Value::Boolean(false)),
             ],
-            null: Arc::new(Value::Null),
+            null: Rc::new(Value::Null),
             intern_threshold: 10,
             access_counts: HashMap::new(),
         }
     }
     
-    pub fn intern_value(&mut self, value: Value) -> Arc<Value> {
+    pub fn intern_value(&mut self, value: Value) -> Rc<Value> {
         match value {
             Value::Integer(i) => {
                 self.integers.entry(i)
-                    .or_insert_with(|| Arc::new(Value::Integer(i)))
+                    .or_insert_with(|| Rc::new(Value::Integer(i)))
                     .clone()
             }
             Value::Float(f) => {
                 let bits = f.to_bits();
                 self.floats.entry(bits)
-                    .or_insert_with(|| Arc::new(Value::Float(f)))
+                    .or_insert_with(|| Rc::new(Value::Float(f)))
                     .clone()
             }
             Value::String(s) => {
                 self.strings.entry(s.clone())
-                    .or_insert_with(|| Arc::new(Value::String(s)))
+                    .or_insert_with(|| Rc::new(Value::String(s)))
                     .clone()
             }
             Value::Boolean(true) => self.booleans[0].clone(),
             Value::Boolean(false) => self.booleans[1].clone(),
             Value::Null => self.null.clone(),
-            _ => Arc::new(value), // Don't intern complex types
+            _ => Rc::new(value), // Don't intern complex types
         }
     }
     
@@ -261,14 +261,14 @@ impl ValueInterner {
     fn estimate_memory_saved(&self) -> usize {
         // Estimate memory saved through interning
         let string_savings: usize = self.strings.iter()
-            .map(|(s, arc)| s.len() * (Arc::strong_count(arc).saturating_sub(1)))
+            .map(|(s, arc)| s.len() * (Rc::strong_count(arc).saturating_sub(1)))
             .sum();
         
         let integer_savings = self.integers.len() * 8 * 
-            self.integers.values().map(|arc| Arc::strong_count(arc).saturating_sub(1)).sum::<usize>();
+            self.integers.values().map(|arc| Rc::strong_count(arc).saturating_sub(1)).sum::<usize>();
         
         let float_savings = self.floats.len() * 8 * 
-            self.floats.values().map(|arc| Arc::strong_count(arc).saturating_sub(1)).sum::<usize>();
+            self.floats.values().map(|arc| Rc::strong_count(arc).saturating_sub(1)).sum::<usize>();
         
         string_savings + integer_savings + float_savings
     }
@@ -297,7 +297,7 @@ impl GenerationalGC {
         }
     }
     
-    pub fn allocate(&mut self, value: Arc<Value>) -> Result<()> {
+    pub fn allocate(&mut self, value: Rc<Value>) -> Result<()> {
         // Allocate in young generation first
         self.young_generation.add_object(value)?;
         
@@ -321,10 +321,10 @@ impl GenerationalGC {
         let mut _freed_count = 0;
         
         for obj in self.young_generation.objects.drain(..) {
-            let ptr = Arc::as_ptr(&obj);
+            let ptr = Rc::as_ptr(&obj);
             if marked.contains(&ptr) {
                 // Object survived, consider for promotion
-                let should_promote = Arc::strong_count(&obj) > self.promotion_threshold as usize;
+                let should_promote = Rc::strong_count(&obj) > self.promotion_threshold as usize;
                 if should_promote {
                     self.old_generation.add_object(obj)?;
                 } else {
@@ -366,7 +366,7 @@ impl GenerationalGC {
         // Sweep old generation
         let initial_size = self.old_generation.objects.len();
         self.old_generation.objects.retain(|obj| {
-            let ptr = Arc::as_ptr(obj);
+            let ptr = Rc::as_ptr(obj);
             marked.contains(&ptr)
         });
         
@@ -400,12 +400,12 @@ impl GenerationalGC {
         unsafe {
             match &*ptr {
                 Value::Array(arr) => {
-                    for element in &arr.elements {
+                    for element in arr.elements().iter() {
                         self.mark_object(element as *const Value, marked);
                     }
                 }
                 Value::Object(obj) => {
-                    for value in obj.properties.values() {
+                    for value in obj.properties().values() {
                         self.mark_object(value as *const Value, marked);
                     }
                 }
@@ -414,9 +414,9 @@ impl GenerationalGC {
         }
     }
     
-    fn should_promote(&self, obj: &Arc<Value>) -> bool {
+    fn should_promote(&self, obj: &Rc<Value>) -> bool {
         // Simple promotion heuristic: promote after surviving multiple collections
-        Arc::strong_count(obj) > self.promotion_threshold as usize
+        Rc::strong_count(obj) > self.promotion_threshold as usize
     }
     
     pub fn add_root(&mut self, ptr: *const Value) {
@@ -443,7 +443,7 @@ impl Generation {
         }
     }
     
-    fn add_object(&mut self, obj: Arc<Value>) -> Result<()> {
+    fn add_object(&mut self, obj: Rc<Value>) -> Result<()> {
         if self.size >= self.capacity {
             return Err(FlowError::runtime_error("Generation capacity exceeded"));
         }
@@ -453,15 +453,15 @@ impl Generation {
         Ok(())
     }
     
-    fn estimate_object_size(&self, obj: &Arc<Value>) -> usize {
+    fn estimate_object_size(&self, obj: &Rc<Value>) -> usize {
         match obj.as_ref() {
             Value::Integer(_) => 8,
             Value::Float(_) => 8,
             Value::Boolean(_) => 1,
             Value::Null => 0,
             Value::String(s) => s.len(),
-            Value::Array(arr) => arr.elements.len() * 8, // Rough estimate
-            Value::Object(obj) => obj.properties.len() * 16, // Rough estimate
+            Value::Array(arr) => arr.elements().len() * 8, // Rough estimate
+            Value::Object(obj) => obj.properties().len() * 16, // Rough estimate
             _ => 64, // Default estimate
         }
     }
@@ -541,14 +541,14 @@ impl RefCountManager {
         unsafe {
             match &*current {
                 Value::Array(arr) => {
-                    for element in &arr.elements {
+                    for element in arr.elements().iter() {
                         if self.dfs_cycle_detection(element as *const Value, visited, path) {
                             return true;
                         }
                     }
                 }
                 Value::Object(obj) => {
-                    for value in obj.properties.values() {
+                    for value in obj.properties().values() {
                         if self.dfs_cycle_detection(value as *const Value, visited, path) {
                             return true;
                         }
@@ -661,7 +661,7 @@ impl AdvancedMemoryManager {
         }
     }
     
-    pub fn allocate_value(&mut self, value: Value) -> Result<Arc<Value>> {
+    pub fn allocate_value(&mut self, value: Value) -> Result<Rc<Value>> {
         let size = self.estimate_value_size(&value);
         self.total_allocated += size;
         self.current_usage += size;
@@ -673,7 +673,7 @@ impl AdvancedMemoryManager {
         let arc_value = if self.config.enable_interning && self.interner.should_intern(&value) {
             self.interner.intern_value(value)
         } else {
-            Arc::new(value)
+            Rc::new(value)
         };
         
         if self.config.enable_gc {
@@ -683,13 +683,13 @@ impl AdvancedMemoryManager {
         Ok(arc_value)
     }
     
-    pub fn deallocate_value(&mut self, value: &Arc<Value>) {
+    pub fn deallocate_value(&mut self, value: &Rc<Value>) {
         let size = self.estimate_value_size(value);
         self.total_freed += size;
         self.current_usage = self.current_usage.saturating_sub(size);
         
         if self.config.enable_gc {
-            let ptr = Arc::as_ptr(value);
+            let ptr = Rc::as_ptr(value);
             if self.ref_counter.decrement_ref(ptr) {
                 // Object can be freed immediately
             }
@@ -751,8 +751,8 @@ impl AdvancedMemoryManager {
             Value::Boolean(_) => 1,
             Value::Null => 0,
             Value::String(s) => s.len() + 24, // String overhead
-            Value::Array(arr) => arr.elements.len() * 8 + 24, // Vec overhead
-            Value::Object(obj) => obj.properties.len() * 16 + 24, // HashMap overhead
+            Value::Array(arr) => arr.elements().len() * 8 + 24, // Vec overhead
+            Value::Object(obj) => obj.properties().len() * 16 + 24, // HashMap overhead
             _ => 64, // Default estimate
         }
     }
@@ -809,7 +809,7 @@ pub struct MemoryStats {
 /// Integration trait for VM memory management
 pub trait MemoryManagement {
     fn set_memory_manager(&mut self, manager: AdvancedMemoryManager);
-    fn allocate_managed(&mut self, value: Value) -> Result<Arc<Value>>;
+    fn allocate_managed(&mut self, value: Value) -> Result<Rc<Value>>;
     fn force_garbage_collection(&mut self) -> Result<()>;
     fn get_memory_usage(&self) -> MemoryStats;
 }
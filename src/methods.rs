@@ -0,0 +1,260 @@
+//! Methods of arrays and objects, shared by the interpreter and the VM
+//!
+//! Each engine passes `call` to run function values, such as the callback
+//! given to `map` or a function stored in an object, the way it runs any
+//! other call.
+
+use crate::error::{FlowError, Result};
+use crate::value::{FlowArray, Value};
+
+/// `object.method(arguments)`, with the object and arguments already evaluated
+pub fn call_method<F>(object: &Value, method: &str, arguments: Vec<Value>, mut call: F) -> Result<Value>
+where
+    F: FnMut(&Value, Vec<Value>) -> Result<Value>,
+{
+    match object {
+        Value::Array(arr) => {
+            // push, pop, reverse and sort change the array in place
+            match method {
+                "push" => {
+                    if arguments.len() != 1 {
+                        return Err(FlowError::argument_count_error("push() expects exactly 1 argument".to_string()));
+                    }
+                    let value = arguments[0].clone();
+                    arr.push(value);
+                    Ok(Value::Array(arr.clone()))
+                }
+                "pop" => {
+                    if !arguments.is_empty() {
+                        return Err(FlowError::argument_count_error("pop() expects no arguments".to_string()));
+                    }
+                    if let Some(value) = arr.pop() {
+                        Ok(value)
+                    } else {
+                        Ok(Value::Null)
+                    }
+                }
+                "length" => {
+                    if !arguments.is_empty() {
+                        return Err(FlowError::argument_count_error("length() expects no arguments".to_string()));
+                    }
+                    Ok(Value::Integer(arr.len() as i64))
+                }
+                "slice" => {
+                    if arguments.len() != 2 {
+                        return Err(FlowError::argument_count_error("slice() expects exactly 2 arguments (start, end)".to_string()));
+                    }
+                    let start_val = arguments[0].clone();
+                    let end_val = arguments[1].clone();
+                    
+                    if let (Value::Integer(start), Value::Integer(end)) = (start_val, end_val) {
+                        match arr.slice(start as usize, end as usize) {
+                            Ok(sliced_arr) => Ok(Value::Array(sliced_arr)),
+                            Err(err) => Err(FlowError::runtime_error(err))
+                        }
+                    } else {
+                        Err(FlowError::type_error("slice() arguments must be integers".to_string()))
+                    }
+                }
+                "map" => {
+                    if arguments.len() != 1 {
+                        return Err(FlowError::argument_count_error("map() expects exactly 1 argument (function)".to_string()));
+                    }
+                    let func = arguments[0].clone();
+                    let mut result = Vec::new();
+                    
+                    for element in arr.to_vec() {
+                        let mapped_value = match &func {
+                            Value::Lambda { .. } | Value::Function { .. } | Value::BytecodeFunction { .. } => call(&func, vec![element.clone()])?,
+                            _ => return Err(FlowError::type_error("map() argument must be a function".to_string()))
+                        };
+                        result.push(mapped_value);
+                    }
+                    
+                    Ok(Value::Array(FlowArray::from_values(result)))
+                }
+                "filter" => {
+                    if arguments.len() != 1 {
+                        return Err(FlowError::argument_count_error("filter() expects exactly 1 argument (function)".to_string()));
+                    }
+                    let func = arguments[0].clone();
+                    let mut result = Vec::new();
+                    
+                    for element in arr.to_vec() {
+                        let should_include = match &func {
+                            Value::Lambda { .. } | Value::Function { .. } | Value::BytecodeFunction { .. } => call(&func, vec![element.clone()])?,
+                            _ => return Err(FlowError::type_error("filter() argument must be a function".to_string()))
+                        };
+                        
+                        if should_include.is_truthy() {
+                            result.push(element.clone());
+                        }
+                    }
+                    
+                    Ok(Value::Array(FlowArray::from_values(result)))
+                }
+                "forEach" => {
+                    if arguments.len() != 1 {
+                        return Err(FlowError::argument_count_error("forEach() expects exactly 1 argument (function)".to_string()));
+                    }
+                    let func = arguments[0].clone();
+                    
+                    for element in arr.to_vec() {
+                        match &func {
+                            Value::Lambda { .. } | Value::Function { .. } | Value::BytecodeFunction { .. } => {
+                                call(&func, vec![element.clone()])?;
+                            }
+                            _ => return Err(FlowError::type_error("forEach() argument must be a function".to_string()))
+                        };
+                    }
+                    
+                    Ok(Value::Null)
+                }
+                "find" => {
+                    if arguments.len() != 1 {
+                        return Err(FlowError::argument_count_error("find() expects exactly 1 argument (function)".to_string()));
+                    }
+                    let func = arguments[0].clone();
+                    
+                    for element in arr.to_vec() {
+                        let matches = match &func {
+                            Value::Lambda { .. } | Value::Function { .. } | Value::BytecodeFunction { .. } => call(&func, vec![element.clone()])?,
+                            _ => return Err(FlowError::type_error("find() argument must be a function".to_string()))
+                        };
+                        
+                        if matches.is_truthy() {
+                            return Ok(element.clone());
+                        }
+                    }
+                    
+                    Ok(Value::Null)
+                }
+                "includes" => {
+                    if arguments.len() != 1 {
+                        return Err(FlowError::argument_count_error("includes() expects exactly 1 argument".to_string()));
+                    }
+                    let value = arguments[0].clone();
+                    Ok(Value::Boolean(arr.contains(&value)))
+                }
+                "indexOf" => {
+                    if arguments.len() != 1 {
+                        return Err(FlowError::argument_count_error("indexOf() expects exactly 1 argument".to_string()));
+                    }
+                    let value = arguments[0].clone();
+                    if let Some(index) = arr.index_of(&value) {
+                        Ok(Value::Integer(index as i64))
+                    } else {
+                        Ok(Value::Integer(-1))
+                    }
+                }
+                "join" => {
+                    let separator = if arguments.is_empty() {
+                        ",".to_string()
+                    } else if arguments.len() == 1 {
+                        let sep_val = arguments[0].clone();
+                        if let Value::String(s) = sep_val {
+                            s
+                        } else {
+                            return Err(FlowError::type_error("join() separator must be a string".to_string()));
+                        }
+                    } else {
+                        return Err(FlowError::argument_count_error("join() expects 0 or 1 arguments".to_string()));
+                    };
+                    
+                    let strings: Vec<String> = arr.elements().iter().map(|v| v.to_string()).collect();
+                    Ok(Value::String(strings.join(&separator)))
+                }
+                "reverse" => {
+                    if !arguments.is_empty() {
+                        return Err(FlowError::argument_count_error("reverse() expects no arguments".to_string()));
+                    }
+                    // In place, like push and pop; the array itself is returned for chaining
+                    arr.reverse();
+                    Ok(Value::Array(arr.clone()))
+                }
+                "sort" => {
+                    if !arguments.is_empty() {
+                        return Err(FlowError::argument_count_error("sort() expects no arguments".to_string()));
+                    }
+                    arr.sort();
+                    Ok(Value::Array(arr.clone()))
+                }
+                "copy" => {
+                    if !arguments.is_empty() {
+                        return Err(FlowError::argument_count_error("copy() expects no arguments".to_string()));
+                    }
+                    Ok(Value::Array(arr.copy()))
+                }
+                "deep_copy" => {
+                    if !arguments.is_empty() {
+                        return Err(FlowError::argument_count_error("deep_copy() expects no arguments".to_string()));
+                    }
+                    Ok(object.deep_copy())
+                }
+                _ => Err(FlowError::invalid_operation(format!("Array has no method '{}'", method)))
+            }
+        }
+        Value::Object(obj) => {
+            // First check if the method exists as a property in the object
+            if let Some(func_value @ (Value::Function { .. } | Value::Lambda { .. } | Value::BytecodeFunction { .. })) = obj.get(method) {
+                return call(&func_value, arguments);
+            }
+            
+            match method {
+                "keys" => {
+                    if !arguments.is_empty() {
+                        return Err(FlowError::argument_count_error("keys() expects no arguments".to_string()));
+                    }
+                    let keys: Vec<Value> = obj.keys()
+                        .into_iter()
+                        .map(Value::String)
+                        .collect();
+                    Ok(Value::Array(FlowArray::from_values(keys)))
+                }
+                "has" => {
+                    if arguments.len() != 1 {
+                        return Err(FlowError::argument_count_error("has() expects exactly 1 argument".to_string()));
+                    }
+                    let key_val = arguments[0].clone();
+                    if let Value::String(key) = key_val {
+                        Ok(Value::Boolean(obj.contains_key(&key)))
+                    } else {
+                        Err(FlowError::type_error("Object key must be a string".to_string()))
+                    }
+                }
+                "remove" => {
+                    if arguments.len() != 1 {
+                        return Err(FlowError::argument_count_error("remove() expects exactly 1 argument".to_string()));
+                    }
+                    let key_val = arguments[0].clone();
+                    if let Value::String(key) = key_val {
+                        if let Some(value) = obj.remove(&key) {
+                            Ok(value)
+                        } else {
+                            Ok(Value::Null)
+                        }
+                    } else {
+                        Err(FlowError::type_error("Object key must be a string".to_string()))
+                    }
+                }
+                "copy" => {
+                    if !arguments.is_empty() {
+                        return Err(FlowError::argument_count_error("copy() expects no arguments".to_string()));
+                    }
+                    Ok(Value::Object(obj.copy()))
+                }
+                "deep_copy" => {
+                    if !arguments.is_empty() {
+                        return Err(FlowError::argument_count_error("deep_copy() expects no arguments".to_string()));
+                    }
+                    Ok(object.deep_copy())
+                }
+                _ => Err(FlowError::invalid_operation(format!("Object has no method '{}'", method)))
+            }
+        }
+        _ => Err(FlowError::type_error(format!(
+            "Cannot call method '{}' on {}",
+            method, object.type_name()
+        )))
+    }
+}
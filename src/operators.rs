@@ -0,0 +1,239 @@
+//! Operators shared by the interpreter and the VM
+//!
+//! `**`, `//`, `%`, the bitwise operators, `in`, property access and spreading
+//! with `...`, applied to operands that have already been evaluated. Integer
+//! results that overflow an `i64` are promoted to big integers where the
+//! operator can produce one.
+
+use crate::ast::BinaryOperator;
+use crate::bigint::BigInt;
+use crate::error::{FlowError, Result};
+use crate::value::Value;
+use std::collections::HashMap;
+
+/// `base ** exponent`. Integers raised to a non-negative integer stay exact;
+/// anything else is computed as a float.
+pub fn power(base: &Value, exponent: &Value) -> Result<Value> {
+    match (base, exponent) {
+        (Value::Integer(base), Value::Integer(exponent)) if *exponent >= 0 => {
+            let exponent = exact_exponent(*exponent)?;
+            Ok(match base.checked_pow(exponent) {
+                Some(result) => Value::Integer(result),
+                None => Value::BigInteger(BigInt::from_i64(*base).pow(exponent)),
+            })
+        }
+        (Value::BigInteger(base), Value::Integer(exponent)) if *exponent >= 0 => {
+            Ok(Value::BigInteger(base.pow(exact_exponent(*exponent)?)))
+        }
+        _ => match (to_f64(base), to_f64(exponent)) {
+            (Some(base), Some(exponent)) => Ok(Value::Float(base.powf(exponent))),
+            _ => Err(FlowError::type_error(format!(
+                "Cannot raise {} to the power of {}",
+                base.type_name(),
+                exponent.type_name()
+            ))),
+        },
+    }
+}
+
+fn exact_exponent(exponent: i64) -> Result<u32> {
+    u32::try_from(exponent)
+        .map_err(|_| FlowError::invalid_operation(format!("Exponent {} is too large", exponent)))
+}
+
+/// `left // right`: division rounded down. Two integers give an integer;
+/// anything else gives a whole-numbered float.
+pub fn floor_divide(left: &Value, right: &Value) -> Result<Value> {
+    match (left, right) {
+        (Value::Integer(_), Value::Integer(0)) => Err(FlowError::DivisionByZero),
+        (Value::Integer(a), Value::Integer(b)) => match a.checked_div(*b) {
+            Some(quotient) if a % b != 0 && (*a < 0) != (*b < 0) => Ok(Value::Integer(quotient - 1)),
+            Some(quotient) => Ok(Value::Integer(quotient)),
+            // i64::MIN // -1
+            None => Ok(Value::BigInteger(BigInt::from_i64(*a) * BigInt::from_i64(-1))),
+        },
+        _ => match (to_f64(left), to_f64(right)) {
+            (Some(_), Some(0.0)) => Err(FlowError::DivisionByZero),
+            (Some(a), Some(b)) => Ok(Value::Float((a / b).floor())),
+            _ => Err(FlowError::type_error(format!(
+                "Cannot divide {} and {}",
+                left.type_name(),
+                right.type_name()
+            ))),
+        },
+    }
+}
+
+/// `left % right`: the remainder of `left // right`, so it takes the sign of
+/// the divisor. Works on integers of any size.
+pub fn modulo(left: &Value, right: &Value) -> Result<Value> {
+    match (left, right) {
+        (Value::Integer(_), Value::Integer(0)) => Err(FlowError::DivisionByZero),
+        (Value::Integer(a), Value::Integer(b)) => {
+            // wrapping_rem only wraps for i64::MIN % -1, which is 0
+            let remainder = a.wrapping_rem(*b);
+            if remainder != 0 && (remainder < 0) != (*b < 0) {
+                Ok(Value::Integer(remainder + b))
+            } else {
+                Ok(Value::Integer(remainder))
+            }
+        }
+        (Value::Integer(_) | Value::BigInteger(_), Value::Integer(_) | Value::BigInteger(_)) => {
+            let (a, b) = (to_big(left), to_big(right));
+            if b.is_zero() {
+                return Err(FlowError::DivisionByZero);
+            }
+            let mut remainder = a % b.clone();
+            if !remainder.is_zero() && remainder.is_negative() != b.is_negative() {
+                remainder = remainder + b;
+            }
+            // No bigger than the divisor, so usually back within an i64
+            Ok(match remainder.to_i64() {
+                Some(remainder) => Value::Integer(remainder),
+                None => Value::BigInteger(remainder),
+            })
+        }
+        _ => Err(FlowError::type_error(format!(
+            "Cannot modulo {} and {}",
+            left.type_name(),
+            right.type_name()
+        ))),
+    }
+}
+
+/// `&`, `|` or `^` on two integers, or on two booleans
+pub fn bitwise(operator: &BinaryOperator, left: &Value, right: &Value) -> Result<Value> {
+    match (left, right) {
+        (Value::Integer(a), Value::Integer(b)) => Ok(Value::Integer(match operator {
+            BinaryOperator::BitAnd => a & b,
+            BinaryOperator::BitOr => a | b,
+            _ => a ^ b,
+        })),
+        (Value::Boolean(a), Value::Boolean(b)) => Ok(Value::Boolean(match operator {
+            BinaryOperator::BitAnd => a & b,
+            BinaryOperator::BitOr => a | b,
+            _ => a ^ b,
+        })),
+        _ => Err(FlowError::type_error(format!(
+            "Cannot apply '{}' to {} and {}",
+            operator,
+            left.type_name(),
+            right.type_name()
+        ))),
+    }
+}
+
+/// `value << amount`, promoting to a big integer rather than losing bits
+pub fn shift_left(value: &Value, amount: &Value) -> Result<Value> {
+    let (value, amount) = shift_operands(BinaryOperator::ShiftLeft, value, amount)?;
+    if amount < 64 && (value << amount) >> amount == value {
+        return Ok(Value::Integer(value << amount));
+    }
+    let amount = u32::try_from(amount)
+        .map_err(|_| FlowError::invalid_operation(format!("Shift amount {} is too large", amount)))?;
+    Ok(Value::BigInteger(BigInt::from_i64(value) * BigInt::from_i64(2).pow(amount)))
+}
+
+/// `value >> amount`, rounding down like division by a power of two
+pub fn shift_right(value: &Value, amount: &Value) -> Result<Value> {
+    let (value, amount) = shift_operands(BinaryOperator::ShiftRight, value, amount)?;
+    Ok(Value::Integer(value >> amount.min(63)))
+}
+
+fn shift_operands(operator: BinaryOperator, value: &Value, amount: &Value) -> Result<(i64, i64)> {
+    match (value, amount) {
+        (Value::Integer(_), Value::Integer(amount)) if *amount < 0 => {
+            Err(FlowError::invalid_operation(format!("Cannot shift by a negative amount ({})", amount)))
+        }
+        (Value::Integer(value), Value::Integer(amount)) => Ok((*value, *amount)),
+        _ => Err(FlowError::type_error(format!(
+            "Cannot apply '{}' to {} and {}",
+            operator,
+            value.type_name(),
+            amount.type_name()
+        ))),
+    }
+}
+
+/// `~value`, flipping every bit of an integer
+pub fn bit_not(value: &Value) -> Result<Value> {
+    match value {
+        Value::Integer(i) => Ok(Value::Integer(!i)),
+        _ => Err(FlowError::type_error(format!("Cannot apply '~' to {}", value.type_name()))),
+    }
+}
+
+/// `item in collection`: an element of an array, a key of an object, or a
+/// substring of a string
+pub fn contains(item: &Value, collection: &Value) -> Result<bool> {
+    match (collection, item) {
+        (Value::Array(array), _) => Ok(array.contains(item)),
+        (Value::Object(object), Value::String(key)) => Ok(object.contains_key(key)),
+        (Value::String(text), Value::String(part)) => Ok(text.contains(part.as_str())),
+        (Value::Object(_) | Value::String(_), _) => Err(FlowError::type_error(format!(
+            "Cannot look for {} in {}",
+            item.type_name(),
+            collection.type_name()
+        ))),
+        _ => Err(FlowError::type_error(format!(
+            "Cannot look for a value in {}",
+            collection.type_name()
+        ))),
+    }
+}
+
+/// `object.property`: an entry of an object, null when it is missing, or
+/// the `length` of an array
+pub fn get_property(object: &Value, property: &str) -> Result<Value> {
+    match object {
+        Value::Object(object) => Ok(object.get(property).unwrap_or(Value::Null)),
+        Value::Array(array) => match property {
+            "length" => Ok(Value::Integer(array.len() as i64)),
+            _ => Err(FlowError::runtime_error(format!("Array has no property '{}'", property))),
+        },
+        _ => Err(FlowError::type_error(format!(
+            "Cannot access property '{}' on {}",
+            property,
+            object.type_name()
+        ))),
+    }
+}
+
+/// The values `...value` stands for in an array literal or argument list
+pub fn spread_elements(value: &Value) -> Result<Vec<Value>> {
+    match value {
+        Value::Array(array) => Ok(array.to_vec()),
+        _ => Err(FlowError::type_error(format!(
+            "Cannot spread {} with '...': only arrays can be spread into arrays and arguments",
+            value.type_name()
+        ))),
+    }
+}
+
+/// The entries `...value` stands for in an object literal
+pub fn spread_entries(value: &Value) -> Result<HashMap<String, Value>> {
+    match value {
+        Value::Object(object) => Ok(object.to_map()),
+        _ => Err(FlowError::type_error(format!(
+            "Cannot spread {} with '...': only objects can be spread into objects",
+            value.type_name()
+        ))),
+    }
+}
+
+fn to_big(value: &Value) -> BigInt {
+    match value {
+        Value::BigInteger(bi) => bi.clone(),
+        Value::Integer(i) => BigInt::from_i64(*i),
+        _ => unreachable!("only called with integers"),
+    }
+}
+
+fn to_f64(value: &Value) -> Option<f64> {
+    match value {
+        Value::Integer(i) => Some(*i as f64),
+        Value::BigInteger(bi) => Some(bi.to_f64()),
+        Value::Float(f) => Some(*f),
+        _ => None,
+    }
+}
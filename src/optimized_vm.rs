@@ -521,8 +521,8 @@ impl VMExtensions for VirtualMachine {
             Value::Integer(i) => *i != 0,
             Value::BigInteger(bi) => !bi.is_zero(),
             Value::String(s) => !s.is_empty(),
-            Value::Array(array) => !array.elements.is_empty(),
-            Value::Object(object) => !object.properties.is_empty(),
+            Value::Array(array) => !array.elements().is_empty(),
+            Value::Object(object) => !object.properties().is_empty(),
             _ => true,
         }
     }
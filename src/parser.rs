@@ -1,28 +1,74 @@
 use crate::ast::*;
 use crate::error::{FlowError, Result};
-use crate::lexer::{Token, TokenWithPos};
+use crate::diagnostic::closest_match;
+use crate::lexer::{keyword_token, StringPart, Token, TokenWithPos, KEYWORDS};
+
+/// Keywords a statement can start with, for suggesting fixes to misspelled ones
+const STATEMENT_KEYWORDS: &[&str] = &[
+    "let", "def", "if", "while", "for", "show", "return", "import", "export",
+    "try", "throw", "break", "continue",
+];
 
 pub struct Parser {
     tokens: Vec<TokenWithPos>,
     current: usize,
+    loop_depth: usize,
+    catch_depth: usize,
+    /// Errors of the statements skipped so far, in source order
+    errors: Vec<FlowError>,
 }
 
 impl Parser {
     pub fn new(tokens: Vec<TokenWithPos>) -> Self {
-        Self { tokens, current: 0 }
+        Self { tokens, current: 0, loop_depth: 0, catch_depth: 0, errors: Vec::new() }
+    }
+    
+    /// Where the next token starts
+    fn current_position(&self) -> Position {
+        self.tokens.get(self.current)
+            .or_else(|| self.tokens.last())
+            .map(|token| Position { line: token.line, column: token.column, offset: token.offset })
+            .unwrap_or(Position { line: 1, column: 1, offset: 0 })
+    }
+    
+    /// A syntax error at the next token
+    fn error_at_current(&self, message: impl Into<String>) -> FlowError {
+        let position = self.current_position();
+        FlowError::parser_error_at(position.line, position.column, message)
+    }
+    
+    /// A syntax error at the token just consumed
+    fn error_at_previous(&self, message: impl Into<String>) -> FlowError {
+        let token = self.previous();
+        FlowError::parser_error_at(token.line, token.column, message)
     }
     
-    fn current_line(&self) -> usize {
-        if self.current < self.tokens.len() {
-            self.tokens[self.current].line
-        } else if !self.tokens.is_empty() {
-            self.tokens[self.tokens.len() - 1].line
+    /// The span from `start` to the end of the last token consumed, leaving out
+    /// the newline that ends a statement
+    fn span_from(&self, start: Position) -> Span {
+        let end = self.tokens[..self.current]
+            .iter()
+            .rev()
+            .find(|token| token.token != Token::Newline)
+            .map(|token| token.end)
+            .unwrap_or(start);
+        Span { start, end }
+    }
+    
+    /// Parse the whole program, failing with the first syntax error
+    pub fn parse(&mut self) -> Result<Program> {
+        let (program, mut errors) = self.parse_recovering();
+        if errors.is_empty() {
+            Ok(program)
         } else {
-            1
+            Err(errors.remove(0))
         }
     }
     
-    pub fn parse(&mut self) -> Result<Program> {
+    /// Parse the whole program, skipping past each statement with a syntax
+    /// error to the next newline or block `end`. Returns every error, in source
+    /// order, and the program without the statements that had them.
+    pub fn parse_recovering(&mut self) -> (Program, Vec<FlowError>) {
         let mut statements = Vec::new();
         
         while !self.is_at_end() {
@@ -32,13 +78,96 @@ impl Parser {
                 continue;
             }
             
-            statements.push(self.statement()?);
+            statements.extend(self.recovering_statement());
         }
         
-        Ok(Program { statements })
+        (Program { statements }, std::mem::take(&mut self.errors))
+    }
+    
+    /// Parse a statement, or record its error and skip to where the next one starts
+    fn recovering_statement(&mut self) -> Option<Statement> {
+        let start = self.current;
+        match self.statement() {
+            Ok(statement) => Some(statement),
+            Err(error) => {
+                self.errors.push(error);
+                self.synchronize(start);
+                None
+            }
+        }
+    }
+    
+    /// Skip a statement that failed to parse, starting over from its first token:
+    /// through the end of its line, or of its `end` if it opens a block. Stops
+    /// early at an `end`, `else`, `catch` or `finally` of the enclosing block.
+    fn synchronize(&mut self, start: usize) {
+        self.current = start;
+        let mut depth = 0;
+        while !self.is_at_end() {
+            let token = &self.peek().token;
+            let at_line_start = self.current == start || self.previous().token == Token::Newline;
+            let opens_block = match token {
+                Token::Def | Token::While | Token::For | Token::Try => true,
+                // A misspelled block keyword still has an `end`
+                Token::Identifier(_) if self.current == start => {
+                    matches!(self.misspelled_keyword(start), Some("def" | "if" | "while" | "for" | "try"))
+                }
+                // `else if` shares the `end` of the `if` it follows
+                Token::If => at_line_start,
+                // A block lambda: `(x) => do ... end`
+                Token::Do => self.previous().token == Token::Arrow,
+                _ => false,
+            };
+            if depth == 0 && matches!(token, Token::End | Token::Else | Token::Catch | Token::Finally) {
+                // Always move on, so a stray closing keyword is skipped
+                if self.current == start {
+                    self.advance();
+                }
+                return;
+            }
+            let closes_block = matches!(token, Token::End);
+            
+            self.advance();
+            if opens_block {
+                depth += 1;
+            } else if closes_block {
+                depth -= 1;
+            }
+            if depth == 0 && self.previous().token == Token::Newline {
+                return;
+            }
+        }
     }
     
     fn statement(&mut self) -> Result<Statement> {
+        let start = self.current_position();
+        let first = self.current;
+        let kind = self.statement_kind().map_err(|error| match self.misspelled_keyword(first) {
+            Some(keyword) => error.with_hint(format!("did you mean `{}`?", keyword)),
+            None => error,
+        })?;
+        Ok(Statement::new(kind, self.span_from(start)))
+    }
+    
+    /// The keyword a statement such as `whlie x do` probably meant: its first
+    /// word is close to a keyword and is followed directly by a value
+    fn misspelled_keyword(&self, first: usize) -> Option<&'static str> {
+        let word = match self.tokens.get(first).map(|token| &token.token) {
+            Some(Token::Identifier(word)) => word,
+            _ => return None,
+        };
+        let followed_by_value = matches!(
+            self.tokens.get(first + 1).map(|token| &token.token),
+            Some(Token::Identifier(_) | Token::Integer(_) | Token::Float(_) | Token::String(_)
+                | Token::InterpolatedString(_) | Token::Boolean(_) | Token::Null)
+        );
+        if !followed_by_value {
+            return None;
+        }
+        closest_match(word, STATEMENT_KEYWORDS.iter().copied())
+    }
+    
+    fn statement_kind(&mut self) -> Result<StatementKind> {
         match &self.peek().token {
             Token::Let => self.variable_declaration(),
             Token::Def => self.function_declaration(),
@@ -47,44 +176,90 @@ impl Parser {
             Token::For => self.for_statement(),
             Token::Show => self.show_statement(),
             Token::Return => self.return_statement(),
+            Token::Break | Token::Continue => self.loop_control_statement(),
             Token::Import => self.import_statement(),
             Token::Export => self.export_statement(),
             Token::Try => self.try_catch_statement(),
+            Token::Throw => self.throw_statement(),
             _ => {
                 let expr = self.expression()?;
+                if let Some(operator) = self.assignment_operator() {
+                    return self.assignment_statement(expr, operator);
+                }
                 self.consume_newline_or_eof()?;
-                Ok(Statement::Expression(expr))
+                Ok(StatementKind::Expression(expr))
             }
         }
     }
+
+    /// Returns `Some(None)` for plain `=` and `Some(Some(op))` for compound forms like `+=`
+    fn assignment_operator(&self) -> Option<Option<BinaryOperator>> {
+        match self.peek().token {
+            Token::Assign => Some(None),
+            Token::PlusAssign => Some(Some(BinaryOperator::Add)),
+            Token::MinusAssign => Some(Some(BinaryOperator::Subtract)),
+            Token::MultiplyAssign => Some(Some(BinaryOperator::Multiply)),
+            Token::DivideAssign => Some(Some(BinaryOperator::Divide)),
+            Token::ModuloAssign => Some(Some(BinaryOperator::Modulo)),
+            _ => None,
+        }
+    }
+
+    fn assignment_statement(&mut self, target: Expression, operator: Option<BinaryOperator>) -> Result<StatementKind> {
+        if !matches!(
+            target.kind,
+            ExpressionKind::Identifier(_)
+                | ExpressionKind::Index { optional: false, .. }
+                | ExpressionKind::PropertyAccess { optional: false, .. }
+        ) {
+            let start = target.span.start;
+            return Err(FlowError::parser_error_at(start.line, start.column, "Invalid assignment target"));
+        }
+
+        self.advance(); // consume '=' or compound operator
+        let value = self.expression()?;
+        self.consume_newline_or_eof()?;
+
+        Ok(StatementKind::Assignment { target, operator, value })
+    }
     
-    fn variable_declaration(&mut self) -> Result<Statement> {
+    fn variable_declaration(&mut self) -> Result<StatementKind> {
         self.consume(&Token::Let, "Expected 'let'")?;
         
+        if matches!(self.peek().token, Token::LeftBracket | Token::LeftBrace) {
+            let pattern = self.pattern()?;
+            self.consume(&Token::Be, "Expected 'be' after pattern")?;
+            let value = self.expression()?;
+            self.consume_newline_or_eof()?;
+            return Ok(StatementKind::Destructuring { pattern, value });
+        }
+        
         let name = match self.advance() {
             Token::Identifier(name) => name.clone(),
             _ => {
-                let line = self.current_line();
-                return Err(FlowError::parser_error_at_line(line, "Expected variable name"));
+                return Err(self.error_at_previous("Expected variable name"));
             }
         };
         
+        if self.check(&Token::Assign) {
+            return Err(self.error_at_current("Expected 'be' after variable name")
+                .with_hint(format!("variables are declared with `be`, as in `let {} be ...`", name)));
+        }
         self.consume(&Token::Be, "Expected 'be' after variable name")?;
         
         let value = self.expression()?;
         self.consume_newline_or_eof()?;
         
-        Ok(Statement::VariableDeclaration { name, value })
+        Ok(StatementKind::VariableDeclaration { name, value })
     }
     
-    fn function_declaration(&mut self) -> Result<Statement> {
+    fn function_declaration(&mut self) -> Result<StatementKind> {
         self.consume(&Token::Def, "Expected 'def'")?;
         
         let name = match self.advance() {
             Token::Identifier(name) => name.clone(),
             _ => {
-                let line = self.current_line();
-                return Err(FlowError::parser_error_at_line(line, "Expected function name"));
+                return Err(self.error_at_previous("Expected function name"));
             }
         };
         
@@ -94,35 +269,8 @@ impl Parser {
             self.advance(); // consume 'with'
             
             loop {
-                // Check for variadic parameter
-                let is_variadic = if self.check(&Token::Ellipsis) {
-                    self.advance();
-                    true
-                } else {
-                    false
-                };
-                
-                let param_name = match self.advance() {
-                    Token::Identifier(param) => param.clone(),
-                    _ => {
-                        let line = self.current_line();
-                        return Err(FlowError::parser_error_at_line(line, "Expected parameter name"));
-                    }
-                };
-                
-                // Check for default value
-                let default_value = if self.check(&Token::Assign) {
-                    self.advance(); // consume '='
-                    Some(self.expression()?)
-                } else {
-                    None
-                };
-                
-                parameters.push(Parameter {
-                    name: param_name,
-                    default_value,
-                    is_variadic,
-                });
+                self.parameter_after(&parameters)?;
+                parameters.push(self.parameter()?);
                 
                 if self.check(&Token::Comma) {
                     self.advance();
@@ -137,33 +285,193 @@ impl Parser {
         }
         
         self.consume(&Token::Do, "Expected 'do' after function parameters")?;
+        let body = self.function_body("Expected 'end' to close function")?;
+        self.consume_newline_or_eof()?;
+        
+        Ok(StatementKind::FunctionDeclaration { name, parameters, body })
+    }
+    
+    /// Fail if another parameter follows `...rest`, which takes every
+    /// remaining argument
+    fn parameter_after(&self, parameters: &[Parameter]) -> Result<()> {
+        if parameters.last().is_some_and(|param| param.is_variadic) {
+            return Err(self.error_at_current("A '...' parameter must be the last one"));
+        }
+        Ok(())
+    }
+    
+    /// A function or lambda parameter: `name`, `name = default`, `...rest`,
+    /// or a destructuring pattern such as `{width, height}`
+    fn parameter(&mut self) -> Result<Parameter> {
+        // Check for variadic parameter
+        let is_variadic = if self.check(&Token::Ellipsis) {
+            self.advance();
+            true
+        } else {
+            false
+        };
+        
+        let (name, pattern) = match &self.peek().token {
+            Token::LeftBracket | Token::LeftBrace => {
+                let pattern = self.pattern()?;
+                (pattern.to_string(), Some(pattern))
+            }
+            Token::Identifier(name) => {
+                let name = name.clone();
+                self.advance();
+                (name, None)
+            }
+            _ => {
+                self.advance();
+                return Err(self.error_at_previous("Expected parameter name"));
+            }
+        };
+        
+        // Check for default value
+        let default_value = if self.check(&Token::Assign) {
+            self.advance(); // consume '='
+            Some(self.expression()?)
+        } else {
+            None
+        };
+        
+        Ok(Parameter { name, default_value, is_variadic, pattern })
+    }
+    
+    /// A destructuring pattern, or a plain variable name inside one
+    fn pattern(&mut self) -> Result<Pattern> {
+        match &self.peek().token {
+            Token::LeftBracket => self.array_pattern(),
+            Token::LeftBrace => self.object_pattern(),
+            Token::Identifier(name) => {
+                let name = name.clone();
+                self.advance();
+                Ok(Pattern::Identifier(name))
+            }
+            _ => Err(self.error_at_current("Expected a variable name, '[' or '{' in pattern")),
+        }
+    }
+    
+    /// `[first, second = 0, ...rest]`
+    fn array_pattern(&mut self) -> Result<Pattern> {
+        self.consume(&Token::LeftBracket, "Expected '['")?;
+        let mut elements = Vec::new();
+        let mut rest = None;
+        
+        self.skip_newlines();
+        while !self.check(&Token::RightBracket) {
+            if self.check(&Token::Ellipsis) {
+                self.advance();
+                rest = match self.advance() {
+                    Token::Identifier(name) => Some(name.clone()),
+                    _ => return Err(self.error_at_previous("Expected a name after '...' in array pattern")),
+                };
+                self.skip_newlines();
+                if !self.check(&Token::RightBracket) {
+                    return Err(self.error_at_current("Expected ']': '...rest' must come last in an array pattern"));
+                }
+                break;
+            }
+            
+            let pattern = self.pattern()?;
+            elements.push(self.pattern_element(pattern)?);
+            
+            self.skip_newlines();
+            if !self.check(&Token::Comma) {
+                break;
+            }
+            self.advance();
+            self.skip_newlines();
+        }
+        
+        self.consume(&Token::RightBracket, "Expected ']' after array pattern")?;
+        Ok(Pattern::Array { elements, rest })
+    }
+    
+    /// `{status, body: text, headers: {server} = {}}`
+    fn object_pattern(&mut self) -> Result<Pattern> {
+        self.consume(&Token::LeftBrace, "Expected '{'")?;
+        let mut properties = Vec::new();
+        
+        self.skip_newlines();
+        while !self.check(&Token::RightBrace) {
+            let key = match self.advance() {
+                Token::Identifier(key) => key.clone(),
+                _ => return Err(self.error_at_previous("Expected a key in object pattern")),
+            };
+            
+            // `key: pattern` binds the value to something other than its key
+            let pattern = if self.check(&Token::Colon) {
+                self.advance();
+                self.pattern()?
+            } else {
+                Pattern::Identifier(key.clone())
+            };
+            properties.push((key, self.pattern_element(pattern)?));
+            
+            self.skip_newlines();
+            if !self.check(&Token::Comma) {
+                break;
+            }
+            self.advance();
+            self.skip_newlines();
+        }
+        
+        self.consume(&Token::RightBrace, "Expected '}' after object pattern")?;
+        Ok(Pattern::Object { properties })
+    }
+    
+    /// A parsed pattern and its `= default`, if it has one
+    fn pattern_element(&mut self, pattern: Pattern) -> Result<PatternElement> {
+        let default_value = if self.check(&Token::Assign) {
+            self.advance();
+            Some(self.expression()?)
+        } else {
+            None
+        };
+        Ok(PatternElement { pattern, default_value })
+    }
+    
+    fn skip_newlines(&mut self) {
+        while self.check(&Token::Newline) {
+            self.advance();
+        }
+    }
+    
+    /// Parse a function or block-lambda body after `do`, through the closing `end`
+    fn function_body(&mut self, end_message: &str) -> Result<Vec<Statement>> {
         self.consume_newline()?;
         
+        // Loops outside the function body can't be broken out of from inside it,
+        // and an enclosing catch's error can't be rethrown from it either
+        let enclosing_loop_depth = std::mem::replace(&mut self.loop_depth, 0);
+        let enclosing_catch_depth = std::mem::replace(&mut self.catch_depth, 0);
         let mut body = Vec::new();
         while !self.check(&Token::End) && !self.is_at_end() {
             if self.check(&Token::Newline) {
                 self.advance();
                 continue;
             }
-            body.push(self.statement()?);
+            body.extend(self.recovering_statement());
         }
+        self.loop_depth = enclosing_loop_depth;
+        self.catch_depth = enclosing_catch_depth;
         
-        self.consume(&Token::End, "Expected 'end' to close function")?;
-        self.consume_newline_or_eof()?;
-        
-        Ok(Statement::FunctionDeclaration { name, parameters, body })
+        self.consume(&Token::End, end_message)?;
+        Ok(body)
     }
     
-    fn if_statement(&mut self) -> Result<Statement> {
+    fn if_statement(&mut self) -> Result<StatementKind> {
         self.if_statement_internal(true)
     }
     
-    fn if_statement_internal(&mut self, consume_end: bool) -> Result<Statement> {
+    fn if_statement_internal(&mut self, consume_end: bool) -> Result<StatementKind> {
         self.consume(&Token::If, "Expected 'if'")?;
         
         let condition = self.expression()?;
         
-        self.consume(&Token::Then, "Expected 'then' after if condition")?;
+        self.consume(&Token::Then, "Expected 'then' after if condition")
+            .map_err(|error| error.with_hint("an `if` condition is followed by `then`, as in `if x > 1 then`"))?;
         self.consume_newline()?;
         
         let mut then_branch = Vec::new();
@@ -172,7 +480,7 @@ impl Parser {
                 self.advance();
                 continue;
             }
-            then_branch.push(self.statement()?);
+            then_branch.extend(self.recovering_statement());
         }
         
         let else_branch = if self.check(&Token::Else) {
@@ -181,8 +489,9 @@ impl Parser {
             // Check for 'else if'
             if self.check(&Token::If) {
                 // Parse else-if as a nested if statement without consuming end
-                let else_if_stmt = self.if_statement_internal(false)?;
-                Some(vec![else_if_stmt])
+                let start = self.current_position();
+                let else_if = self.if_statement_internal(false)?;
+                Some(vec![Statement::new(else_if, self.span_from(start))])
             } else {
                 self.consume_newline()?;
                 let mut else_stmts = Vec::new();
@@ -191,7 +500,7 @@ impl Parser {
                         self.advance();
                         continue;
                     }
-                    else_stmts.push(self.statement()?);
+                    else_stmts.extend(self.recovering_statement());
                 }
                 Some(else_stmts)
             }
@@ -204,84 +513,153 @@ impl Parser {
             self.consume_newline_or_eof()?;
         }
         
-        Ok(Statement::If {
+        Ok(StatementKind::If {
             condition,
             then_branch,
             else_branch,
         })
     }
     
-    fn while_statement(&mut self) -> Result<Statement> {
+    fn while_statement(&mut self) -> Result<StatementKind> {
         self.consume(&Token::While, "Expected 'while'")?;
         
         let condition = self.expression()?;
         
-        self.consume(&Token::Do, "Expected 'do' after while condition")?;
+        self.consume(&Token::Do, "Expected 'do' after while condition")
+            .map_err(|error| error.with_hint("a `while` condition is followed by `do`, as in `while x > 1 do`"))?;
         self.consume_newline()?;
         
+        self.loop_depth += 1;
         let mut body = Vec::new();
         while !self.check(&Token::End) && !self.is_at_end() {
             if self.check(&Token::Newline) {
                 self.advance();
                 continue;
             }
-            body.push(self.statement()?);
+            body.extend(self.recovering_statement());
         }
+        self.loop_depth -= 1;
         
         self.consume(&Token::End, "Expected 'end' to close while loop")?;
         self.consume_newline_or_eof()?;
         
-        Ok(Statement::While { condition, body })
+        Ok(StatementKind::While { condition, body })
     }
     
-    fn for_statement(&mut self) -> Result<Statement> {
+    fn for_statement(&mut self) -> Result<StatementKind> {
         self.consume(&Token::For, "Expected 'for'")?;
         
         let variable = match self.advance() {
             Token::Identifier(name) => name.clone(),
             _ => {
-                let line = self.current_line();
-                return Err(FlowError::parser_error_at_line(line, "Expected variable name in for loop"));
+                return Err(self.error_at_previous("Expected variable name in for loop"));
+            }
+        };
+        
+        let value_variable = if self.check(&Token::Comma) {
+            self.advance();
+            match self.advance() {
+                Token::Identifier(name) => Some(name.clone()),
+                _ => {
+                    return Err(self.error_at_previous("Expected second variable name after ',' in for loop"));
+                }
             }
+        } else {
+            None
         };
         
-        self.consume(&Token::From, "Expected 'from' in for loop")?;
+        if value_variable.is_some() || self.check(&Token::In) {
+            self.consume(&Token::In, "Expected 'in' after for loop variables")?;
+            let iterable = self.expression()?;
+            
+            // `for i in 1 to 10` is the same range loop as `for i from 1 to 10`
+            if value_variable.is_none() && self.check(&Token::To) {
+                self.advance();
+                let end = self.expression()?;
+                let step = self.for_step()?;
+                self.consume(&Token::Do, "Expected 'do' after for range").map_err(Self::for_do_hint)?;
+                let body = self.for_body()?;
+                return Ok(StatementKind::For {
+                    variable,
+                    start: iterable,
+                    end,
+                    step,
+                    body,
+                });
+            }
+            
+            self.consume(&Token::Do, "Expected 'do' after for iterable").map_err(Self::for_do_hint)?;
+            let body = self.for_body()?;
+            return Ok(StatementKind::ForIn {
+                variable,
+                value_variable,
+                iterable,
+                body,
+            });
+        }
+        
+        self.consume(&Token::From, "Expected 'from' or 'in' in for loop")?;
         let start = self.expression()?;
         
         self.consume(&Token::To, "Expected 'to' in for loop")?;
         let end = self.expression()?;
+        let step = self.for_step()?;
+        
+        self.consume(&Token::Do, "Expected 'do' after for range").map_err(Self::for_do_hint)?;
+        let body = self.for_body()?;
         
-        self.consume(&Token::Do, "Expected 'do' after for range")?;
+        Ok(StatementKind::For {
+            variable,
+            start,
+            end,
+            step,
+            body,
+        })
+    }
+    
+    fn for_do_hint(error: FlowError) -> FlowError {
+        error.with_hint("a `for` loop header is followed by `do`, as in `for i in 1 to 10 do`")
+    }
+    
+    /// Parse the optional `by <step>` clause of a numeric for loop
+    fn for_step(&mut self) -> Result<Option<Expression>> {
+        if self.check(&Token::By) {
+            self.advance();
+            Ok(Some(self.expression()?))
+        } else {
+            Ok(None)
+        }
+    }
+    
+    /// Parse a for loop body after `do`, through the closing `end`
+    fn for_body(&mut self) -> Result<Vec<Statement>> {
         self.consume_newline()?;
         
+        self.loop_depth += 1;
         let mut body = Vec::new();
         while !self.check(&Token::End) && !self.is_at_end() {
             if self.check(&Token::Newline) {
                 self.advance();
                 continue;
             }
-            body.push(self.statement()?);
+            body.extend(self.recovering_statement());
         }
+        self.loop_depth -= 1;
         
         self.consume(&Token::End, "Expected 'end' to close for loop")?;
         self.consume_newline_or_eof()?;
         
-        Ok(Statement::For {
-            variable,
-            start,
-            end,
-            body,
-        })
+        Ok(body)
     }
     
-    fn show_statement(&mut self) -> Result<Statement> {
+    fn show_statement(&mut self) -> Result<StatementKind> {
         self.consume(&Token::Show, "Expected 'show'")?;
         let expr = self.expression()?;
         self.consume_newline_or_eof()?;
-        Ok(Statement::Show(expr))
+        Ok(StatementKind::Show(expr))
     }
     
-    fn return_statement(&mut self) -> Result<Statement> {
+    fn return_statement(&mut self) -> Result<StatementKind> {
         self.consume(&Token::Return, "Expected 'return'")?;
         
         let value = if self.check(&Token::Newline) || self.is_at_end() {
@@ -291,139 +669,274 @@ impl Parser {
         };
         
         self.consume_newline_or_eof()?;
-        Ok(Statement::Return(value))
+        Ok(StatementKind::Return(value))
+    }
+    
+    fn throw_statement(&mut self) -> Result<StatementKind> {
+        self.consume(&Token::Throw, "Expected 'throw'")?;
+        
+        let value = if self.check(&Token::Newline) || self.is_at_end() {
+            if self.catch_depth == 0 {
+                return Err(self.error_at_previous("'throw' without a value can only be used inside 'catch'"));
+            }
+            None
+        } else {
+            Some(self.expression()?)
+        };
+        
+        self.consume_newline_or_eof()?;
+        Ok(StatementKind::Throw(value))
+    }
+    
+    fn loop_control_statement(&mut self) -> Result<StatementKind> {
+        let statement = match self.advance() {
+            Token::Break => StatementKind::Break,
+            _ => StatementKind::Continue,
+        };
+        
+        if self.loop_depth == 0 {
+            let keyword = if matches!(statement, StatementKind::Break) { "break" } else { "continue" };
+            return Err(self.error_at_previous(format!("'{}' can only be used inside a loop", keyword)));
+        }
+        
+        self.consume_newline_or_eof()?;
+        Ok(statement)
     }
     
-    fn import_statement(&mut self) -> Result<Statement> {
+    fn import_statement(&mut self) -> Result<StatementKind> {
         self.consume(&Token::Import, "Expected 'import'")?;
         
+        // import { func1, func2 as f } from "./module.flow"
+        if self.check(&Token::LeftBrace) {
+            let imports = self.import_list()?;
+            self.consume(&Token::From, "Expected 'from' after import list")?;
+            let module_path = self.module_file_path()?;
+            self.consume_newline_or_eof()?;
+            return Ok(StatementKind::Import { module_path, imports });
+        }
+        
         let module_path = match self.advance() {
             Token::Identifier(name) => name.clone(),
+            Token::String(path) => path.clone(),
             _ => {
-                let line = self.current_line();
-                return Err(FlowError::parser_error_at_line(line, "Expected module name"));
+                return Err(self.error_at_previous("Expected module name"));
             }
         };
         
-        let imports = if self.check(&Token::LeftBrace) {
+        let (module_path, imports) = if self.check(&Token::From) {
+            // import name from "./module.flow"
+            self.advance(); // consume 'from'
+            let file_path = self.module_file_path()?;
+            (file_path.clone(), ImportType::Aliased(file_path, module_path))
+        } else if self.check(&Token::LeftBrace) {
             // import module { func1, func2 }
-            self.advance(); // consume '{'
-            let mut specific_imports = Vec::new();
-            
-            loop {
-                let func_name = match self.advance() {
-                    Token::Identifier(name) => name.clone(),
-                    _ => {
-                        let line = self.current_line();
-                        return Err(FlowError::parser_error_at_line(line, "Expected function name"));
-                    }
-                };
-                
-                if self.check(&Token::As) {
-                    self.advance(); // consume 'as'
-                    let alias = match self.advance() {
-                        Token::Identifier(name) => name.clone(),
-                        _ => {
-                            let line = self.current_line();
-                            return Err(FlowError::parser_error_at_line(line, "Expected alias name"));
-                        }
-                    };
-                    specific_imports.push((func_name, alias));
-                } else {
-                    specific_imports.push((func_name.clone(), func_name));
-                }
-                
-                if self.check(&Token::Comma) {
-                    self.advance();
-                } else {
-                    break;
-                }
-            }
-            
-            self.consume(&Token::RightBrace, "Expected '}'")?;
-            
-            // Determine if we have any aliases
-            if specific_imports.iter().any(|(name, alias)| name != alias) {
-                ImportType::SpecificAliased(specific_imports)
-            } else {
-                ImportType::Specific(specific_imports
-                    .into_iter()
-                    .map(|(name, _)| name)
-                    .collect()
-                )
-            }
+            (module_path, self.import_list()?)
         } else if self.check(&Token::As) {
             // import module as alias
             self.advance(); // consume 'as'
             let alias = match self.advance() {
                 Token::Identifier(name) => name.clone(),
                 _ => {
-                    let line = self.current_line();
-                    return Err(FlowError::parser_error_at_line(line, "Expected alias name"));
+                    return Err(self.error_at_previous("Expected alias name"));
                 }
             };
-            ImportType::Aliased(module_path.clone(), alias)
+            (module_path.clone(), ImportType::Aliased(module_path, alias))
         } else {
             // import module
-            ImportType::All
+            (module_path, ImportType::All)
         };
         
         self.consume_newline_or_eof()?;
-        Ok(Statement::Import { module_path, imports })
+        Ok(StatementKind::Import { module_path, imports })
+    }
+    
+    /// The quoted path after `from` in a file import
+    fn module_file_path(&mut self) -> Result<String> {
+        match self.advance() {
+            Token::String(path) => Ok(path.clone()),
+            _ => {
+                Err(self.error_at_previous("Expected a module path string after 'from'"))
+            }
+        }
     }
     
-    fn export_statement(&mut self) -> Result<Statement> {
+    /// A braced import list: `{ func1, func2 as alias }`
+    fn import_list(&mut self) -> Result<ImportType> {
+        self.consume(&Token::LeftBrace, "Expected '{'")?;
+        let mut specific_imports = Vec::new();
+        
+        loop {
+            let func_name = match self.advance() {
+                Token::Identifier(name) => name.clone(),
+                _ => {
+                    return Err(self.error_at_previous("Expected function name"));
+                }
+            };
+            
+            if self.check(&Token::As) {
+                self.advance(); // consume 'as'
+                let alias = match self.advance() {
+                    Token::Identifier(name) => name.clone(),
+                    _ => {
+                        return Err(self.error_at_previous("Expected alias name"));
+                    }
+                };
+                specific_imports.push((func_name, alias));
+            } else {
+                specific_imports.push((func_name.clone(), func_name));
+            }
+            
+            if self.check(&Token::Comma) {
+                self.advance();
+            } else {
+                break;
+            }
+        }
+        
+        self.consume(&Token::RightBrace, "Expected '}'")?;
+        
+        // Determine if we have any aliases
+        if specific_imports.iter().any(|(name, alias)| name != alias) {
+            Ok(ImportType::SpecificAliased(specific_imports))
+        } else {
+            Ok(ImportType::Specific(specific_imports
+                .into_iter()
+                .map(|(name, _)| name)
+                .collect()
+            ))
+        }
+    }
+    
+    fn export_statement(&mut self) -> Result<StatementKind> {
         self.consume(&Token::Export, "Expected 'export'")?;
         let statement = self.statement()?;
-        Ok(Statement::Export(Box::new(statement)))
+        
+        match statement.kind {
+            StatementKind::VariableDeclaration { .. }
+            | StatementKind::Destructuring { .. }
+            | StatementKind::FunctionDeclaration { .. } => {
+                Ok(StatementKind::Export(Box::new(statement)))
+            }
+            _ => {
+                let start = statement.span.start;
+                Err(FlowError::parser_error_at(start.line, start.column, "Only 'let' and 'def' declarations can be exported"))
+            }
+        }
     }
     
-    fn try_catch_statement(&mut self) -> Result<Statement> {
+    fn try_catch_statement(&mut self) -> Result<StatementKind> {
         self.consume(&Token::Try, "Expected 'try'")?;
         self.consume_newline()?;
         
         let mut try_block = Vec::new();
-        while !self.check(&Token::Catch) && !self.is_at_end() {
+        while !self.check(&Token::Catch) && !self.check(&Token::Finally) && !self.check(&Token::End) && !self.is_at_end() {
             if self.check(&Token::Newline) {
                 self.advance();
                 continue;
             }
-            try_block.push(self.statement()?);
+            try_block.extend(self.recovering_statement());
         }
         
-        self.consume(&Token::Catch, "Expected 'catch'")?;
-        
-        let catch_variable = match self.advance() {
-            Token::Identifier(name) => name.clone(),
-            _ => {
-                let line = self.current_line();
-                return Err(FlowError::parser_error_at_line(line, "Expected error variable name"));
+        // `catch` may only be left out when there is a `finally` block
+        let catch_clause = if self.check(&Token::Finally) {
+            None
+        } else {
+            self.consume(&Token::Catch, "Expected 'catch' or 'finally'")?;
+            
+            let variable = match self.advance() {
+                Token::Identifier(name) => name.clone(),
+                _ => {
+                    return Err(self.error_at_previous("Expected error variable name"));
+                }
+            };
+            
+            self.consume_newline()?;
+            
+            self.catch_depth += 1;
+            let mut block = Vec::new();
+            while !self.check(&Token::End) && !self.check(&Token::Finally) && !self.is_at_end() {
+                if self.check(&Token::Newline) {
+                    self.advance();
+                    continue;
+                }
+                block.extend(self.recovering_statement());
             }
+            self.catch_depth -= 1;
+            Some(CatchClause { variable, block })
         };
         
-        self.consume_newline()?;
-        
-        let mut catch_block = Vec::new();
-        while !self.check(&Token::End) && !self.is_at_end() {
-            if self.check(&Token::Newline) {
-                self.advance();
-                continue;
+        let finally_block = if self.check(&Token::Finally) {
+            self.advance(); // consume 'finally'
+            self.consume_newline()?;
+            
+            let mut finally_block = Vec::new();
+            while !self.check(&Token::End) && !self.is_at_end() {
+                if self.check(&Token::Newline) {
+                    self.advance();
+                    continue;
+                }
+                finally_block.extend(self.recovering_statement());
             }
-            catch_block.push(self.statement()?);
-        }
+            Some(finally_block)
+        } else {
+            None
+        };
         
         self.consume(&Token::End, "Expected 'end'")?;
         self.consume_newline_or_eof()?;
         
-        Ok(Statement::TryCatch {
+        Ok(StatementKind::TryCatch {
             try_block,
-            catch_variable,
-            catch_block,
+            catch_clause,
+            finally_block,
         })
     }
     
     fn expression(&mut self) -> Result<Expression> {
-        self.logical_or()
+        if self.check(&Token::If) {
+            return self.conditional_expression();
+        }
+        
+        let expr = self.logical_or()?;
+        
+        if self.check(&Token::If) {
+            // `a if condition else b`
+            self.advance();
+            let condition = self.logical_or()?;
+            self.consume(&Token::Else, "Expected 'else' after condition in conditional expression")?;
+            let else_branch = self.expression()?;
+            return Ok(Self::conditional(condition, expr, else_branch));
+        }
+        
+        Ok(expr)
+    }
+    
+    /// `if condition then a else b`. The `else` branch is an expression in
+    /// turn, so `else if` chains without an `end`.
+    fn conditional_expression(&mut self) -> Result<Expression> {
+        let start = self.current_position();
+        self.consume(&Token::If, "Expected 'if'")?;
+        let condition = self.expression()?;
+        self.consume(&Token::Then, "Expected 'then' after condition in conditional expression")?;
+        let then_branch = self.expression()?;
+        self.consume(&Token::Else, "Expected 'else' in conditional expression")?;
+        let else_branch = self.expression()?;
+        let kind = ExpressionKind::Conditional {
+            condition: Box::new(condition),
+            then_branch: Box::new(then_branch),
+            else_branch: Box::new(else_branch),
+        };
+        Ok(Expression::new(kind, self.span_from(start)))
+    }
+    
+    fn conditional(condition: Expression, then_branch: Expression, else_branch: Expression) -> Expression {
+        let span = Span { start: then_branch.span.start, end: else_branch.span.end };
+        let kind = ExpressionKind::Conditional {
+            condition: Box::new(condition),
+            then_branch: Box::new(then_branch),
+            else_branch: Box::new(else_branch),
+        };
+        Expression::new(kind, span)
     }
     
     fn logical_or(&mut self) -> Result<Expression> {
@@ -432,11 +945,7 @@ impl Parser {
         while self.check(&Token::Or) {
             self.advance();
             let right = self.logical_and()?;
-            expr = Expression::Binary {
-                left: Box::new(expr),
-                operator: BinaryOperator::Or,
-                right: Box::new(right),
-            };
+            expr = Self::binary(expr, BinaryOperator::Or, right);
         }
         
         Ok(expr)
@@ -448,11 +957,7 @@ impl Parser {
         while self.check(&Token::And) {
             self.advance();
             let right = self.equality()?;
-            expr = Expression::Binary {
-                left: Box::new(expr),
-                operator: BinaryOperator::And,
-                right: Box::new(right),
-            };
+            expr = Self::binary(expr, BinaryOperator::And, right);
         }
         
         Ok(expr)
@@ -468,33 +973,154 @@ impl Parser {
                 _ => unreachable!(),
             };
             let right = self.comparison()?;
-            expr = Expression::Binary {
-                left: Box::new(expr),
-                operator,
-                right: Box::new(right),
-            };
+            expr = Self::binary(expr, operator, right);
         }
         
         Ok(expr)
     }
     
     fn comparison(&mut self) -> Result<Expression> {
-        let mut expr = self.term()?;
+        let mut expr = self.pipe()?;
         
-        while matches!(self.peek().token, Token::Greater | Token::GreaterEqual | Token::Less | Token::LessEqual) {
+        while matches!(
+            self.peek().token,
+            Token::Greater | Token::GreaterEqual | Token::Less | Token::LessEqual | Token::In
+        ) {
             let operator = match self.advance() {
                 Token::Greater => BinaryOperator::Greater,
                 Token::GreaterEqual => BinaryOperator::GreaterEqual,
                 Token::Less => BinaryOperator::Less,
                 Token::LessEqual => BinaryOperator::LessEqual,
+                Token::In => BinaryOperator::In,
                 _ => unreachable!(),
             };
-            let right = self.term()?;
-            expr = Expression::Binary {
-                left: Box::new(expr),
-                operator,
-                right: Box::new(right),
+            let right = self.pipe()?;
+            expr = Self::binary(expr, operator, right);
+        }
+        
+        Ok(expr)
+    }
+    
+    /// `value |> f(args)` is the call `f(value, args)`, so pipes are rewritten
+    /// into ordinary calls here rather than reaching either engine
+    fn pipe(&mut self) -> Result<Expression> {
+        let mut expr = self.null_coalesce()?;
+        
+        while self.check(&Token::Pipe) {
+            self.advance();
+            expr = self.pipe_target(expr)?;
+        }
+        
+        Ok(expr)
+    }
+    
+    /// The call `value` is piped into: `f(args)`, a bare `f`, `module.f(args)`,
+    /// or `.method(args)` to call a method of `value` itself
+    fn pipe_target(&mut self, value: Expression) -> Result<Expression> {
+        let start = value.span.start;
+        
+        if self.check(&Token::Dot) {
+            self.advance();
+            let position = self.current_position();
+            let method = match self.advance() {
+                Token::Identifier(name) => name.clone(),
+                _ => return Err(self.error_at_previous("Expected method name after '|> .'")),
             };
+            self.consume(&Token::LeftParen, "Expected '(' after method name")?;
+            let arguments = self.arguments()?;
+            let kind = ExpressionKind::MethodCall {
+                object: Box::new(value),
+                method,
+                arguments,
+                position,
+                optional: false,
+            };
+            return Ok(Expression::new(kind, self.span_from(start)));
+        }
+        
+        let target = self.call()?;
+        let kind = match target.kind {
+            ExpressionKind::Identifier(name) => ExpressionKind::FunctionCall {
+                name,
+                arguments: vec![value],
+                position: target.span.start,
+            },
+            ExpressionKind::FunctionCall { name, mut arguments, position } => {
+                arguments.insert(0, value);
+                ExpressionKind::FunctionCall { name, arguments, position }
+            }
+            ExpressionKind::MethodCall { object, method, mut arguments, position, optional } => {
+                arguments.insert(0, value);
+                ExpressionKind::MethodCall { object, method, arguments, position, optional }
+            }
+            _ => {
+                let start = target.span.start;
+                return Err(FlowError::parser_error_at(start.line, start.column, "Expected a function call after '|>'"));
+            }
+        };
+        Ok(Expression::new(kind, self.span_from(start)))
+    }
+    
+    /// `??` groups to the right, so `a ?? b ?? c` takes the first that isn't null
+    fn null_coalesce(&mut self) -> Result<Expression> {
+        let expr = self.bit_or()?;
+        
+        if self.check(&Token::NullCoalesce) {
+            self.advance();
+            let right = self.null_coalesce()?;
+            return Ok(Self::binary(expr, BinaryOperator::NullCoalesce, right));
+        }
+        
+        Ok(expr)
+    }
+    
+    fn bit_or(&mut self) -> Result<Expression> {
+        let mut expr = self.bit_xor()?;
+        
+        while self.check(&Token::BitOr) {
+            self.advance();
+            let right = self.bit_xor()?;
+            expr = Self::binary(expr, BinaryOperator::BitOr, right);
+        }
+        
+        Ok(expr)
+    }
+    
+    fn bit_xor(&mut self) -> Result<Expression> {
+        let mut expr = self.bit_and()?;
+        
+        while self.check(&Token::BitXor) {
+            self.advance();
+            let right = self.bit_and()?;
+            expr = Self::binary(expr, BinaryOperator::BitXor, right);
+        }
+        
+        Ok(expr)
+    }
+    
+    fn bit_and(&mut self) -> Result<Expression> {
+        let mut expr = self.shift()?;
+        
+        while self.check(&Token::BitAnd) {
+            self.advance();
+            let right = self.shift()?;
+            expr = Self::binary(expr, BinaryOperator::BitAnd, right);
+        }
+        
+        Ok(expr)
+    }
+    
+    fn shift(&mut self) -> Result<Expression> {
+        let mut expr = self.term()?;
+        
+        while matches!(self.peek().token, Token::ShiftLeft | Token::ShiftRight) {
+            let operator = match self.advance() {
+                Token::ShiftLeft => BinaryOperator::ShiftLeft,
+                Token::ShiftRight => BinaryOperator::ShiftRight,
+                _ => unreachable!(),
+            };
+            let right = self.term()?;
+            expr = Self::binary(expr, operator, right);
         }
         
         Ok(expr)
@@ -510,11 +1136,7 @@ impl Parser {
                 _ => unreachable!(),
             };
             let right = self.factor()?;
-            expr = Expression::Binary {
-                left: Box::new(expr),
-                operator,
-                right: Box::new(right),
-            };
+            expr = Self::binary(expr, operator, right);
         }
         
         Ok(expr)
@@ -523,121 +1145,128 @@ impl Parser {
     fn factor(&mut self) -> Result<Expression> {
         let mut expr = self.unary()?;
         
-        while matches!(self.peek().token, Token::Multiply | Token::Divide | Token::Modulo) {
+        while matches!(self.peek().token, Token::Multiply | Token::Divide | Token::IntegerDivide | Token::Modulo) {
             let operator = match self.advance() {
                 Token::Multiply => BinaryOperator::Multiply,
                 Token::Divide => BinaryOperator::Divide,
+                Token::IntegerDivide => BinaryOperator::IntegerDivide,
                 Token::Modulo => BinaryOperator::Modulo,
                 _ => unreachable!(),
             };
             let right = self.unary()?;
-            expr = Expression::Binary {
-                left: Box::new(expr),
-                operator,
-                right: Box::new(right),
-            };
+            expr = Self::binary(expr, operator, right);
         }
         
         Ok(expr)
     }
     
+    /// `left operator right`, spanning both operands
+    fn binary(left: Expression, operator: BinaryOperator, right: Expression) -> Expression {
+        let span = Span { start: left.span.start, end: right.span.end };
+        Expression::new(ExpressionKind::Binary {
+            left: Box::new(left),
+            operator,
+            right: Box::new(right),
+        }, span)
+    }
+    
     fn unary(&mut self) -> Result<Expression> {
-        if matches!(self.peek().token, Token::Not | Token::Minus) {
+        if matches!(self.peek().token, Token::Not | Token::Minus | Token::BitNot) {
+            let start = self.current_position();
             let operator = match self.advance() {
                 Token::Not => UnaryOperator::Not,
                 Token::Minus => UnaryOperator::Minus,
+                Token::BitNot => UnaryOperator::BitNot,
                 _ => unreachable!(),
             };
             let operand = self.unary()?;
-            Ok(Expression::Unary {
+            Ok(Expression::new(ExpressionKind::Unary {
                 operator,
                 operand: Box::new(operand),
-            })
+            }, self.span_from(start)))
         } else {
-            self.call()
+            self.power()
         }
     }
     
+    /// `**` binds tighter than a unary operator on its left and groups to the
+    /// right, so `-2 ** 2` is `-(2 ** 2)` and `2 ** 3 ** 2` is `2 ** 9`
+    fn power(&mut self) -> Result<Expression> {
+        let expr = self.call()?;
+        
+        if self.check(&Token::Power) {
+            self.advance();
+            let right = self.unary()?;
+            return Ok(Self::binary(expr, BinaryOperator::Power, right));
+        }
+        
+        Ok(expr)
+    }
+    
     fn call(&mut self) -> Result<Expression> {
+        let position = self.current_position();
         let mut expr = self.primary()?;
         
         loop {
             if self.check(&Token::LeftParen) {
                 self.advance();
-                let mut arguments = Vec::new();
+                let arguments = self.arguments()?;
                 
-                if !self.check(&Token::RightParen) {
-                    loop {
-                        arguments.push(self.expression()?);
-                        if self.check(&Token::Comma) {
-                            self.advance();
-                        } else {
-                            break;
-                        }
-                    }
-                }
-                
-                self.consume(&Token::RightParen, "Expected ')' after arguments")?;
-                
-                match expr {
-                    Expression::Identifier(name) => {
-                        expr = Expression::FunctionCall { name, arguments };
+                match expr.kind {
+                    ExpressionKind::Identifier(name) => {
+                        let kind = ExpressionKind::FunctionCall { name, arguments, position };
+                        expr = Expression::new(kind, self.span_from(position));
                     }
                     _ => {
-                        let line = self.current_line();
-                        return Err(FlowError::parser_error_at_line(line, "Invalid function call"));
+                        let start = expr.span.start;
+                        return Err(FlowError::parser_error_at(start.line, start.column, "Invalid function call"));
                     }
                 }
-            } else if self.check(&Token::Dot) {
-                self.advance();
+            } else if matches!(self.peek().token, Token::Dot | Token::OptionalDot) {
+                let optional = *self.advance() == Token::OptionalDot;
+                let method_position = self.current_position();
                 let property = match self.advance() {
                     Token::Identifier(name) => name.clone(),
                     _ => {
-                        let line = self.current_line();
-                        return Err(FlowError::parser_error_at_line(line, "Expected property name after '.'"));
+                        let dot = if optional { "?." } else { "." };
+                        return Err(self.error_at_previous(format!("Expected property name after '{}'", dot)));
                     },
                 };
                 
                 if self.check(&Token::LeftParen) {
                     // Method call
                     self.advance();
-                    let mut arguments = Vec::new();
-                    
-                    if !self.check(&Token::RightParen) {
-                        loop {
-                            arguments.push(self.expression()?);
-                            if self.check(&Token::Comma) {
-                                self.advance();
-                            } else {
-                                break;
-                            }
-                        }
-                    }
+                    let arguments = self.arguments()?;
                     
-                    self.consume(&Token::RightParen, "Expected ')' after arguments")?;
-                    
-                    expr = Expression::MethodCall {
+                    let kind = ExpressionKind::MethodCall {
                         object: Box::new(expr),
                         method: property,
                         arguments,
+                        position: method_position,
+                        optional,
                     };
+                    expr = Expression::new(kind, self.span_from(position));
                 } else {
                     // Property access
-                    expr = Expression::PropertyAccess {
+                    let kind = ExpressionKind::PropertyAccess {
                         object: Box::new(expr),
                         property,
+                        optional,
                     };
+                    expr = Expression::new(kind, self.span_from(position));
                 }
-            } else if self.check(&Token::LeftBracket) {
+            } else if matches!(self.peek().token, Token::LeftBracket | Token::OptionalBracket) {
                 // Index access
-                self.advance();
+                let optional = *self.advance() == Token::OptionalBracket;
                 let index = self.expression()?;
                 self.consume(&Token::RightBracket, "Expected ']' after index")?;
                 
-                expr = Expression::Index {
+                let kind = ExpressionKind::Index {
                     object: Box::new(expr),
                     index: Box::new(index),
+                    optional,
                 };
+                expr = Expression::new(kind, self.span_from(position));
             } else {
                 break;
             }
@@ -646,15 +1275,55 @@ impl Parser {
         Ok(expr)
     }
     
+    /// Call arguments, after the opening '(' through the closing ')'
+    fn arguments(&mut self) -> Result<Vec<Expression>> {
+        let mut arguments = Vec::new();
+        
+        if !self.check(&Token::RightParen) {
+            loop {
+                arguments.push(self.spreadable()?);
+                if self.check(&Token::Comma) {
+                    self.advance();
+                } else {
+                    break;
+                }
+            }
+        }
+        
+        self.consume(&Token::RightParen, "Expected ')' after arguments")?;
+        Ok(arguments)
+    }
+    
+    /// An array element or call argument, which may be `...items`
+    fn spreadable(&mut self) -> Result<Expression> {
+        if !self.check(&Token::Ellipsis) {
+            return self.expression();
+        }
+        let start = self.current_position();
+        self.advance();
+        let items = self.expression()?;
+        Ok(Expression::new(ExpressionKind::Spread(Box::new(items)), self.span_from(start)))
+    }
+    
     fn primary(&mut self) -> Result<Expression> {
+        let start = self.current_position();
+        let kind = self.primary_kind()?;
+        Ok(Expression::new(kind, self.span_from(start)))
+    }
+    
+    fn primary_kind(&mut self) -> Result<ExpressionKind> {
         match self.advance() {
-            Token::String(s) => Ok(Expression::Literal(Literal::String(s.clone()))),
-            Token::Integer(i) => Ok(Expression::Literal(Literal::Integer(*i))),
-            Token::BigInteger(bi) => Ok(Expression::Literal(Literal::BigInteger(bi.clone()))),
-            Token::Float(f) => Ok(Expression::Literal(Literal::Float(*f))),
-            Token::Boolean(b) => Ok(Expression::Literal(Literal::Boolean(*b))),
-            Token::Null => Ok(Expression::Literal(Literal::Null)),
-            Token::Identifier(name) => Ok(Expression::Identifier(name.clone())),
+            Token::String(s) => Ok(ExpressionKind::Literal(Literal::String(s.clone()))),
+            Token::InterpolatedString(parts) => {
+                let parts = parts.clone();
+                self.parse_interpolated_string(parts)
+            }
+            Token::Integer(i) => Ok(ExpressionKind::Literal(Literal::Integer(*i))),
+            Token::BigInteger(bi) => Ok(ExpressionKind::Literal(Literal::BigInteger(bi.clone()))),
+            Token::Float(f) => Ok(ExpressionKind::Literal(Literal::Float(*f))),
+            Token::Boolean(b) => Ok(ExpressionKind::Literal(Literal::Boolean(*b))),
+            Token::Null => Ok(ExpressionKind::Literal(Literal::Null)),
+            Token::Identifier(name) => Ok(ExpressionKind::Identifier(name.clone())),
             Token::LeftParen => {
                 // We already advanced past the LeftParen, so we need to step back
                 self.current -= 1;
@@ -665,19 +1334,37 @@ impl Parser {
                     self.advance(); // consume the LeftParen again
                     let expr = self.expression()?;
                     self.consume(&Token::RightParen, "Expected ')' after expression")?;
-                    Ok(expr)
+                    Ok(expr.kind)
                 }
             }
             Token::LeftBracket => self.parse_array(),
             Token::LeftBrace => self.parse_object(),
             _ => {
-                let line = self.current_line();
-                Err(FlowError::parser_error_at_line(line, "Expected expression"))
+                Err(self.error_at_previous("Expected expression"))
             }
         }
     }
 
-    fn parse_array(&mut self) -> Result<Expression> {
+    /// Parse the embedded expressions of an interpolated string token
+    fn parse_interpolated_string(&mut self, parts: Vec<StringPart>) -> Result<ExpressionKind> {
+        let mut parsed = Vec::new();
+        for part in parts {
+            match part {
+                StringPart::Literal(text) => parsed.push(InterpolationPart::Literal(text)),
+                StringPart::Expression(tokens) => {
+                    let mut parser = Parser::new(tokens);
+                    let expression = parser.expression()?;
+                    if !parser.is_at_end() {
+                        return Err(parser.error_at_current("Unexpected token in string interpolation"));
+                    }
+                    parsed.push(InterpolationPart::Expression(expression));
+                }
+            }
+        }
+        Ok(ExpressionKind::InterpolatedString { parts: parsed })
+    }
+    
+    fn parse_array(&mut self) -> Result<ExpressionKind> {
         let mut elements = Vec::new();
         
         if !self.check(&Token::RightBracket) {
@@ -692,7 +1379,7 @@ impl Parser {
                     break;
                 }
                 
-                elements.push(self.expression()?);
+                elements.push(self.spreadable()?);
                 if self.check(&Token::Comma) {
                     self.advance();
                     // Skip any newlines after the comma
@@ -711,10 +1398,10 @@ impl Parser {
         }
         
         self.consume(&Token::RightBracket, "Expected ']' after array elements")?;
-        Ok(Expression::Array { elements })
+        Ok(ExpressionKind::Array { elements })
     }
 
-    fn parse_object(&mut self) -> Result<Expression> {
+    fn parse_object(&mut self) -> Result<ExpressionKind> {
         let mut properties = Vec::new();
         
         if !self.check(&Token::RightBrace) {
@@ -729,6 +1416,16 @@ impl Parser {
                     break;
                 }
                 
+                if self.check(&Token::Ellipsis) {
+                    self.advance();
+                    properties.push(ObjectProperty::Spread(self.expression()?));
+                    if self.check(&Token::Comma) {
+                        self.advance();
+                        continue;
+                    }
+                    break;
+                }
+                
                 let key = match &self.peek().token {
                     Token::String(s) => {
                         let key = s.clone();
@@ -741,14 +1438,13 @@ impl Parser {
                         key
                     }
                     _ => {
-                        let line = self.current_line();
-                        return Err(FlowError::parser_error_at_line(line, "Expected string or identifier as object key"));
+                        return Err(self.error_at_current("Expected string or identifier as object key"));
                     }
                 };
                 
                 self.consume(&Token::Colon, "Expected ':' after object key")?;
                 let value = self.expression()?;
-                properties.push((key, value));
+                properties.push(ObjectProperty::KeyValue(key, value));
                 
                 if self.check(&Token::Comma) {
                     self.advance();
@@ -768,7 +1464,7 @@ impl Parser {
         }
         
         self.consume(&Token::RightBrace, "Expected '}' after object properties")?;
-        Ok(Expression::Object { properties })
+        Ok(ExpressionKind::Object { properties })
     }
     
     fn is_lambda_expression(&self) -> bool {
@@ -790,42 +1486,15 @@ impl Parser {
         pos < self.tokens.len() && matches!(self.tokens[pos].token, Token::Arrow)
     }
     
-    fn parse_lambda(&mut self) -> Result<Expression> {
+    fn parse_lambda(&mut self) -> Result<ExpressionKind> {
         self.consume(&Token::LeftParen, "Expected '(' for lambda parameters")?;
         
         let mut parameters = Vec::new();
         
         if !self.check(&Token::RightParen) {
             loop {
-                // Check for variadic parameter
-                let is_variadic = if self.check(&Token::Ellipsis) {
-                    self.advance();
-                    true
-                } else {
-                    false
-                };
-                
-                let param_name = match self.advance() {
-                    Token::Identifier(name) => name.clone(),
-                    _ => {
-                        let line = self.current_line();
-                        return Err(FlowError::parser_error_at_line(line, "Expected parameter name"));
-                    }
-                };
-                
-                // Check for default value
-                let default_value = if self.check(&Token::Assign) {
-                    self.advance(); // consume '='
-                    Some(self.expression()?)
-                } else {
-                    None
-                };
-                
-                parameters.push(Parameter {
-                    name: param_name,
-                    default_value,
-                    is_variadic,
-                });
+                self.parameter_after(&parameters)?;
+                parameters.push(self.parameter()?);
                 
                 if self.check(&Token::Comma) {
                     self.advance();
@@ -838,17 +1507,29 @@ impl Parser {
         self.consume(&Token::RightParen, "Expected ')' after lambda parameters")?;
         self.consume(&Token::Arrow, "Expected '=>' after lambda parameters")?;
         
-        let body = Box::new(self.expression()?);
+        let body = if self.check(&Token::Do) {
+            self.advance();
+            LambdaBody::Block(self.function_body("Expected 'end' to close lambda body")?)
+        } else {
+            LambdaBody::Expression(Box::new(self.expression()?))
+        };
         
-        Ok(Expression::Lambda { parameters, body })
+        Ok(ExpressionKind::Lambda { parameters, body })
     }
     
     fn consume(&mut self, expected: &Token, message: &str) -> Result<&Token> {
         if self.check(expected) {
-            Ok(self.advance())
-        } else {
-            let line = self.current_line();
-            Err(FlowError::parser_error_at_line(line, message))
+            return Ok(self.advance());
+        }
+        
+        let error = self.error_at_current(message);
+        // A misspelling of the expected keyword, such as `thn` for `then`
+        let keyword = KEYWORDS.iter().find(|word| keyword_token(word).as_ref() == Some(expected));
+        match (&self.peek().token, keyword) {
+            (Token::Identifier(word), Some(keyword)) if closest_match(word, [*keyword]).is_some() => {
+                Err(error.with_hint(format!("did you mean `{}`?", keyword)))
+            }
+            _ => Err(error),
         }
     }
     
@@ -857,8 +1538,7 @@ impl Parser {
             self.advance();
             Ok(())
         } else {
-            let line = self.current_line();
-            Err(FlowError::parser_error_at_line(line, "Expected newline"))
+            Err(self.error_at_current("Expected newline"))
         }
     }
     
@@ -869,8 +1549,7 @@ impl Parser {
             }
             Ok(())
         } else {
-            let line = self.current_line();
-            Err(FlowError::parser_error_at_line(line, "Expected newline or end of file"))
+            Err(self.error_at_current("Expected newline or end of file"))
         }
     }
     
@@ -206,7 +206,7 @@ fn stdlib_input(args: Vec<Value>) -> Result<Value> {
 
 fn stdlib_assert(args: Vec<Value>) -> Result<Value> {
     if args.is_empty() {
-        return Err(FlowError::runtime_error("assert requires at least one argument"));
+        return Err(FlowError::argument_count_error("assert requires at least one argument"));
     }
     
     let condition = &args[0];
@@ -234,7 +234,7 @@ fn stdlib_panic(args: Vec<Value>) -> Result<Value> {
 // String functions
 fn stdlib_str_len(args: Vec<Value>) -> Result<Value> {
     if args.len() != 1 {
-        return Err(FlowError::runtime_error("str_len requires exactly one argument"));
+        return Err(FlowError::argument_count_error("str_len requires exactly one argument"));
     }
     
     match &args[0] {
@@ -245,7 +245,7 @@ fn stdlib_str_len(args: Vec<Value>) -> Result<Value> {
 
 fn stdlib_str_upper(args: Vec<Value>) -> Result<Value> {
     if args.len() != 1 {
-        return Err(FlowError::runtime_error("str_upper requires exactly one argument"));
+        return Err(FlowError::argument_count_error("str_upper requires exactly one argument"));
     }
     
     match &args[0] {
@@ -256,7 +256,7 @@ fn stdlib_str_upper(args: Vec<Value>) -> Result<Value> {
 
 fn stdlib_str_lower(args: Vec<Value>) -> Result<Value> {
     if args.len() != 1 {
-        return Err(FlowError::runtime_error("str_lower requires exactly one argument"));
+        return Err(FlowError::argument_count_error("str_lower requires exactly one argument"));
     }
     
     match &args[0] {
@@ -267,7 +267,7 @@ fn stdlib_str_lower(args: Vec<Value>) -> Result<Value> {
 
 fn stdlib_str_trim(args: Vec<Value>) -> Result<Value> {
     if args.len() != 1 {
-        return Err(FlowError::runtime_error("str_trim requires exactly one argument"));
+        return Err(FlowError::argument_count_error("str_trim requires exactly one argument"));
     }
     
     match &args[0] {
@@ -278,7 +278,7 @@ fn stdlib_str_trim(args: Vec<Value>) -> Result<Value> {
 
 fn stdlib_str_split(args: Vec<Value>) -> Result<Value> {
     if args.len() != 2 {
-        return Err(FlowError::runtime_error("str_split requires exactly two arguments"));
+        return Err(FlowError::argument_count_error("str_split requires exactly two arguments"));
     }
     
     match (&args[0], &args[1]) {
@@ -287,7 +287,7 @@ fn stdlib_str_split(args: Vec<Value>) -> Result<Value> {
                 .map(|part| Value::String(part.to_string()))
                 .collect();
             
-            let mut array = FlowArray::new();
+            let array = FlowArray::new();
             for part in parts {
                 array.push(part);
             }
@@ -300,12 +300,12 @@ fn stdlib_str_split(args: Vec<Value>) -> Result<Value> {
 
 fn stdlib_str_join(args: Vec<Value>) -> Result<Value> {
     if args.len() != 2 {
-        return Err(FlowError::runtime_error("str_join requires exactly two arguments"));
+        return Err(FlowError::argument_count_error("str_join requires exactly two arguments"));
     }
     
     match (&args[0], &args[1]) {
         (Value::Array(arr), Value::String(separator)) => {
-            let strings: Result<Vec<String>> = arr.elements.iter()
+            let strings: Result<Vec<String>> = arr.elements().iter()
                 .map(|elem| match elem {
                     Value::String(s) => Ok(s.clone()),
                     other => Ok(format!("{}", other)),
@@ -323,7 +323,7 @@ fn stdlib_str_join(args: Vec<Value>) -> Result<Value> {
 
 fn stdlib_str_contains(args: Vec<Value>) -> Result<Value> {
     if args.len() != 2 {
-        return Err(FlowError::runtime_error("str_contains requires exactly two arguments"));
+        return Err(FlowError::argument_count_error("str_contains requires exactly two arguments"));
     }
     
     match (&args[0], &args[1]) {
@@ -336,7 +336,7 @@ fn stdlib_str_contains(args: Vec<Value>) -> Result<Value> {
 
 fn stdlib_str_starts_with(args: Vec<Value>) -> Result<Value> {
     if args.len() != 2 {
-        return Err(FlowError::runtime_error("str_starts_with requires exactly two arguments"));
+        return Err(FlowError::argument_count_error("str_starts_with requires exactly two arguments"));
     }
     
     match (&args[0], &args[1]) {
@@ -349,7 +349,7 @@ fn stdlib_str_starts_with(args: Vec<Value>) -> Result<Value> {
 
 fn stdlib_str_ends_with(args: Vec<Value>) -> Result<Value> {
     if args.len() != 2 {
-        return Err(FlowError::runtime_error("str_ends_with requires exactly two arguments"));
+        return Err(FlowError::argument_count_error("str_ends_with requires exactly two arguments"));
     }
     
     match (&args[0], &args[1]) {
@@ -362,7 +362,7 @@ fn stdlib_str_ends_with(args: Vec<Value>) -> Result<Value> {
 
 fn stdlib_str_replace(args: Vec<Value>) -> Result<Value> {
     if args.len() != 3 {
-        return Err(FlowError::runtime_error("str_replace requires exactly three arguments"));
+        return Err(FlowError::argument_count_error("str_replace requires exactly three arguments"));
     }
     
     match (&args[0], &args[1], &args[2]) {
@@ -375,7 +375,7 @@ fn stdlib_str_replace(args: Vec<Value>) -> Result<Value> {
 
 fn stdlib_str_substring(args: Vec<Value>) -> Result<Value> {
     if args.len() < 2 || args.len() > 3 {
-        return Err(FlowError::runtime_error("str_substring requires 2 or 3 arguments"));
+        return Err(FlowError::argument_count_error("str_substring requires 2 or 3 arguments"));
     }
     
     match &args[0] {
@@ -407,7 +407,7 @@ fn stdlib_str_substring(args: Vec<Value>) -> Result<Value> {
 // Array functions
 fn stdlib_array_len(args: Vec<Value>) -> Result<Value> {
     if args.len() != 1 {
-        return Err(FlowError::runtime_error("array_len requires exactly one argument"));
+        return Err(FlowError::argument_count_error("array_len requires exactly one argument"));
     }
     
     match &args[0] {
@@ -418,14 +418,14 @@ fn stdlib_array_len(args: Vec<Value>) -> Result<Value> {
 
 fn stdlib_array_push(args: Vec<Value>) -> Result<Value> {
     if args.len() != 2 {
-        return Err(FlowError::runtime_error("array_push requires exactly two arguments"));
+        return Err(FlowError::argument_count_error("array_push requires exactly two arguments"));
     }
     
     match &args[0] {
         Value::Array(arr) => {
-            let mut new_arr = arr.clone();
-            new_arr.push(args[1].clone());
-            Ok(Value::Array(new_arr))
+            // Arrays are shared, so this pushes onto the caller's array
+            arr.push(args[1].clone());
+            Ok(Value::Array(arr.clone()))
         }
         _ => Err(FlowError::type_error("array_push requires an array as first argument".to_string())),
     }
@@ -433,13 +433,12 @@ fn stdlib_array_push(args: Vec<Value>) -> Result<Value> {
 
 fn stdlib_array_pop(args: Vec<Value>) -> Result<Value> {
     if args.len() != 1 {
-        return Err(FlowError::runtime_error("array_pop requires exactly one argument"));
+        return Err(FlowError::argument_count_error("array_pop requires exactly one argument"));
     }
     
     match &args[0] {
         Value::Array(arr) => {
-            let mut new_arr = arr.clone();
-            match new_arr.pop() {
+            match arr.pop() {
                 Some(value) => Ok(value),
                 None => Ok(Value::Null),
             }
@@ -450,7 +449,7 @@ fn stdlib_array_pop(args: Vec<Value>) -> Result<Value> {
 
 fn stdlib_array_slice(args: Vec<Value>) -> Result<Value> {
     if args.len() < 2 || args.len() > 3 {
-        return Err(FlowError::runtime_error("array_slice requires 2 or 3 arguments"));
+        return Err(FlowError::argument_count_error("array_slice requires 2 or 3 arguments"));
     }
     
     match &args[0] {
@@ -478,17 +477,34 @@ fn stdlib_array_slice(args: Vec<Value>) -> Result<Value> {
     }
 }
 
-// Placeholder implementations for remaining functions
-fn stdlib_array_concat(_args: Vec<Value>) -> Result<Value> {
-    Err(FlowError::runtime_error("array_concat not yet implemented"))
+fn stdlib_array_concat(args: Vec<Value>) -> Result<Value> {
+    let result = FlowArray::new();
+    for arg in &args {
+        match arg {
+            Value::Array(arr) => result.elements_mut().extend(arr.to_vec()),
+            _ => return Err(FlowError::type_error("array_concat requires array arguments".to_string())),
+        }
+    }
+    Ok(Value::Array(result))
 }
 
 fn stdlib_array_reverse(_args: Vec<Value>) -> Result<Value> {
     Err(FlowError::runtime_error("array_reverse not yet implemented"))
 }
 
-fn stdlib_array_sort(_args: Vec<Value>) -> Result<Value> {
-    Err(FlowError::runtime_error("array_sort not yet implemented"))
+fn stdlib_array_sort(args: Vec<Value>) -> Result<Value> {
+    if args.len() != 1 {
+        return Err(FlowError::argument_count_error("array_sort requires exactly one argument"));
+    }
+    
+    match &args[0] {
+        Value::Array(arr) => {
+            let sorted = arr.copy();
+            sorted.sort();
+            Ok(Value::Array(sorted))
+        }
+        _ => Err(FlowError::type_error("array_sort requires an array argument".to_string())),
+    }
 }
 
 fn stdlib_array_map(_args: Vec<Value>) -> Result<Value> {
@@ -507,20 +523,27 @@ fn stdlib_array_find(_args: Vec<Value>) -> Result<Value> {
     Err(FlowError::runtime_error("array_find not yet implemented"))
 }
 
-fn stdlib_array_contains(_args: Vec<Value>) -> Result<Value> {
-    Err(FlowError::runtime_error("array_contains not yet implemented"))
+fn stdlib_array_contains(args: Vec<Value>) -> Result<Value> {
+    if args.len() != 2 {
+        return Err(FlowError::argument_count_error("array_contains requires exactly two arguments"));
+    }
+    
+    match &args[0] {
+        Value::Array(arr) => Ok(Value::Boolean(arr.contains(&args[1]))),
+        _ => Err(FlowError::type_error("array_contains requires an array as first argument".to_string())),
+    }
 }
 
 // Object functions
 fn stdlib_object_keys(args: Vec<Value>) -> Result<Value> {
     if args.len() != 1 {
-        return Err(FlowError::runtime_error("object_keys requires exactly one argument"));
+        return Err(FlowError::argument_count_error("object_keys requires exactly one argument"));
     }
     
     match &args[0] {
         Value::Object(obj) => {
-            let mut array = FlowArray::new();
-            for key in obj.properties.keys() {
+            let array = FlowArray::new();
+            for key in obj.properties().keys() {
                 array.push(Value::String(key.clone()));
             }
             Ok(Value::Array(array))
@@ -531,13 +554,13 @@ fn stdlib_object_keys(args: Vec<Value>) -> Result<Value> {
 
 fn stdlib_object_values(args: Vec<Value>) -> Result<Value> {
     if args.len() != 1 {
-        return Err(FlowError::runtime_error("object_values requires exactly one argument"));
+        return Err(FlowError::argument_count_error("object_values requires exactly one argument"));
     }
     
     match &args[0] {
         Value::Object(obj) => {
-            let mut array = FlowArray::new();
-            for value in obj.properties.values() {
+            let array = FlowArray::new();
+            for value in obj.properties().values() {
                 array.push(value.clone());
             }
             Ok(Value::Array(array))
@@ -554,14 +577,22 @@ fn stdlib_object_has_key(_args: Vec<Value>) -> Result<Value> {
     Err(FlowError::runtime_error("object_has_key not yet implemented"))
 }
 
-fn stdlib_object_merge(_args: Vec<Value>) -> Result<Value> {
-    Err(FlowError::runtime_error("object_merge not yet implemented"))
+fn stdlib_object_merge(args: Vec<Value>) -> Result<Value> {
+    // Later objects overwrite keys of earlier ones
+    let result = FlowObject::new();
+    for arg in &args {
+        match arg {
+            Value::Object(obj) => result.merge(obj),
+            _ => return Err(FlowError::type_error("object_merge requires object arguments".to_string())),
+        }
+    }
+    Ok(Value::Object(result))
 }
 
 // Math functions
 fn stdlib_abs(args: Vec<Value>) -> Result<Value> {
     if args.len() != 1 {
-        return Err(FlowError::runtime_error("abs requires exactly one argument"));
+        return Err(FlowError::argument_count_error("abs requires exactly one argument"));
     }
     
     match &args[0] {
@@ -573,7 +604,7 @@ fn stdlib_abs(args: Vec<Value>) -> Result<Value> {
 
 fn stdlib_min(args: Vec<Value>) -> Result<Value> {
     if args.is_empty() {
-        return Err(FlowError::runtime_error("min requires at least one argument"));
+        return Err(FlowError::argument_count_error("min requires at least one argument"));
     }
     
     let mut min_val = &args[0];
@@ -608,7 +639,7 @@ fn stdlib_min(args: Vec<Value>) -> Result<Value> {
 
 fn stdlib_max(args: Vec<Value>) -> Result<Value> {
     if args.is_empty() {
-        return Err(FlowError::runtime_error("max requires at least one argument"));
+        return Err(FlowError::argument_count_error("max requires at least one argument"));
     }
     
     let mut max_val = &args[0];
@@ -643,7 +674,7 @@ fn stdlib_max(args: Vec<Value>) -> Result<Value> {
 
 fn stdlib_floor(args: Vec<Value>) -> Result<Value> {
     if args.len() != 1 {
-        return Err(FlowError::runtime_error("floor requires exactly one argument"));
+        return Err(FlowError::argument_count_error("floor requires exactly one argument"));
     }
     
     match &args[0] {
@@ -655,7 +686,7 @@ fn stdlib_floor(args: Vec<Value>) -> Result<Value> {
 
 fn stdlib_ceil(args: Vec<Value>) -> Result<Value> {
     if args.len() != 1 {
-        return Err(FlowError::runtime_error("ceil requires exactly one argument"));
+        return Err(FlowError::argument_count_error("ceil requires exactly one argument"));
     }
     
     match &args[0] {
@@ -667,7 +698,7 @@ fn stdlib_ceil(args: Vec<Value>) -> Result<Value> {
 
 fn stdlib_round(args: Vec<Value>) -> Result<Value> {
     if args.len() != 1 {
-        return Err(FlowError::runtime_error("round requires exactly one argument"));
+        return Err(FlowError::argument_count_error("round requires exactly one argument"));
     }
     
     match &args[0] {
@@ -679,7 +710,7 @@ fn stdlib_round(args: Vec<Value>) -> Result<Value> {
 
 fn stdlib_sqrt(args: Vec<Value>) -> Result<Value> {
     if args.len() != 1 {
-        return Err(FlowError::runtime_error("sqrt requires exactly one argument"));
+        return Err(FlowError::argument_count_error("sqrt requires exactly one argument"));
     }
     
     match &args[0] {
@@ -703,7 +734,7 @@ fn stdlib_sqrt(args: Vec<Value>) -> Result<Value> {
 
 fn stdlib_pow(args: Vec<Value>) -> Result<Value> {
     if args.len() != 2 {
-        return Err(FlowError::runtime_error("pow requires exactly two arguments"));
+        return Err(FlowError::argument_count_error("pow requires exactly two arguments"));
     }
     
     match (&args[0], &args[1]) {
@@ -747,14 +778,14 @@ fn stdlib_random(_args: Vec<Value>) -> Result<Value> {
 // IO functions
 fn stdlib_read_file(args: Vec<Value>) -> Result<Value> {
     if args.len() != 1 {
-        return Err(FlowError::runtime_error("read_file requires exactly one argument"));
+        return Err(FlowError::argument_count_error("read_file requires exactly one argument"));
     }
     
     match &args[0] {
         Value::String(path) => {
             match std::fs::read_to_string(path) {
                 Ok(content) => Ok(Value::String(content)),
-                Err(e) => Err(FlowError::runtime_error(&format!("Failed to read file: {}", e))),
+                Err(e) => Err(FlowError::IoError { message: format!("Failed to read file: {}", e) }),
             }
         }
         _ => Err(FlowError::type_error("read_file requires a string argument".to_string())),
@@ -763,14 +794,14 @@ fn stdlib_read_file(args: Vec<Value>) -> Result<Value> {
 
 fn stdlib_write_file(args: Vec<Value>) -> Result<Value> {
     if args.len() != 2 {
-        return Err(FlowError::runtime_error("write_file requires exactly two arguments"));
+        return Err(FlowError::argument_count_error("write_file requires exactly two arguments"));
     }
     
     match (&args[0], &args[1]) {
         (Value::String(path), Value::String(content)) => {
             match std::fs::write(path, content) {
                 Ok(_) => Ok(Value::Null),
-                Err(e) => Err(FlowError::runtime_error(&format!("Failed to write file: {}", e))),
+                Err(e) => Err(FlowError::IoError { message: format!("Failed to write file: {}", e) }),
             }
         }
         _ => Err(FlowError::type_error("write_file requires two string arguments".to_string())),
@@ -779,7 +810,7 @@ fn stdlib_write_file(args: Vec<Value>) -> Result<Value> {
 
 fn stdlib_file_exists(args: Vec<Value>) -> Result<Value> {
     if args.len() != 1 {
-        return Err(FlowError::runtime_error("file_exists requires exactly one argument"));
+        return Err(FlowError::argument_count_error("file_exists requires exactly one argument"));
     }
     
     match &args[0] {
@@ -802,7 +833,7 @@ fn stdlib_now(_args: Vec<Value>) -> Result<Value> {
 
 fn stdlib_sleep(args: Vec<Value>) -> Result<Value> {
     if args.len() != 1 {
-        return Err(FlowError::runtime_error("sleep requires exactly one argument"));
+        return Err(FlowError::argument_count_error("sleep requires exactly one argument"));
     }
     
     match &args[0] {
@@ -821,7 +852,7 @@ fn stdlib_sleep(args: Vec<Value>) -> Result<Value> {
 // Type functions
 fn stdlib_type_of(args: Vec<Value>) -> Result<Value> {
     if args.len() != 1 {
-        return Err(FlowError::runtime_error("type_of requires exactly one argument"));
+        return Err(FlowError::argument_count_error("type_of requires exactly one argument"));
     }
     
     let type_name = args[0].type_name();
@@ -830,7 +861,7 @@ fn stdlib_type_of(args: Vec<Value>) -> Result<Value> {
 
 fn stdlib_to_string(args: Vec<Value>) -> Result<Value> {
     if args.len() != 1 {
-        return Err(FlowError::runtime_error("to_string requires exactly one argument"));
+        return Err(FlowError::argument_count_error("to_string requires exactly one argument"));
     }
     
     Ok(Value::String(format!("{}", args[0])))
@@ -838,7 +869,7 @@ fn stdlib_to_string(args: Vec<Value>) -> Result<Value> {
 
 fn stdlib_to_int(args: Vec<Value>) -> Result<Value> {
     if args.len() != 1 {
-        return Err(FlowError::runtime_error("to_int requires exactly one argument"));
+        return Err(FlowError::argument_count_error("to_int requires exactly one argument"));
     }
     
     match &args[0] {
@@ -857,7 +888,7 @@ fn stdlib_to_int(args: Vec<Value>) -> Result<Value> {
 
 fn stdlib_to_float(args: Vec<Value>) -> Result<Value> {
     if args.len() != 1 {
-        return Err(FlowError::runtime_error("to_float requires exactly one argument"));
+        return Err(FlowError::argument_count_error("to_float requires exactly one argument"));
     }
     
     match &args[0] {
@@ -875,7 +906,7 @@ fn stdlib_to_float(args: Vec<Value>) -> Result<Value> {
 
 fn stdlib_to_bool(args: Vec<Value>) -> Result<Value> {
     if args.len() != 1 {
-        return Err(FlowError::runtime_error("to_bool requires exactly one argument"));
+        return Err(FlowError::argument_count_error("to_bool requires exactly one argument"));
     }
     
     Ok(Value::Boolean(args[0].is_truthy()))
@@ -6,7 +6,7 @@ use std::hash::{Hash, Hasher};
 // Hashing functions
 pub fn hash_string(args: Vec<Value>) -> Result<Value, FlowError> {
     if args.len() != 1 {
-        return Err(FlowError::runtime_error("hash_string expects 1 argument"));
+        return Err(FlowError::argument_count_error("hash_string expects 1 argument"));
     }
     
     match &args[0] {
@@ -22,7 +22,7 @@ pub fn hash_string(args: Vec<Value>) -> Result<Value, FlowError> {
 
 pub fn md5_hash(args: Vec<Value>) -> Result<Value, FlowError> {
     if args.len() != 1 {
-        return Err(FlowError::runtime_error("md5_hash expects 1 argument"));
+        return Err(FlowError::argument_count_error("md5_hash expects 1 argument"));
     }
     
     match &args[0] {
@@ -37,7 +37,7 @@ pub fn md5_hash(args: Vec<Value>) -> Result<Value, FlowError> {
 
 pub fn sha256_hash(args: Vec<Value>) -> Result<Value, FlowError> {
     if args.len() != 1 {
-        return Err(FlowError::runtime_error("sha256_hash expects 1 argument"));
+        return Err(FlowError::argument_count_error("sha256_hash expects 1 argument"));
     }
     
     match &args[0] {
@@ -53,7 +53,7 @@ pub fn sha256_hash(args: Vec<Value>) -> Result<Value, FlowError> {
 // Base64 encoding/decoding
 pub fn base64_encode(args: Vec<Value>) -> Result<Value, FlowError> {
     if args.len() != 1 {
-        return Err(FlowError::runtime_error("base64_encode expects 1 argument"));
+        return Err(FlowError::argument_count_error("base64_encode expects 1 argument"));
     }
     
     match &args[0] {
@@ -67,7 +67,7 @@ pub fn base64_encode(args: Vec<Value>) -> Result<Value, FlowError> {
 
 pub fn base64_decode(args: Vec<Value>) -> Result<Value, FlowError> {
     if args.len() != 1 {
-        return Err(FlowError::runtime_error("base64_decode expects 1 argument"));
+        return Err(FlowError::argument_count_error("base64_decode expects 1 argument"));
     }
     
     match &args[0] {
@@ -89,7 +89,7 @@ pub fn base64_decode(args: Vec<Value>) -> Result<Value, FlowError> {
 // Hex encoding/decoding
 pub fn hex_encode(args: Vec<Value>) -> Result<Value, FlowError> {
     if args.len() != 1 {
-        return Err(FlowError::runtime_error("hex_encode expects 1 argument"));
+        return Err(FlowError::argument_count_error("hex_encode expects 1 argument"));
     }
     
     match &args[0] {
@@ -105,7 +105,7 @@ pub fn hex_encode(args: Vec<Value>) -> Result<Value, FlowError> {
 
 pub fn hex_decode(args: Vec<Value>) -> Result<Value, FlowError> {
     if args.len() != 1 {
-        return Err(FlowError::runtime_error("hex_decode expects 1 argument"));
+        return Err(FlowError::argument_count_error("hex_decode expects 1 argument"));
     }
     
     match &args[0] {
@@ -147,7 +147,7 @@ pub fn random_int(args: Vec<Value>) -> Result<Value, FlowError> {
             _ => return Err(FlowError::runtime_error("random_int expects integer arguments"))
         }
     } else {
-        return Err(FlowError::runtime_error("random_int expects 0, 1, or 2 arguments"));
+        return Err(FlowError::argument_count_error("random_int expects 0, 1, or 2 arguments"));
     };
     
     if min >= max {
@@ -287,7 +287,7 @@ fn simple_random() -> i64 {
 // Initialize random seed
 pub fn set_random_seed(args: Vec<Value>) -> Result<Value, FlowError> {
     if args.len() != 1 {
-        return Err(FlowError::runtime_error("set_random_seed expects 1 argument"));
+        return Err(FlowError::argument_count_error("set_random_seed expects 1 argument"));
     }
     
     match &args[0] {
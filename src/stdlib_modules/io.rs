@@ -11,7 +11,7 @@ use std::path::Path;
 /// Read entire file contents as string
 pub fn read_file(args: Vec<Value>) -> Result<Value> {
     if args.len() != 1 {
-        return Err(FlowError::runtime_error("read_file expects 1 argument"));
+        return Err(FlowError::argument_count_error("read_file expects 1 argument"));
     }
     
     match &args[0] {
@@ -28,7 +28,7 @@ pub fn read_file(args: Vec<Value>) -> Result<Value> {
 /// Write string content to file
 pub fn write_file(args: Vec<Value>) -> Result<Value> {
     if args.len() != 2 {
-        return Err(FlowError::runtime_error("write_file requires exactly two arguments"));
+        return Err(FlowError::argument_count_error("write_file requires exactly two arguments"));
     }
     
     match (&args[0], &args[1]) {
@@ -45,7 +45,7 @@ pub fn write_file(args: Vec<Value>) -> Result<Value> {
 /// Append string content to file
 pub fn append_file(args: Vec<Value>) -> Result<Value> {
     if args.len() != 2 {
-        return Err(FlowError::runtime_error("append_file requires exactly two arguments"));
+        return Err(FlowError::argument_count_error("append_file requires exactly two arguments"));
     }
     
     match (&args[0], &args[1]) {
@@ -67,7 +67,7 @@ pub fn append_file(args: Vec<Value>) -> Result<Value> {
 /// Read file lines as array
 pub fn read_lines(args: Vec<Value>) -> Result<Value> {
     if args.len() != 1 {
-        return Err(FlowError::runtime_error("read_lines requires exactly one argument"));
+        return Err(FlowError::argument_count_error("read_lines requires exactly one argument"));
     }
     
     match &args[0] {
@@ -75,7 +75,7 @@ pub fn read_lines(args: Vec<Value>) -> Result<Value> {
             match File::open(path) {
                 Ok(file) => {
                     let reader = BufReader::new(file);
-                    let mut array = FlowArray::new();
+                    let array = FlowArray::new();
                     
                     for line in reader.lines() {
                         match line {
@@ -96,7 +96,7 @@ pub fn read_lines(args: Vec<Value>) -> Result<Value> {
 /// Check if file exists
 pub fn file_exists(args: Vec<Value>) -> Result<Value> {
     if args.len() != 1 {
-        return Err(FlowError::runtime_error("file_exists requires exactly one argument"));
+        return Err(FlowError::argument_count_error("file_exists requires exactly one argument"));
     }
     
     match &args[0] {
@@ -108,7 +108,7 @@ pub fn file_exists(args: Vec<Value>) -> Result<Value> {
 /// Check if path is directory
 pub fn is_directory(args: Vec<Value>) -> Result<Value> {
     if args.len() != 1 {
-        return Err(FlowError::runtime_error("is_directory requires exactly one argument"));
+        return Err(FlowError::argument_count_error("is_directory requires exactly one argument"));
     }
     
     match &args[0] {
@@ -120,7 +120,7 @@ pub fn is_directory(args: Vec<Value>) -> Result<Value> {
 /// Check if path is file
 pub fn is_file(args: Vec<Value>) -> Result<Value> {
     if args.len() != 1 {
-        return Err(FlowError::runtime_error("is_file requires exactly one argument"));
+        return Err(FlowError::argument_count_error("is_file requires exactly one argument"));
     }
     
     match &args[0] {
@@ -132,7 +132,7 @@ pub fn is_file(args: Vec<Value>) -> Result<Value> {
 /// Create directory
 pub fn create_dir(args: Vec<Value>) -> Result<Value> {
     if args.len() != 1 {
-        return Err(FlowError::runtime_error("create_dir requires exactly one argument"));
+        return Err(FlowError::argument_count_error("create_dir requires exactly one argument"));
     }
     
     match &args[0] {
@@ -149,7 +149,7 @@ pub fn create_dir(args: Vec<Value>) -> Result<Value> {
 /// Remove file or directory
 pub fn remove_path(args: Vec<Value>) -> Result<Value> {
     if args.len() != 1 {
-        return Err(FlowError::runtime_error("remove_path requires exactly one argument"));
+        return Err(FlowError::argument_count_error("remove_path requires exactly one argument"));
     }
     
     match &args[0] {
@@ -174,14 +174,14 @@ pub fn remove_path(args: Vec<Value>) -> Result<Value> {
 /// List directory contents
 pub fn list_dir(args: Vec<Value>) -> Result<Value> {
     if args.len() != 1 {
-        return Err(FlowError::runtime_error("list_dir requires exactly one argument"));
+        return Err(FlowError::argument_count_error("list_dir requires exactly one argument"));
     }
     
     match &args[0] {
         Value::String(path) => {
             match fs::read_dir(path) {
                 Ok(entries) => {
-                    let mut array = FlowArray::new();
+                    let array = FlowArray::new();
                     
                     for entry in entries {
                         match entry {
@@ -206,7 +206,7 @@ pub fn list_dir(args: Vec<Value>) -> Result<Value> {
 /// Copy file
 pub fn copy_file(args: Vec<Value>) -> Result<Value> {
     if args.len() != 2 {
-        return Err(FlowError::runtime_error("copy_file requires exactly two arguments"));
+        return Err(FlowError::argument_count_error("copy_file requires exactly two arguments"));
     }
     
     match (&args[0], &args[1]) {
@@ -223,7 +223,7 @@ pub fn copy_file(args: Vec<Value>) -> Result<Value> {
 /// Get file size
 pub fn file_size(args: Vec<Value>) -> Result<Value> {
     if args.len() != 1 {
-        return Err(FlowError::runtime_error("file_size requires exactly one argument"));
+        return Err(FlowError::argument_count_error("file_size requires exactly one argument"));
     }
     
     match &args[0] {
@@ -5,7 +5,7 @@ use std::collections::HashMap;
 // JSON parsing and stringification
 pub fn json_parse(args: Vec<Value>) -> Result<Value, FlowError> {
     if args.len() != 1 {
-        return Err(FlowError::runtime_error("json_parse expects 1 argument"));
+        return Err(FlowError::argument_count_error("json_parse expects 1 argument"));
     }
     
     match &args[0] {
@@ -21,7 +21,7 @@ pub fn json_parse(args: Vec<Value>) -> Result<Value, FlowError> {
 
 pub fn json_stringify(args: Vec<Value>) -> Result<Value, FlowError> {
     if args.is_empty() {
-        return Err(FlowError::runtime_error("json_stringify expects at least 1 argument"));
+        return Err(FlowError::argument_count_error("json_stringify expects at least 1 argument"));
     }
     
     let pretty = if args.len() > 1 {
@@ -155,7 +155,7 @@ impl JsonParser {
         // Handle empty object
         if self.current_char() == Some('}') {
             self.advance();
-            return Ok(Value::Object(FlowObject { properties: object }));
+            return Ok(Value::Object(FlowObject::from_map(object)));
         }
         
         loop {
@@ -194,7 +194,7 @@ impl JsonParser {
             }
         }
         
-        Ok(Value::Object(FlowObject { properties: object }))
+        Ok(Value::Object(FlowObject::from_map(object)))
     }
     
     fn parse_array(&mut self) -> Result<Value, String> {
@@ -206,7 +206,7 @@ impl JsonParser {
         // Handle empty array
         if self.current_char() == Some(']') {
             self.advance();
-            return Ok(Value::Array(FlowArray { elements: array }));
+            return Ok(Value::Array(FlowArray::from_values(array)));
         }
         
         loop {
@@ -228,7 +228,7 @@ impl JsonParser {
             }
         }
         
-        Ok(Value::Array(FlowArray { elements: array }))
+        Ok(Value::Array(FlowArray::from_values(array)))
     }
     
     fn parse_boolean(&mut self) -> Result<Value, String> {
@@ -333,7 +333,7 @@ fn stringify_json(value: &Value, pretty: bool, indent: usize) -> Result<String,
             
             let mut result = String::from("[");
             
-            for (i, item) in arr.elements.iter().enumerate() {
+            for (i, item) in arr.elements().iter().enumerate() {
                 if i > 0 {
                     result.push(',');
                 }
@@ -361,7 +361,7 @@ fn stringify_json(value: &Value, pretty: bool, indent: usize) -> Result<String,
             
             let mut result = String::from("{");
             
-            for (i, (key, value)) in obj.properties.iter().enumerate() {
+            for (i, (key, value)) in obj.properties().iter().enumerate() {
                 if i > 0 {
                     result.push(',');
                 }
@@ -7,7 +7,7 @@ use std::time::Duration;
 // HTTP client functionality
 pub fn http_get(args: Vec<Value>) -> Result<Value, FlowError> {
     if args.is_empty() {
-        return Err(FlowError::runtime_error("http_get expects at least 1 argument (URL)"));
+        return Err(FlowError::argument_count_error("http_get expects at least 1 argument (URL)"));
     }
     
     let url = match &args[0] {
@@ -18,7 +18,7 @@ pub fn http_get(args: Vec<Value>) -> Result<Value, FlowError> {
     // Parse headers if provided
     let headers = if args.len() > 1 {
         match &args[1] {
-            Value::Object(h) => Some(&h.properties),
+            Value::Object(h) => Some(h.to_map()),
             Value::Null => None,
             _ => return Err(FlowError::runtime_error("http_get headers must be an object"))
         }
@@ -28,7 +28,7 @@ pub fn http_get(args: Vec<Value>) -> Result<Value, FlowError> {
     
     // Simple HTTP GET implementation using std library
     // Note: In a real implementation, you'd want to use a proper HTTP client like reqwest
-    match make_http_request("GET", url, headers, None) {
+    match make_http_request("GET", url, headers.as_ref(), None) {
         Ok(response) => Ok(response),
         Err(e) => Err(FlowError::runtime_error(&format!("HTTP GET failed: {}", e)))
     }
@@ -36,7 +36,7 @@ pub fn http_get(args: Vec<Value>) -> Result<Value, FlowError> {
 
 pub fn http_post(args: Vec<Value>) -> Result<Value, FlowError> {
     if args.len() < 2 {
-        return Err(FlowError::runtime_error("http_post expects at least 2 arguments (URL, body)"));
+        return Err(FlowError::argument_count_error("http_post expects at least 2 arguments (URL, body)"));
     }
     
     let url = match &args[0] {
@@ -53,7 +53,7 @@ pub fn http_post(args: Vec<Value>) -> Result<Value, FlowError> {
     // Parse headers if provided
     let headers = if args.len() > 2 {
         match &args[2] {
-            Value::Object(h) => Some(&h.properties),
+            Value::Object(h) => Some(h.to_map()),
             Value::Null => None,
             _ => return Err(FlowError::runtime_error("http_post headers must be an object"))
         }
@@ -61,7 +61,7 @@ pub fn http_post(args: Vec<Value>) -> Result<Value, FlowError> {
         None
     };
     
-    match make_http_request("POST", url, headers, body) {
+    match make_http_request("POST", url, headers.as_ref(), body) {
         Ok(response) => Ok(response),
         Err(e) => Err(FlowError::runtime_error(&format!("HTTP POST failed: {}", e)))
     }
@@ -69,7 +69,7 @@ pub fn http_post(args: Vec<Value>) -> Result<Value, FlowError> {
 
 pub fn http_put(args: Vec<Value>) -> Result<Value, FlowError> {
     if args.len() < 2 {
-        return Err(FlowError::runtime_error("http_put expects at least 2 arguments (URL, body)"));
+        return Err(FlowError::argument_count_error("http_put expects at least 2 arguments (URL, body)"));
     }
     
     let url = match &args[0] {
@@ -85,7 +85,7 @@ pub fn http_put(args: Vec<Value>) -> Result<Value, FlowError> {
     
     let headers = if args.len() > 2 {
         match &args[2] {
-            Value::Object(h) => Some(&h.properties),
+            Value::Object(h) => Some(h.to_map()),
             Value::Null => None,
             _ => return Err(FlowError::runtime_error("http_put headers must be an object"))
         }
@@ -93,7 +93,7 @@ pub fn http_put(args: Vec<Value>) -> Result<Value, FlowError> {
         None
     };
     
-    match make_http_request("PUT", url, headers, body) {
+    match make_http_request("PUT", url, headers.as_ref(), body) {
         Ok(response) => Ok(response),
         Err(e) => Err(FlowError::runtime_error(&format!("HTTP PUT failed: {}", e)))
     }
@@ -101,7 +101,7 @@ pub fn http_put(args: Vec<Value>) -> Result<Value, FlowError> {
 
 pub fn http_delete(args: Vec<Value>) -> Result<Value, FlowError> {
     if args.is_empty() {
-        return Err(FlowError::runtime_error("http_delete expects at least 1 argument (URL)"));
+        return Err(FlowError::argument_count_error("http_delete expects at least 1 argument (URL)"));
     }
     
     let url = match &args[0] {
@@ -111,7 +111,7 @@ pub fn http_delete(args: Vec<Value>) -> Result<Value, FlowError> {
     
     let headers = if args.len() > 1 {
         match &args[1] {
-            Value::Object(h) => Some(&h.properties),
+            Value::Object(h) => Some(h.to_map()),
             Value::Null => None,
             _ => return Err(FlowError::runtime_error("http_delete headers must be an object"))
         }
@@ -119,7 +119,7 @@ pub fn http_delete(args: Vec<Value>) -> Result<Value, FlowError> {
         None
     };
     
-    match make_http_request("DELETE", url, headers, None) {
+    match make_http_request("DELETE", url, headers.as_ref(), None) {
         Ok(response) => Ok(response),
         Err(e) => Err(FlowError::runtime_error(&format!("HTTP DELETE failed: {}", e)))
     }
@@ -283,7 +283,7 @@ fn make_http_request(
     let mut response_map = HashMap::new();
     response_map.insert("status".to_string(), Value::Integer(status_code));
     response_map.insert("body".to_string(), Value::String(response_body));
-    response_map.insert("headers".to_string(), Value::Object(FlowObject { properties: response_headers }));
+    response_map.insert("headers".to_string(), Value::Object(FlowObject::from_map(response_headers)));
     
     // Add request info for debugging
     let mut request_info = HashMap::new();
@@ -291,16 +291,16 @@ fn make_http_request(
     request_info.insert("url".to_string(), Value::String(url.to_string()));
     
     if let Some(h) = headers {
-        request_info.insert("headers".to_string(), Value::Object(FlowObject { properties: h.clone() }));
+        request_info.insert("headers".to_string(), Value::Object(FlowObject::from_map(h.clone())));
     }
     
     if let Some(b) = body {
         request_info.insert("body".to_string(), Value::String(b.to_string()));
     }
     
-    response_map.insert("request".to_string(), Value::Object(FlowObject { properties: request_info }));
+    response_map.insert("request".to_string(), Value::Object(FlowObject::from_map(request_info)));
 
-    Ok(Value::Object(FlowObject { properties: response_map }))
+    Ok(Value::Object(FlowObject::from_map(response_map)))
 }
 
 // Helper struct for URL parsing
@@ -376,7 +376,7 @@ fn parse_header_line(line: &str) -> Option<(String, String)> {
 // URL utilities
 pub fn url_encode(args: Vec<Value>) -> Result<Value, FlowError> {
     if args.len() != 1 {
-        return Err(FlowError::runtime_error("url_encode expects 1 argument"));
+        return Err(FlowError::argument_count_error("url_encode expects 1 argument"));
     }
     
     match &args[0] {
@@ -390,7 +390,7 @@ pub fn url_encode(args: Vec<Value>) -> Result<Value, FlowError> {
 
 pub fn url_decode(args: Vec<Value>) -> Result<Value, FlowError> {
     if args.len() != 1 {
-        return Err(FlowError::runtime_error("url_decode expects 1 argument"));
+        return Err(FlowError::argument_count_error("url_decode expects 1 argument"));
     }
     
     match &args[0] {
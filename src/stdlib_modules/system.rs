@@ -7,7 +7,7 @@ use std::collections::HashMap;
 // Environment variable operations
 pub fn get_env(args: Vec<Value>) -> Result<Value, FlowError> {
     if args.len() != 1 {
-        return Err(FlowError::runtime_error("get_env expects 1 argument"));
+        return Err(FlowError::argument_count_error("get_env expects 1 argument"));
     }
     
     match &args[0] {
@@ -23,7 +23,7 @@ pub fn get_env(args: Vec<Value>) -> Result<Value, FlowError> {
 
 pub fn set_env(args: Vec<Value>) -> Result<Value, FlowError> {
     if args.len() != 2 {
-        return Err(FlowError::runtime_error("set_env expects 2 arguments"));
+        return Err(FlowError::argument_count_error("set_env expects 2 arguments"));
     }
     
     match (&args[0], &args[1]) {
@@ -37,7 +37,7 @@ pub fn set_env(args: Vec<Value>) -> Result<Value, FlowError> {
 
 pub fn remove_env(args: Vec<Value>) -> Result<Value, FlowError> {
     if args.len() != 1 {
-        return Err(FlowError::runtime_error("remove_env expects 1 argument"));
+        return Err(FlowError::argument_count_error("remove_env expects 1 argument"));
     }
     
     match &args[0] {
@@ -56,13 +56,13 @@ pub fn get_all_env(_args: Vec<Value>) -> Result<Value, FlowError> {
         env_map.insert(key, Value::String(value));
     }
     
-    Ok(Value::Object(FlowObject { properties: env_map }))
+    Ok(Value::Object(FlowObject::from_map(env_map)))
 }
 
 // Process operations
 pub fn execute_command(args: Vec<Value>) -> Result<Value, FlowError> {
     if args.is_empty() {
-        return Err(FlowError::runtime_error("execute_command expects at least 1 argument"));
+        return Err(FlowError::argument_count_error("execute_command expects at least 1 argument"));
     }
     
     let command = match &args[0] {
@@ -87,7 +87,7 @@ pub fn execute_command(args: Vec<Value>) -> Result<Value, FlowError> {
             result.insert("stderr".to_string(), Value::String(String::from_utf8_lossy(&output.stderr).to_string()));
             result.insert("exit_code".to_string(), Value::Integer(output.status.code().unwrap_or(-1) as i64));
             result.insert("success".to_string(), Value::Boolean(output.status.success()));
-            Ok(Value::Object(FlowObject { properties: result }))
+            Ok(Value::Object(FlowObject::from_map(result)))
         }
         Err(e) => Err(FlowError::runtime_error(&format!("Failed to execute command: {}", e)))
     }
@@ -102,7 +102,7 @@ pub fn get_current_dir(_args: Vec<Value>) -> Result<Value, FlowError> {
 
 pub fn change_dir(args: Vec<Value>) -> Result<Value, FlowError> {
     if args.len() != 1 {
-        return Err(FlowError::runtime_error("change_dir expects 1 argument"));
+        return Err(FlowError::argument_count_error("change_dir expects 1 argument"));
     }
     
     match &args[0] {
@@ -118,7 +118,7 @@ pub fn change_dir(args: Vec<Value>) -> Result<Value, FlowError> {
 
 pub fn get_args(_args: Vec<Value>) -> Result<Value, FlowError> {
     let args: Vec<Value> = env::args().map(|arg| Value::String(arg)).collect();
-    Ok(Value::Array(FlowArray { elements: args }))
+    Ok(Value::Array(FlowArray::from_values(args)))
 }
 
 pub fn exit_program(args: Vec<Value>) -> Result<Value, FlowError> {
@@ -1,9 +1,14 @@
 //! Shared value types for the Flow language
 
+use std::cell::{Ref, RefCell, RefMut};
+use std::cmp::Ordering;
 use std::collections::HashMap;
 use std::fmt;
-use crate::ast::{Statement, Expression, Parameter};
+use std::path::PathBuf;
+use std::rc::Rc;
+use crate::ast::{Statement, LambdaBody, Parameter};
 use crate::bigint::BigInt;
+use crate::bytecode::Upvalue;
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum Value {
@@ -15,238 +20,372 @@ pub enum Value {
     Null,
     Array(FlowArray),
     Object(FlowObject),
+    /// A `def` function. User-defined functions carry the scope they were
+    /// declared in; stdlib wrappers have no closure.
     Function {
         name: String,
         parameters: Vec<Parameter>,
         body: Vec<Statement>,
+        closure: Option<Environment>,
     },
     Lambda {
         parameters: Vec<Parameter>,
-        body: Box<Expression>,
+        body: LambdaBody,
         closure: Environment,
     },
+    /// A compiled function or lambda. `captured` holds the variables it closes
+    /// over and is empty in the constant pool until `NewClosure` fills it in.
     BytecodeFunction {
         address: usize,
         arity: usize,
         locals_count: usize,
+        captured: Vec<Upvalue>,
     },
 }
 
+/// An array value. Clones are references to the same elements, so a change
+/// made through one is seen through all of them; `copy` makes a new array.
 #[derive(Debug, Clone, PartialEq)]
 pub struct FlowArray {
-    pub elements: Vec<Value>,
+    elements: Rc<RefCell<Vec<Value>>>,
 }
 
+/// An object value. Like arrays, clones share the same properties.
 #[derive(Debug, Clone, PartialEq)]
 pub struct FlowObject {
-    pub properties: HashMap<String, Value>,
+    properties: Rc<RefCell<HashMap<String, Value>>>,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+/// A handle to a scope in the interpreter's scope chain. Cloning the handle
+/// shares the scope rather than copying it, so closures see and update the
+/// same variables as the code that created them.
+#[derive(Clone)]
 pub struct Environment {
-    pub variables: HashMap<String, Value>,
-    pub functions: HashMap<String, Value>,
-    pub parent: Option<Box<Environment>>,
+    scope: Rc<RefCell<Scope>>,
+}
+
+struct Scope {
+    variables: HashMap<String, Value>,
+    functions: HashMap<String, Value>,
+    parent: Option<Environment>,
+    /// Whether this scope belongs to a block, such as one loop iteration,
+    /// rather than to a function call or the program
+    is_block: bool,
+    /// For the global scope of an imported module, the module's file
+    file: Option<PathBuf>,
 }
 
 impl FlowArray {
     pub fn new() -> Self {
-        FlowArray {
-            elements: Vec::new(),
-        }
+        Self::from_values(Vec::new())
     }
 
     pub fn with_capacity(capacity: usize) -> Self {
+        Self::from_values(Vec::with_capacity(capacity))
+    }
+
+    pub fn from_values(values: Vec<Value>) -> Self {
         FlowArray {
-            elements: Vec::with_capacity(capacity),
+            elements: Rc::new(RefCell::new(values)),
         }
     }
 
-    pub fn from_values(values: Vec<Value>) -> Self {
-        FlowArray { elements: values }
+    /// Borrow the elements. The borrow must end before the array is changed.
+    pub fn elements(&self) -> Ref<'_, Vec<Value>> {
+        self.elements.borrow()
     }
 
-    pub fn push(&mut self, value: Value) {
-        self.elements.push(value);
+    pub fn elements_mut(&self) -> RefMut<'_, Vec<Value>> {
+        self.elements.borrow_mut()
     }
 
-    pub fn pop(&mut self) -> Option<Value> {
-        self.elements.pop()
+    /// A snapshot of the elements, for walking the array while running code
+    /// that may change it
+    pub fn to_vec(&self) -> Vec<Value> {
+        self.elements.borrow().clone()
     }
 
-    pub fn get(&self, index: usize) -> Option<&Value> {
-        self.elements.get(index)
+    /// A new array holding the same elements
+    pub fn copy(&self) -> FlowArray {
+        Self::from_values(self.to_vec())
     }
 
-    pub fn set(&mut self, index: usize, value: Value) -> Result<(), String> {
-        if index < self.elements.len() {
-            self.elements[index] = value;
+    /// Whether both values refer to the same array
+    pub fn ptr_eq(&self, other: &FlowArray) -> bool {
+        Rc::ptr_eq(&self.elements, &other.elements)
+    }
+
+    /// Identifies the elements this array shares with its clones
+    fn id(&self) -> *const () {
+        Rc::as_ptr(&self.elements) as *const ()
+    }
+
+    pub fn push(&self, value: Value) {
+        self.elements.borrow_mut().push(value);
+    }
+
+    pub fn pop(&self) -> Option<Value> {
+        self.elements.borrow_mut().pop()
+    }
+
+    pub fn get(&self, index: usize) -> Option<Value> {
+        self.elements.borrow().get(index).cloned()
+    }
+
+    pub fn set(&self, index: usize, value: Value) -> Result<(), String> {
+        let mut elements = self.elements.borrow_mut();
+        if index < elements.len() {
+            elements[index] = value;
             Ok(())
         } else {
-            Err(format!("Index {} out of bounds for array of length {}", index, self.elements.len()))
+            Err(format!("Index {} out of bounds for array of length {}", index, elements.len()))
         }
     }
 
     pub fn len(&self) -> usize {
-        self.elements.len()
+        self.elements.borrow().len()
     }
 
     pub fn is_empty(&self) -> bool {
-        self.elements.is_empty()
+        self.elements.borrow().is_empty()
     }
 
-    pub fn clear(&mut self) {
-        self.elements.clear();
+    pub fn clear(&self) {
+        self.elements.borrow_mut().clear();
     }
 
     pub fn contains(&self, value: &Value) -> bool {
-        self.elements.contains(value)
+        self.index_of(value).is_some()
     }
 
     pub fn index_of(&self, value: &Value) -> Option<usize> {
-        self.elements.iter().position(|x| x == value)
+        self.elements.borrow().iter().position(|x| x.equals(value))
     }
 
-    pub fn reverse(&mut self) {
-        self.elements.reverse();
+    pub fn reverse(&self) {
+        self.elements.borrow_mut().reverse();
     }
 
-    pub fn sort(&mut self) {
-        self.elements.sort_by(|a, b| {
-            match (a, b) {
-                (Value::Integer(a), Value::Integer(b)) => a.cmp(b),
-                (Value::Float(a), Value::Float(b)) => a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal),
-                (Value::String(a), Value::String(b)) => a.cmp(b),
-                (Value::Boolean(a), Value::Boolean(b)) => a.cmp(b),
-                _ => std::cmp::Ordering::Equal,
-            }
-        });
+    /// Sort the elements in place by `Value::compare`
+    pub fn sort(&self) {
+        // Sort a snapshot, since an element may be this array itself
+        let mut elements = self.to_vec();
+        elements.sort_by(Value::compare);
+        *self.elements.borrow_mut() = elements;
     }
 
     pub fn slice(&self, start: usize, end: usize) -> Result<FlowArray, String> {
+        let elements = self.elements.borrow();
         if start > end {
             return Err("Start index cannot be greater than end index".to_string());
         }
-        if start > self.elements.len() {
+        if start > elements.len() {
             return Err("Start index out of bounds".to_string());
         }
-        let end = end.min(self.elements.len());
-        Ok(FlowArray {
-            elements: self.elements[start..end].to_vec(),
-        })
+        let end = end.min(elements.len());
+        Ok(FlowArray::from_values(elements[start..end].to_vec()))
     }
 }
 
 impl FlowObject {
     pub fn new() -> Self {
-        FlowObject {
-            properties: HashMap::new(),
-        }
+        Self::from_map(HashMap::new())
     }
 
     pub fn with_capacity(capacity: usize) -> Self {
+        Self::from_map(HashMap::with_capacity(capacity))
+    }
+
+    pub fn from_map(map: HashMap<String, Value>) -> Self {
         FlowObject {
-            properties: HashMap::with_capacity(capacity),
+            properties: Rc::new(RefCell::new(map)),
         }
     }
 
-    pub fn from_map(map: HashMap<String, Value>) -> Self {
-        FlowObject { properties: map }
+    /// Borrow the properties. The borrow must end before the object is changed.
+    pub fn properties(&self) -> Ref<'_, HashMap<String, Value>> {
+        self.properties.borrow()
+    }
+
+    pub fn properties_mut(&self) -> RefMut<'_, HashMap<String, Value>> {
+        self.properties.borrow_mut()
+    }
+
+    /// A snapshot of the properties
+    pub fn to_map(&self) -> HashMap<String, Value> {
+        self.properties.borrow().clone()
+    }
+
+    /// A new object holding the same properties
+    pub fn copy(&self) -> FlowObject {
+        Self::from_map(self.to_map())
+    }
+
+    /// Whether both values refer to the same object
+    pub fn ptr_eq(&self, other: &FlowObject) -> bool {
+        Rc::ptr_eq(&self.properties, &other.properties)
     }
 
-    pub fn get(&self, key: &str) -> Option<&Value> {
-        self.properties.get(key)
+    /// Identifies the properties this object shares with its clones
+    fn id(&self) -> *const () {
+        Rc::as_ptr(&self.properties) as *const ()
     }
 
-    pub fn set(&mut self, key: String, value: Value) {
-        self.properties.insert(key, value);
+    pub fn get(&self, key: &str) -> Option<Value> {
+        self.properties.borrow().get(key).cloned()
     }
 
-    pub fn remove(&mut self, key: &str) -> Option<Value> {
-        self.properties.remove(key)
+    pub fn set(&self, key: String, value: Value) {
+        self.properties.borrow_mut().insert(key, value);
+    }
+
+    pub fn remove(&self, key: &str) -> Option<Value> {
+        self.properties.borrow_mut().remove(key)
     }
 
     pub fn contains_key(&self, key: &str) -> bool {
-        self.properties.contains_key(key)
+        self.properties.borrow().contains_key(key)
     }
 
     pub fn keys(&self) -> Vec<String> {
-        self.properties.keys().cloned().collect()
+        self.properties.borrow().keys().cloned().collect()
     }
 
-    pub fn values(&self) -> Vec<&Value> {
-        self.properties.values().collect()
+    pub fn values(&self) -> Vec<Value> {
+        self.properties.borrow().values().cloned().collect()
     }
 
     pub fn len(&self) -> usize {
-        self.properties.len()
+        self.properties.borrow().len()
     }
 
     pub fn is_empty(&self) -> bool {
-        self.properties.is_empty()
+        self.properties.borrow().is_empty()
     }
 
-    pub fn clear(&mut self) {
-        self.properties.clear();
+    pub fn clear(&self) {
+        self.properties.borrow_mut().clear();
     }
 
-    pub fn merge(&mut self, other: &FlowObject) {
-        for (key, value) in &other.properties {
-            self.properties.insert(key.clone(), value.clone());
-        }
+    pub fn merge(&self, other: &FlowObject) {
+        // Snapshot first in case `other` is this same object
+        let other = other.to_map();
+        self.properties.borrow_mut().extend(other);
     }
 }
 
 impl Environment {
     pub fn new() -> Self {
-        Environment {
-            variables: HashMap::new(),
-            functions: HashMap::new(),
-            parent: None,
-        }
+        Self::from_scope(None, false)
     }
 
     pub fn with_parent(parent: Environment) -> Self {
+        Self::from_scope(Some(parent), false)
+    }
+
+    /// The global scope of the module loaded from `file`
+    pub fn for_file(file: PathBuf) -> Self {
+        let environment = Self::new();
+        environment.scope.borrow_mut().file = Some(file);
+        environment
+    }
+
+    /// The file of the module this scope belongs to, or None for the main
+    /// script
+    pub fn file(&self) -> Option<PathBuf> {
+        let scope = self.scope.borrow();
+        match &scope.parent {
+            Some(parent) => parent.file(),
+            None => scope.file.clone(),
+        }
+    }
+
+    fn from_scope(parent: Option<Environment>, is_block: bool) -> Self {
         Environment {
-            variables: HashMap::new(),
-            functions: HashMap::new(),
-            parent: Some(Box::new(parent)),
+            scope: Rc::new(RefCell::new(Scope {
+                variables: HashMap::new(),
+                functions: HashMap::new(),
+                parent,
+                is_block,
+                file: None,
+            })),
         }
     }
 
+    /// Whether this is the outermost (global) scope
+    pub fn is_global(&self) -> bool {
+        self.scope.borrow().parent.is_none()
+    }
+
     pub fn define_variable(&mut self, name: String, value: Value) {
-        self.variables.insert(name, value);
+        self.scope.borrow_mut().variables.insert(name, value);
+    }
+
+    /// Define a variable for `let`. Inside a block scope, a variable of the
+    /// same name in the enclosing blocks or their function is updated
+    /// instead, and only new names stay local to the block.
+    pub fn declare_variable(&mut self, name: String, value: Value) {
+        match self.declaring_scope(&name) {
+            Some(mut scope) => scope.define_variable(name, value),
+            None => self.define_variable(name, value),
+        }
+    }
+
+    /// The scope among the enclosing blocks and their function that already
+    /// holds `name`
+    fn declaring_scope(&self, name: &str) -> Option<Environment> {
+        let scope = self.scope.borrow();
+        if scope.variables.contains_key(name) {
+            Some(self.clone())
+        } else if scope.is_block {
+            scope.parent.as_ref()?.declaring_scope(name)
+        } else {
+            None
+        }
     }
 
     pub fn define_function(&mut self, name: String, value: Value) {
-        self.functions.insert(name, value);
+        self.scope.borrow_mut().functions.insert(name, value);
     }
 
-    pub fn get_variable(&self, name: &str) -> Option<&Value> {
-        if let Some(value) = self.variables.get(name) {
-            Some(value)
-        } else if let Some(parent) = &self.parent {
+    pub fn get_variable(&self, name: &str) -> Option<Value> {
+        let scope = self.scope.borrow();
+        if let Some(value) = scope.variables.get(name) {
+            Some(value.clone())
+        } else if let Some(parent) = &scope.parent {
             parent.get_variable(name)
         } else {
             None
         }
     }
 
-    pub fn get_function(&self, name: &str) -> Option<&Value> {
-        if let Some(value) = self.functions.get(name) {
-            Some(value)
-        } else if let Some(parent) = &self.parent {
+    pub fn get_function(&self, name: &str) -> Option<Value> {
+        let scope = self.scope.borrow();
+        if let Some(value) = scope.functions.get(name) {
+            Some(value.clone())
+        } else if let Some(parent) = &scope.parent {
             parent.get_function(name)
         } else {
             None
         }
     }
 
+    /// The names of every variable and function visible from this scope
+    pub fn names(&self) -> Vec<String> {
+        let scope = self.scope.borrow();
+        let mut names: Vec<String> = scope.variables.keys().chain(scope.functions.keys()).cloned().collect();
+        if let Some(parent) = &scope.parent {
+            names.extend(parent.names());
+        }
+        names
+    }
+
     pub fn set_variable(&mut self, name: &str, value: Value) -> Result<(), String> {
-        if self.variables.contains_key(name) {
-            self.variables.insert(name.to_string(), value);
+        let mut scope = self.scope.borrow_mut();
+        if let Some(variable) = scope.variables.get_mut(name) {
+            *variable = value;
             Ok(())
-        } else if let Some(parent) = &mut self.parent {
+        } else if let Some(parent) = &mut scope.parent {
             parent.set_variable(name, value)
         } else {
             Err(format!("Undefined variable: {}", name))
@@ -254,22 +393,44 @@ impl Environment {
     }
 
     pub fn push_scope(&mut self) {
-        let new_env = Environment {
-            variables: HashMap::new(),
-            functions: HashMap::new(),
-            parent: None,
-        };
-        let old_env = std::mem::replace(self, new_env);
-        self.parent = Some(Box::new(old_env));
+        let parent = self.clone();
+        *self = Self::with_parent(parent);
+    }
+
+    /// Enter the scope of a block, such as one iteration of a loop body
+    pub fn push_block_scope(&mut self) {
+        let parent = self.clone();
+        *self = Self::from_scope(Some(parent), true);
     }
 
     pub fn pop_scope(&mut self) {
-        if let Some(parent) = self.parent.take() {
-            *self = *parent;
+        let parent = self.scope.borrow().parent.clone();
+        if let Some(parent) = parent {
+            *self = parent;
         }
     }
 }
 
+impl PartialEq for Environment {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.scope, &other.scope)
+    }
+}
+
+impl fmt::Debug for Environment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Scopes can hold closures that point back at them, so only the
+        // names are shown
+        let scope = self.scope.borrow();
+        let mut names: Vec<&String> = scope.variables.keys().chain(scope.functions.keys()).collect();
+        names.sort();
+        f.debug_struct("Environment")
+            .field("names", &names)
+            .field("parent", &scope.parent)
+            .finish()
+    }
+}
+
 impl Value {
     pub fn type_name(&self) -> &'static str {
         match self {
@@ -301,10 +462,187 @@ impl Value {
             other => other,
         }
     }
-}
+    
+    /// Deep equality, used by `==`. Arrays and objects are equal when their
+    /// contents are, and integers, big integers and floats are equal when they
+    /// have the same numeric value. Functions are only equal to themselves.
+    pub fn equals(&self, other: &Value) -> bool {
+        self.equals_with(other, &mut Vec::new())
+    }
+    
+    /// `equals`, where `comparing` holds the pairs of arrays or objects being
+    /// compared further out. Reaching one of those pairs again means both
+    /// sides loop back in the same way, which is taken as equal.
+    fn equals_with(&self, other: &Value, comparing: &mut Vec<(*const (), *const ())>) -> bool {
+        match (self, other) {
+            (Value::Array(a), Value::Array(b)) => {
+                let pair = (a.id(), b.id());
+                if a.ptr_eq(b) || comparing.contains(&pair) {
+                    return true;
+                }
+                let (a, b) = (a.elements(), b.elements());
+                if a.len() != b.len() {
+                    return false;
+                }
+                comparing.push(pair);
+                let equal = a.iter().zip(b.iter()).all(|(x, y)| x.equals_with(y, comparing));
+                comparing.pop();
+                equal
+            }
+            (Value::Object(a), Value::Object(b)) => {
+                let pair = (a.id(), b.id());
+                if a.ptr_eq(b) || comparing.contains(&pair) {
+                    return true;
+                }
+                let (a, b) = (a.properties(), b.properties());
+                if a.len() != b.len() {
+                    return false;
+                }
+                comparing.push(pair);
+                let equal = a.iter().all(|(key, x)| b.get(key).is_some_and(|y| x.equals_with(y, comparing)));
+                comparing.pop();
+                equal
+            }
+            (Value::Function { .. } | Value::Lambda { .. } | Value::BytecodeFunction { .. }, _) => self == other,
+            _ => self.kind_rank() == other.kind_rank() && self.compare(other) == Ordering::Equal,
+        }
+    }
+    
+    /// A total order over all values, used for sorting. Values of different
+    /// kinds order as null, booleans, numbers, strings, arrays, objects and
+    /// then functions. Numbers compare by value whatever their type, with NaN
+    /// after all others; strings compare by character code, arrays element by
+    /// element, and objects by their entries in key order. Values that
+    /// `equals` calls equal compare as `Equal`.
+    pub fn compare(&self, other: &Value) -> Ordering {
+        self.compare_with(other, &mut Vec::new())
+    }
+    
+    /// `compare`, where `comparing` holds the pairs of arrays or objects being
+    /// compared further out, which compare as `Equal` when reached again, as
+    /// in `equals_with`
+    fn compare_with(&self, other: &Value, comparing: &mut Vec<(*const (), *const ())>) -> Ordering {
+        let rank = self.kind_rank().cmp(&other.kind_rank());
+        if rank != Ordering::Equal {
+            return rank;
+        }
+        match (self, other) {
+            (Value::Boolean(a), Value::Boolean(b)) => a.cmp(b),
+            (Value::Integer(a), Value::Integer(b)) => a.cmp(b),
+            (Value::BigInteger(a), Value::BigInteger(b)) => a.cmp(b),
+            (Value::BigInteger(a), Value::Integer(b)) => a.cmp(&BigInt::from_i64(*b)),
+            (Value::Integer(a), Value::BigInteger(b)) => BigInt::from_i64(*a).cmp(b),
+            (Value::Float(a), Value::Float(b)) => compare_floats(*a, *b),
+            (Value::Integer(a), Value::Float(b)) => compare_integer_to_float(*a, *b),
+            (Value::Float(a), Value::Integer(b)) => compare_integer_to_float(*b, *a).reverse(),
+            (Value::BigInteger(a), Value::Float(b)) => compare_floats(a.to_f64(), *b),
+            (Value::Float(a), Value::BigInteger(b)) => compare_floats(*a, b.to_f64()),
+            (Value::String(a), Value::String(b)) => a.cmp(b),
+            (Value::Array(a), Value::Array(b)) => {
+                let pair = (a.id(), b.id());
+                if a.ptr_eq(b) || comparing.contains(&pair) {
+                    return Ordering::Equal;
+                }
+                let (a, b) = (a.elements(), b.elements());
+                comparing.push(pair);
+                let ordering = a.iter().zip(b.iter())
+                    .map(|(x, y)| x.compare_with(y, comparing))
+                    .find(|ordering| *ordering != Ordering::Equal)
+                    .unwrap_or_else(|| a.len().cmp(&b.len()));
+                comparing.pop();
+                ordering
+            }
+            (Value::Object(a), Value::Object(b)) => {
+                let pair = (a.id(), b.id());
+                if a.ptr_eq(b) || comparing.contains(&pair) {
+                    return Ordering::Equal;
+                }
+                let (a, b) = (a.properties(), b.properties());
+                let mut a: Vec<_> = a.iter().collect();
+                let mut b: Vec<_> = b.iter().collect();
+                a.sort_by(|x, y| x.0.cmp(y.0));
+                b.sort_by(|x, y| x.0.cmp(y.0));
+                comparing.push(pair);
+                let ordering = a.iter().zip(b.iter())
+                    .map(|((key_a, x), (key_b, y))| key_a.cmp(key_b).then_with(|| x.compare_with(y, comparing)))
+                    .find(|ordering| *ordering != Ordering::Equal)
+                    .unwrap_or_else(|| a.len().cmp(&b.len()));
+                comparing.pop();
+                ordering
+            }
+            (Value::Function { name: a, .. }, Value::Function { name: b, .. }) => a.cmp(b),
+            (Value::BytecodeFunction { address: a, .. }, Value::BytecodeFunction { address: b, .. }) => a.cmp(b),
+            // Other functions have nothing to tell them apart by
+            _ => Ordering::Equal,
+        }
+    }
+    
+    /// The order used by `<`, `<=`, `>` and `>=`, which only compare numbers,
+    /// strings and arrays with others of their kind
+    pub fn partial_compare(&self, other: &Value) -> Option<Ordering> {
+        let orderable = matches!(
+            self,
+            Value::Integer(_) | Value::BigInteger(_) | Value::Float(_) | Value::String(_) | Value::Array(_)
+        );
+        if orderable && self.kind_rank() == other.kind_rank() {
+            Some(self.compare(other))
+        } else {
+            None
+        }
+    }
+    
+    /// Position of the value's kind in `compare`'s order
+    fn kind_rank(&self) -> u8 {
+        match self {
+            Value::Null => 0,
+            Value::Boolean(_) => 1,
+            Value::Integer(_) | Value::BigInteger(_) | Value::Float(_) => 2,
+            Value::String(_) => 3,
+            Value::Array(_) => 4,
+            Value::Object(_) => 5,
+            Value::Function { .. } | Value::Lambda { .. } | Value::BytecodeFunction { .. } => 6,
+        }
+    }
+    
+    /// A copy that shares nothing with the original: arrays and objects are
+    /// copied all the way down instead of being referenced. An array or
+    /// object reached twice is copied once, so cycles are kept as cycles.
+    pub fn deep_copy(&self) -> Value {
+        self.deep_copy_with(&mut HashMap::new())
+    }
 
-impl fmt::Display for Value {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    fn deep_copy_with(&self, copies: &mut HashMap<*const (), Value>) -> Value {
+        match self {
+            Value::Array(array) => {
+                if let Some(copy) = copies.get(&array.id()) {
+                    return copy.clone();
+                }
+                let copy = FlowArray::with_capacity(array.len());
+                copies.insert(array.id(), Value::Array(copy.clone()));
+                for element in array.to_vec() {
+                    copy.push(element.deep_copy_with(copies));
+                }
+                Value::Array(copy)
+            }
+            Value::Object(object) => {
+                if let Some(copy) = copies.get(&object.id()) {
+                    return copy.clone();
+                }
+                let copy = FlowObject::with_capacity(object.len());
+                copies.insert(object.id(), Value::Object(copy.clone()));
+                for (key, value) in object.to_map() {
+                    copy.set(key, value.deep_copy_with(copies));
+                }
+                Value::Object(copy)
+            }
+            other => other.clone(),
+        }
+    }
+
+    /// Write the value as `Display` does. `enclosing` holds the arrays and
+    /// objects being written around it; one that contains itself is written
+    /// as `[...]` or `{...}` where it reappears.
+    fn write_display(&self, f: &mut fmt::Formatter<'_>, enclosing: &mut Vec<*const ()>) -> fmt::Result {
         match self {
             Value::Integer(i) => write!(f, "{}", i),
             Value::BigInteger(bi) => write!(f, "{}", bi),
@@ -313,64 +651,118 @@ impl fmt::Display for Value {
             Value::Boolean(b) => write!(f, "{}", b),
             Value::Null => write!(f, "null"),
             Value::Array(arr) => {
+                if enclosing.contains(&arr.id()) {
+                    return write!(f, "[...]");
+                }
+                enclosing.push(arr.id());
                 write!(f, "[")?;
-                for (i, elem) in arr.elements.iter().enumerate() {
+                for (i, elem) in arr.elements().iter().enumerate() {
                     if i > 0 {
                         write!(f, ", ")?;
                     }
-                    write!(f, "{}", elem)?;
+                    elem.write_display(f, enclosing)?;
                 }
-                write!(f, "]")?;
-        Ok(())
+                enclosing.pop();
+                write!(f, "]")
             }
             Value::Object(obj) => {
+                if enclosing.contains(&obj.id()) {
+                    return write!(f, "{{...}}");
+                }
+                enclosing.push(obj.id());
                 write!(f, "{{")?;
-                let mut first = true;
-                for (key, value) in &obj.properties {
-                    if !first {
+                for (i, (key, value)) in obj.properties().iter().enumerate() {
+                    if i > 0 {
                         write!(f, ", ")?;
                     }
-                    write!(f, "\"{}\": {}", key, value)?;
-                    first = false;
+                    write!(f, "\"{}\": ", key)?;
+                    value.write_display(f, enclosing)?;
                 }
-                write!(f, "}}");
-                Ok(())
+                enclosing.pop();
+                write!(f, "}}")
             }
             Value::Function { name, .. } => write!(f, "<function {}>", name),
             Value::Lambda { .. } => write!(f, "<lambda>"),
             Value::BytecodeFunction { .. } => write!(f, "<bytecode function>"),
         }
     }
+    
+    /// Entries visited by a `for ... in` loop as `(key, item)` pairs, or `None`
+    /// if the value is not iterable.
+    ///
+    /// Arrays yield `(index, element)`, strings yield `(index, character)` and
+    /// objects yield `(key, value)` in sorted key order.
+    pub fn iteration_entries(&self) -> Option<Vec<(Value, Value)>> {
+        match self {
+            Value::Array(array) => Some(
+                array.elements().iter().enumerate()
+                    .map(|(i, element)| (Value::Integer(i as i64), element.clone()))
+                    .collect()
+            ),
+            Value::String(s) => Some(
+                s.chars().enumerate()
+                    .map(|(i, c)| (Value::Integer(i as i64), Value::String(c.to_string())))
+                    .collect()
+            ),
+            Value::Object(object) => {
+                let mut keys = object.keys();
+                keys.sort();
+                Some(
+                    keys.into_iter()
+                        .map(|key| {
+                            let value = object.get(&key).unwrap_or(Value::Null);
+                            (Value::String(key), value)
+                        })
+                        .collect()
+                )
+            }
+            _ => None,
+        }
+    }
+    
+    /// Items bound by a single-variable `for ... in` loop: the keys of an
+    /// object, and the elements of anything else that is iterable.
+    pub fn iteration_items(&self) -> Option<Vec<Value>> {
+        let entries = self.iteration_entries()?;
+        Some(match self {
+            Value::Object(_) => entries.into_iter().map(|(key, _)| key).collect(),
+            _ => entries.into_iter().map(|(_, item)| item).collect(),
+        })
+    }
+}
 
+/// Floats in numeric order, with NaN equal to itself and after every number
+fn compare_floats(a: f64, b: f64) -> Ordering {
+    a.partial_cmp(&b).unwrap_or_else(|| a.is_nan().cmp(&b.is_nan()))
+}
 
+/// Compare exactly, even where converting the integer to a float would round it
+fn compare_integer_to_float(a: i64, b: f64) -> Ordering {
+    // 2^63, the first float past i64::MAX
+    const LIMIT: f64 = 9_223_372_036_854_775_808.0;
+    match (a as f64).partial_cmp(&b) {
+        None => Ordering::Less,
+        // Equal as floats, so `b` is a whole number no smaller than i64::MIN
+        Some(Ordering::Equal) if b >= LIMIT => Ordering::Less,
+        Some(Ordering::Equal) => a.cmp(&(b as i64)),
+        Some(ordering) => ordering,
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write_display(f, &mut Vec::new())
+    }
 }
 
 impl fmt::Display for FlowArray {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "[")?;
-        for (i, elem) in self.elements.iter().enumerate() {
-            if i > 0 {
-                write!(f, ", ")?;
-            }
-            write!(f, "{}", elem)?;
-        }
-        write!(f, "]")?;
-        Ok(())
+        Value::Array(self.clone()).fmt(f)
     }
 }
 
 impl fmt::Display for FlowObject {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{{")?;
-        let mut first = true;
-        for (key, value) in &self.properties {
-            if !first {
-                write!(f, ", ")?;
-            }
-            write!(f, "\"{}\": {}", key, value)?;
-            first = false;
-        }
-        write!(f, "}}");
-        Ok(())
+        Value::Object(self.clone()).fmt(f)
     }
-}
\ No newline at end of file
+}
@@ -1,8 +1,13 @@
-use flowlang::lexer::Lexer;
+use flowlang::lexer::{Lexer, Token};
 use flowlang::parser::Parser;
 use flowlang::interpreter::Interpreter;
 use flowlang::ast::*;
 use flowlang::error::FlowError;
+use flowlang::value::Value;
+use flowlang::compiler::compile_program;
+use flowlang::bytecode::{Chunk, Instruction, VirtualMachine};
+use flowlang::diagnostic::{closest_match, Diagnostic};
+use flowlang::error_codes::{explain, EXPLANATIONS};
 
 #[test]
 fn test_basic_arithmetic() {
@@ -185,6 +190,55 @@ fn test_undefined_variable_error() {
     }
 }
 
+#[test]
+fn test_undefined_function_error() {
+    let source = r#"
+        let result be missing_function(5)
+    "#;
+    
+    let mut lexer = Lexer::new(source);
+    let tokens = lexer.tokenize().expect("Lexing failed");
+    
+    let mut parser = Parser::new(tokens);
+    let ast = parser.parse().expect("Parsing failed");
+    
+    let mut interpreter = Interpreter::new();
+    let error = interpreter.execute(&ast).unwrap_err();
+    assert_eq!((error.kind(), error.code()), ("UndefinedFunction".to_string(), "E0006"));
+    
+    let chunk = compile_program(&ast.statements).expect("Compilation failed");
+    let mut vm = VirtualMachine::new();
+    vm.load_chunk(chunk);
+    let error = vm.run().unwrap_err();
+    assert_eq!((error.kind(), error.code()), ("UndefinedFunction".to_string(), "E0006"));
+}
+
+#[test]
+fn test_property_access_on_non_object_error() {
+    let source = r#"
+        let n be 5
+        let result be n.x
+    "#;
+    
+    let mut lexer = Lexer::new(source);
+    let tokens = lexer.tokenize().expect("Lexing failed");
+    
+    let mut parser = Parser::new(tokens);
+    let ast = parser.parse().expect("Parsing failed");
+    
+    let mut interpreter = Interpreter::new();
+    let error = interpreter.execute(&ast).unwrap_err();
+    assert_eq!((error.kind(), error.code()), ("TypeError".to_string(), "E0004"));
+    assert!(error.to_string().contains("Cannot access property 'x' on integer"), "unexpected error: {}", error);
+    
+    let chunk = compile_program(&ast.statements).expect("Compilation failed");
+    let mut vm = VirtualMachine::new();
+    vm.load_chunk(chunk);
+    let vm_error = vm.run().unwrap_err();
+    assert_eq!((vm_error.kind(), vm_error.code()), ("TypeError".to_string(), "E0004"));
+    assert_eq!(vm_error.to_string(), error.to_string());
+}
+
 #[test]
 fn test_division_by_zero_error() {
     let source = r#"
@@ -205,4 +259,2221 @@ fn test_division_by_zero_error() {
         FlowError::DivisionByZero => {},
         _ => panic!("Expected DivisionByZero error"),
     }
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_reassignment_statements() {
+    let source = r#"
+        let count be 1
+        count = count + 1
+        count += 10
+        let items be [1, 2, 3]
+        items[0] = 5
+        items[2] *= 4
+        let user be { name: "Ada", address: { city: "Paris" } }
+        user.name = "Grace"
+        user.address.city = "London"
+    "#;
+    
+    let mut lexer = Lexer::new(source);
+    let tokens = lexer.tokenize().expect("Lexing failed");
+    
+    let mut parser = Parser::new(tokens);
+    let ast = parser.parse().expect("Parsing failed");
+    
+    let mut interpreter = Interpreter::new();
+    interpreter.execute(&ast).expect("Execution failed");
+    
+    let env = interpreter.get_environment_mut();
+    assert_eq!(env.get_variable("count"), Some(Value::Integer(12)));
+    assert_eq!(env.get_variable("items").map(|v| v.to_string()), Some("[5, 2, 12]".to_string()));
+    match env.get_variable("user") {
+        Some(Value::Object(user)) => {
+            assert_eq!(user.get("name"), Some(Value::String("Grace".to_string())));
+            assert_eq!(user.get("address").map(|v| v.to_string()), Some("{\"city\": London}".to_string()));
+        }
+        other => panic!("Expected object, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_assignment_to_undefined_variable_error() {
+    let source = r#"
+        missing = 5
+    "#;
+    
+    let mut lexer = Lexer::new(source);
+    let tokens = lexer.tokenize().expect("Lexing failed");
+    
+    let mut parser = Parser::new(tokens);
+    let ast = parser.parse().expect("Parsing failed");
+    
+    let mut interpreter = Interpreter::new();
+    match interpreter.execute(&ast).unwrap_err() {
+        FlowError::UndefinedVariable { name } => assert_eq!(name, "missing"),
+        other => panic!("Expected UndefinedVariable error, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_reassignment_in_vm() {
+    let source = r#"
+        let count be 1
+        count += 2
+        let items be [1, 2, 3]
+        items[1] = 20
+        let user be { name: "Ada" }
+        user.name = "Grace"
+    "#;
+    
+    let mut lexer = Lexer::new(source);
+    let tokens = lexer.tokenize().expect("Lexing failed");
+    
+    let mut parser = Parser::new(tokens);
+    let ast = parser.parse().expect("Parsing failed");
+    
+    let chunk = compile_program(&ast.statements).expect("Compilation failed");
+    let mut vm = VirtualMachine::new();
+    vm.load_chunk(chunk);
+    vm.run().expect("Execution failed");
+    
+    assert_eq!(vm.globals.get("count"), Some(&Value::Integer(3)));
+    assert_eq!(vm.globals.get("items").map(|v| v.to_string()), Some("[1, 20, 3]".to_string()));
+    assert_eq!(vm.globals.get("user").map(|v| v.to_string()), Some("{\"name\": Grace}".to_string()));
+}
+
+#[test]
+fn test_compound_assignment_evaluates_target_once() {
+    let source = r#"
+        let calls be 0
+        def next_index do
+            calls += 1
+            return calls - 1
+        end
+        let items be [10, 20, 30]
+        items[next_index()] += 5
+        let counter be {count: 1}
+        def lookup do
+            calls += 1
+            return counter
+        end
+        lookup().count *= 7
+    "#;
+    
+    let ast = Parser::new(Lexer::new(source).tokenize().expect("Lexing failed"))
+        .parse()
+        .expect("Parsing failed");
+    
+    let mut interpreter = Interpreter::new();
+    interpreter.execute(&ast).expect("Execution failed");
+    let env = interpreter.get_environment_mut();
+    assert_eq!(env.get_variable("calls"), Some(Value::Integer(2)));
+    assert_eq!(env.get_variable("items").map(|v| v.to_string()), Some("[15, 20, 30]".to_string()));
+    assert_eq!(env.get_variable("counter").map(|v| v.to_string()), Some("{\"count\": 7}".to_string()));
+    
+    let chunk = compile_program(&ast.statements).expect("Compilation failed");
+    let mut vm = VirtualMachine::new();
+    vm.load_chunk(chunk);
+    vm.run().expect("Execution failed");
+    assert_eq!(vm.globals.get("calls"), Some(&Value::Integer(2)));
+    assert_eq!(vm.globals.get("items").map(|v| v.to_string()), Some("[15, 20, 30]".to_string()));
+    assert_eq!(vm.globals.get("counter").map(|v| v.to_string()), Some("{\"count\": 7}".to_string()));
+}
+
+#[test]
+fn test_break_and_continue() {
+    let source = r#"
+        let total be 0
+        let i be 0
+        while true do
+            i += 1
+            if i > 10 then
+                break
+            end
+            if i % 2 == 0 then
+                continue
+            end
+            total += i
+        end
+        
+        let hits be 0
+        for j from 1 to 20 do
+            if j == 3 then
+                continue
+            end
+            try
+                if j == 6 then
+                    break
+                end
+            catch err
+                hits = -100
+            end
+            hits += 1
+        end
+    "#;
+    
+    let mut lexer = Lexer::new(source);
+    let tokens = lexer.tokenize().expect("Lexing failed");
+    
+    let mut parser = Parser::new(tokens);
+    let ast = parser.parse().expect("Parsing failed");
+    
+    let mut interpreter = Interpreter::new();
+    interpreter.execute(&ast).expect("Execution failed");
+    
+    let env = interpreter.get_environment_mut();
+    assert_eq!(env.get_variable("total"), Some(Value::Integer(25)));
+    assert_eq!(env.get_variable("hits"), Some(Value::Integer(4)));
+}
+
+#[test]
+fn test_examples_parse() {
+    // Drafts written in syntax the parser has never accepted, such as `//`
+    // comments, `elif` and calls without parentheses
+    const DRAFTS: &[&str] = &[
+        "comprehensive_test.flow",
+        "debug_import3.flow",
+        "simple_lambda_test.flow",
+        "simple_test.flow",
+        "specific_import_test.flow",
+        "test_array_with_lambdas.flow",
+        "test_bigint.flow",
+        "test_function_calls.flow",
+        "test_http_section.flow",
+        "test_line_by_line.flow",
+        "test_minimal.flow",
+        "test_simple_stdlib.flow",
+        "test_stdlib_basic.flow",
+        "test_stdlib_debug.flow",
+        "weather_app_with_http_module.flow",
+    ];
+    
+    let examples = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("examples");
+    let mut failures = Vec::new();
+    for entry in std::fs::read_dir(&examples).expect("Reading examples failed") {
+        let path = entry.expect("Reading examples failed").path();
+        let is_draft = path.file_name().is_some_and(|name| DRAFTS.iter().any(|draft| name == *draft));
+        if is_draft || path.extension().map_or(true, |extension| extension != "flow") {
+            continue;
+        }
+        let source = std::fs::read_to_string(&path).expect("Reading example failed");
+        let result = Lexer::new(&source).tokenize().and_then(|tokens| Parser::new(tokens).parse());
+        if let Err(error) = result {
+            failures.push(format!("{}: {}", path.display(), error));
+        }
+    }
+    assert!(failures.is_empty(), "Examples that don't parse:\n{}", failures.join("\n"));
+}
+
+#[test]
+fn test_break_outside_loop_error() {
+    let source = r#"
+        def helper do
+            break
+        end
+    "#;
+    
+    let mut lexer = Lexer::new(source);
+    let tokens = lexer.tokenize().expect("Lexing failed");
+    
+    let mut parser = Parser::new(tokens);
+    match parser.parse().unwrap_err() {
+        FlowError::ParserError { line, .. } => assert_eq!(line, 3),
+        other => panic!("Expected ParserError, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_break_and_continue_in_vm() {
+    let source = r#"
+        let total be 0
+        let i be 0
+        while true do
+            i += 1
+            if i > 10 then
+                break
+            end
+            if i % 2 == 0 then
+                continue
+            end
+            total += i
+        end
+    "#;
+    
+    let mut lexer = Lexer::new(source);
+    let tokens = lexer.tokenize().expect("Lexing failed");
+    
+    let mut parser = Parser::new(tokens);
+    let ast = parser.parse().expect("Parsing failed");
+    
+    let chunk = compile_program(&ast.statements).expect("Compilation failed");
+    let mut vm = VirtualMachine::new();
+    vm.load_chunk(chunk);
+    vm.run().expect("Execution failed");
+    
+    assert_eq!(vm.globals.get("total"), Some(&Value::Integer(25)));
+}
+
+#[test]
+fn test_for_in_loops() {
+    let source = r#"
+        let total be 0
+        for n in [1, 2, 3, 4] do
+            if n == 4 then
+                break
+            end
+            total += n
+        end
+        
+        let pairs be ""
+        for key, value in { b: 2, a: 1 } do
+            pairs = pairs + key + "=" + value + ";"
+        end
+        
+        let keys be ""
+        for key in { b: 2, a: 1 } do
+            keys = keys + key
+        end
+        
+        let letters be ""
+        for i, c in "flow" do
+            if i == 1 then
+                continue
+            end
+            letters = letters + c
+        end
+        
+        let squares be 0
+        for i in 1 to 3 do
+            squares += i * i
+        end
+    "#;
+    
+    let mut lexer = Lexer::new(source);
+    let tokens = lexer.tokenize().expect("Lexing failed");
+    
+    let mut parser = Parser::new(tokens);
+    let ast = parser.parse().expect("Parsing failed");
+    
+    let mut interpreter = Interpreter::new();
+    interpreter.execute(&ast).expect("Execution failed");
+    
+    let env = interpreter.get_environment_mut();
+    assert_eq!(env.get_variable("total"), Some(Value::Integer(6)));
+    assert_eq!(env.get_variable("pairs"), Some(Value::String("a=1;b=2;".to_string())));
+    assert_eq!(env.get_variable("keys"), Some(Value::String("ab".to_string())));
+    assert_eq!(env.get_variable("letters"), Some(Value::String("fow".to_string())));
+    assert_eq!(env.get_variable("squares"), Some(Value::Integer(14)));
+}
+
+#[test]
+fn test_for_in_non_iterable_error() {
+    let source = r#"
+        for x in 42 do
+            show x
+        end
+    "#;
+    
+    let mut lexer = Lexer::new(source);
+    let tokens = lexer.tokenize().expect("Lexing failed");
+    
+    let mut parser = Parser::new(tokens);
+    let ast = parser.parse().expect("Parsing failed");
+    
+    let mut interpreter = Interpreter::new();
+    match interpreter.execute(&ast).unwrap_err() {
+        FlowError::TypeError { message } => assert!(message.contains("integer")),
+        other => panic!("Expected TypeError, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_for_in_loops_in_vm() {
+    let source = r#"
+        let total be 0
+        for n in [1, 2, 3, 4] do
+            if n == 4 then
+                break
+            end
+            total += n
+        end
+        
+        let pairs be ""
+        for key, value in { b: 2, a: 1 } do
+            pairs = pairs + key + "=" + value + ";"
+        end
+        
+        let letters be ""
+        for i, c in "flow" do
+            if i == 1 then
+                continue
+            end
+            letters = letters + c
+        end
+    "#;
+    
+    let mut lexer = Lexer::new(source);
+    let tokens = lexer.tokenize().expect("Lexing failed");
+    
+    let mut parser = Parser::new(tokens);
+    let ast = parser.parse().expect("Parsing failed");
+    
+    let chunk = compile_program(&ast.statements).expect("Compilation failed");
+    let mut vm = VirtualMachine::new();
+    vm.load_chunk(chunk);
+    vm.run().expect("Execution failed");
+    
+    assert_eq!(vm.globals.get("total"), Some(&Value::Integer(6)));
+    assert_eq!(vm.globals.get("pairs"), Some(&Value::String("a=1;b=2;".to_string())));
+    assert_eq!(vm.globals.get("letters"), Some(&Value::String("fow".to_string())));
+}
+
+#[test]
+fn test_stepped_for_loops() {
+    let source = r#"
+        let down be ""
+        for i from 10 to 1 by -3 do
+            down = down + i + ","
+        end
+        
+        let odds be 0
+        for i in 1 to 9 by 2 do
+            odds += i
+        end
+        
+        let countdown be ""
+        for i from 3 to 1 do
+            countdown = countdown + i + ","
+        end
+    "#;
+    
+    let mut lexer = Lexer::new(source);
+    let tokens = lexer.tokenize().expect("Lexing failed");
+    
+    let mut parser = Parser::new(tokens);
+    let ast = parser.parse().expect("Parsing failed");
+    
+    let mut interpreter = Interpreter::new();
+    interpreter.execute(&ast).expect("Execution failed");
+    
+    let env = interpreter.get_environment_mut();
+    assert_eq!(env.get_variable("down"), Some(Value::String("10,7,4,1,".to_string())));
+    assert_eq!(env.get_variable("odds"), Some(Value::Integer(25)));
+    assert_eq!(env.get_variable("countdown"), Some(Value::String("3,2,1,".to_string())));
+}
+
+#[test]
+fn test_invalid_for_range_errors() {
+    let cases = [
+        ("for i from 1 to 5 by 0 do\n    show i\nend\n", "step cannot be zero"),
+        ("for i from 1.5 to 5 do\n    show i\nend\n", "bounds must be integers"),
+    ];
+    
+    for (source, expected) in cases {
+        let mut lexer = Lexer::new(source);
+        let tokens = lexer.tokenize().expect("Lexing failed");
+        
+        let mut parser = Parser::new(tokens);
+        let ast = parser.parse().expect("Parsing failed");
+        
+        let mut interpreter = Interpreter::new();
+        let error = interpreter.execute(&ast).unwrap_err();
+        assert!(error.to_string().contains(expected), "unexpected error: {}", error);
+        
+        let chunk = compile_program(&ast.statements).expect("Compilation failed");
+        let mut vm = VirtualMachine::new();
+        vm.load_chunk(chunk);
+        let error = vm.run().unwrap_err();
+        assert!(error.to_string().contains(expected), "unexpected VM error: {}", error);
+    }
+}
+
+#[test]
+fn test_stepped_for_loops_in_vm() {
+    let source = r#"
+        let down be ""
+        for i from 10 to 1 by -3 do
+            down = down + i + ","
+        end
+        
+        let total be 0
+        for i from 1 to 5 do
+            total += i
+        end
+        
+        let countdown be ""
+        for i from 3 to 1 do
+            countdown = countdown + i + ","
+        end
+    "#;
+    
+    let mut lexer = Lexer::new(source);
+    let tokens = lexer.tokenize().expect("Lexing failed");
+    
+    let mut parser = Parser::new(tokens);
+    let ast = parser.parse().expect("Parsing failed");
+    
+    let chunk = compile_program(&ast.statements).expect("Compilation failed");
+    let mut vm = VirtualMachine::new();
+    vm.load_chunk(chunk);
+    vm.run().expect("Execution failed");
+    
+    assert_eq!(vm.globals.get("down"), Some(&Value::String("10,7,4,1,".to_string())));
+    assert_eq!(vm.globals.get("total"), Some(&Value::Integer(15)));
+    assert_eq!(vm.globals.get("countdown"), Some(&Value::String("3,2,1,".to_string())));
+}
+
+#[test]
+fn test_string_interpolation() {
+    let source = r#"
+        let name be "Ada"
+        let count be 2
+        let greeting be "Hello, {name}, you have {count + 1} items"
+        let escaped be "\{name\} is {str_upper(name)}"
+        let nested be "{"[" + name + "]"}"
+    "#;
+    
+    let mut lexer = Lexer::new(source);
+    let tokens = lexer.tokenize().expect("Lexing failed");
+    
+    let mut parser = Parser::new(tokens);
+    let ast = parser.parse().expect("Parsing failed");
+    
+    let mut interpreter = Interpreter::new();
+    interpreter.execute(&ast).expect("Execution failed");
+    
+    let env = interpreter.get_environment_mut();
+    assert_eq!(env.get_variable("greeting"), Some(Value::String("Hello, Ada, you have 3 items".to_string())));
+    assert_eq!(env.get_variable("escaped"), Some(Value::String("{name} is ADA".to_string())));
+    assert_eq!(env.get_variable("nested"), Some(Value::String("[Ada]".to_string())));
+    
+    let chunk = compile_program(&ast.statements[..3]).expect("Compilation failed");
+    let mut vm = VirtualMachine::new();
+    vm.load_chunk(chunk);
+    vm.run().expect("Execution failed");
+    
+    assert_eq!(vm.globals.get("greeting"), Some(&Value::String("Hello, Ada, you have 3 items".to_string())));
+}
+
+#[test]
+fn test_unclosed_interpolation_error() {
+    let source = "let x be 1\nshow \"value: {x + 1\"\n";
+    
+    let mut lexer = Lexer::new(source);
+    match lexer.tokenize().unwrap_err() {
+        FlowError::LexerError { line, column, .. } => {
+            assert_eq!(line, 2);
+            assert_eq!(column, 14);
+        }
+        other => panic!("Expected LexerError, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_multiline_raw_and_escaped_strings() {
+    let source = r#"
+        let name be "Ada"
+        let letter be """
+            Dear {name},
+              thanks!
+            """
+        let path be r"C:\flow\{name}"
+        let json be r"""
+            {"ok": true}
+            """
+        let escapes be "\u{1F600}\x41\0"
+    "#;
+    
+    let mut lexer = Lexer::new(source);
+    let tokens = lexer.tokenize().expect("Lexing failed");
+    
+    let mut parser = Parser::new(tokens);
+    let ast = parser.parse().expect("Parsing failed");
+    
+    let mut interpreter = Interpreter::new();
+    interpreter.execute(&ast).expect("Execution failed");
+    
+    let env = interpreter.get_environment_mut();
+    assert_eq!(env.get_variable("letter"), Some(Value::String("Dear Ada,\n  thanks!".to_string())));
+    assert_eq!(env.get_variable("path"), Some(Value::String("C:\\flow\\{name}".to_string())));
+    assert_eq!(env.get_variable("json"), Some(Value::String("{\"ok\": true}".to_string())));
+    assert_eq!(env.get_variable("escapes"), Some(Value::String("\u{1F600}A\0".to_string())));
+}
+
+#[test]
+fn test_positions_after_multiline_strings() {
+    let source = "let a be \"\"\"\n  one\n  two\n  \"\"\"\nlet b be \"x\ny\" + \"\\q\"\n";
+    
+    let mut lexer = Lexer::new(source);
+    match lexer.tokenize().unwrap_err() {
+        FlowError::LexerError { line, column, .. } => {
+            assert_eq!(line, 6);
+            assert_eq!(column, 7);
+        }
+        other => panic!("Expected LexerError, got {:?}", other),
+    }
+    
+    let mut lexer = Lexer::new("let a be \"\"\"\n  one\n  \"\"\"\nshow a\n");
+    let tokens = lexer.tokenize().expect("Lexing failed");
+    let show = tokens.iter().find(|t| t.token == Token::Show).expect("Missing show token");
+    assert_eq!((show.line, show.column), (4, 1));
+}
+
+#[test]
+fn test_numeric_literal_forms() {
+    let source = r#"
+        let hex be 0xFF
+        let bin be 0b1010
+        let oct be 0o17
+        let million be 1_000_000
+        let tiny be 1e-9
+        let sci be 2.5E3
+        let big_hex be 0x1_0000_0000_0000_0000
+    "#;
+    
+    let mut lexer = Lexer::new(source);
+    let tokens = lexer.tokenize().expect("Lexing failed");
+    
+    let mut parser = Parser::new(tokens);
+    let ast = parser.parse().expect("Parsing failed");
+    
+    let mut interpreter = Interpreter::new();
+    interpreter.execute(&ast).expect("Execution failed");
+    
+    let env = interpreter.get_environment_mut();
+    assert_eq!(env.get_variable("hex"), Some(Value::Integer(255)));
+    assert_eq!(env.get_variable("bin"), Some(Value::Integer(10)));
+    assert_eq!(env.get_variable("oct"), Some(Value::Integer(15)));
+    assert_eq!(env.get_variable("million"), Some(Value::Integer(1_000_000)));
+    assert_eq!(env.get_variable("tiny"), Some(Value::Float(1e-9)));
+    assert_eq!(env.get_variable("sci"), Some(Value::Float(2500.0)));
+    match env.get_variable("big_hex") {
+        Some(Value::BigInteger(big)) => assert_eq!(big.to_string(), "18446744073709551616"),
+        other => panic!("Expected BigInteger, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_invalid_numeric_literal_errors() {
+    let cases = [
+        ("let x be 0b102", 14),
+        ("let x be 1__000", 10),
+        ("let x be 0x", 10),
+    ];
+    
+    for (source, expected_column) in cases {
+        let mut lexer = Lexer::new(source);
+        match lexer.tokenize().unwrap_err() {
+            FlowError::LexerError { column, .. } => assert_eq!(column, expected_column, "{}", source),
+            other => panic!("Expected LexerError, got {:?}", other),
+        }
+    }
+}
+
+#[test]
+fn test_block_lambdas_and_nested_functions() {
+    let source = r#"
+        let classify be (n) => do
+            if n % 2 == 0 then
+                return "even"
+            end
+            return "odd"
+        end
+        let kinds be [1, 2, 3].map(classify)
+        let squares be [1, 2, 3].map((x) => do
+            let y be x * x
+            return y + 1
+        end)
+        
+        def make_greeter with greeting do
+            def greet with name do
+                return "{greeting}, {name}!"
+            end
+            return greet
+        end
+        let hello be make_greeter("Hello")
+        let message be hello("Ada")
+    "#;
+    
+    let mut lexer = Lexer::new(source);
+    let tokens = lexer.tokenize().expect("Lexing failed");
+    
+    let mut parser = Parser::new(tokens);
+    let ast = parser.parse().expect("Parsing failed");
+    
+    let mut interpreter = Interpreter::new();
+    interpreter.execute(&ast).expect("Execution failed");
+    
+    let env = interpreter.get_environment_mut();
+    assert_eq!(env.get_variable("kinds").map(|v| v.to_string()), Some("[odd, even, odd]".to_string()));
+    assert_eq!(env.get_variable("squares").map(|v| v.to_string()), Some("[2, 5, 10]".to_string()));
+    assert_eq!(env.get_variable("message"), Some(Value::String("Hello, Ada!".to_string())));
+}
+
+#[test]
+fn test_functions_and_lambdas_in_vm() {
+    let source = r#"
+        def add with a, b = 10 do
+            return a + b
+        end
+        
+        def fact with n do
+            if n <= 1 then
+                return 1
+            end
+            return n * fact(n - 1)
+        end
+        
+        let classify be (n) => do
+            if n % 2 == 0 then
+                let kind be "even"
+            else
+                let kind be "odd"
+            end
+            return kind
+        end
+        
+        let sum be add(1, 2)
+        let defaulted be add(5)
+        let factorial be fact(10)
+        let kind be classify(7)
+    "#;
+    
+    let mut lexer = Lexer::new(source);
+    let tokens = lexer.tokenize().expect("Lexing failed");
+    
+    let mut parser = Parser::new(tokens);
+    let ast = parser.parse().expect("Parsing failed");
+    
+    let chunk = compile_program(&ast.statements).expect("Compilation failed");
+    let mut vm = VirtualMachine::new();
+    vm.load_chunk(chunk);
+    vm.run().expect("Execution failed");
+    
+    assert_eq!(vm.globals.get("sum"), Some(&Value::Integer(3)));
+    assert_eq!(vm.globals.get("defaulted"), Some(&Value::Integer(15)));
+    assert_eq!(vm.globals.get("factorial"), Some(&Value::Integer(3628800)));
+    assert_eq!(vm.globals.get("kind"), Some(&Value::String("odd".to_string())));
+}
+
+#[test]
+fn test_too_many_arguments_error() {
+    let cases = [
+        "def add with a, b do\n    return a + b\nend\nadd(1, 2, 3)\n",
+        "let double be (x) => x * 2\ndouble(1, 2)\n",
+    ];
+    
+    for source in cases {
+        let mut lexer = Lexer::new(source);
+        let tokens = lexer.tokenize().expect("Lexing failed");
+        
+        let mut parser = Parser::new(tokens);
+        let ast = parser.parse().expect("Parsing failed");
+        
+        let mut interpreter = Interpreter::new();
+        let error = interpreter.execute(&ast).unwrap_err();
+        assert!(error.to_string().contains("Expected at most"), "unexpected error: {}", error);
+        assert_eq!(error.code(), "E0012");
+        
+        let chunk = compile_program(&ast.statements).expect("Compilation failed");
+        let mut vm = VirtualMachine::new();
+        vm.load_chunk(chunk);
+        let error = vm.run().unwrap_err();
+        assert!(error.to_string().contains("Expected at most"), "unexpected VM error: {}", error);
+        assert_eq!(error.code(), "E0012");
+    }
+}
+
+const CLOSURE_SOURCE: &str = r#"
+    def make_counter do
+        let count be 0
+        return () => do
+            count += 1
+            return count
+        end
+    end
+    
+    let counter be make_counter()
+    counter()
+    counter()
+    let third be counter()
+    let other be make_counter()
+    let fresh be other()
+    
+    def make_cell with value do
+        let get be () => value
+        let put be (v) => do
+            value = v
+        end
+        put(42)
+        return get
+    end
+    let cell be make_cell(1)
+    let stored be cell()
+    
+    def outer do
+        def fact with n do
+            if n <= 1 then
+                return 1
+            end
+            return n * fact(n - 1)
+        end
+        return fact(5)
+    end
+    let factorial be outer()
+    
+    let total be 0
+    def add_total with n do
+        total += n
+    end
+    add_total(5)
+    add_total(7)
+"#;
+
+#[test]
+fn test_closures_share_captured_variables() {
+    let mut lexer = Lexer::new(CLOSURE_SOURCE);
+    let tokens = lexer.tokenize().expect("Lexing failed");
+    
+    let mut parser = Parser::new(tokens);
+    let ast = parser.parse().expect("Parsing failed");
+    
+    let mut interpreter = Interpreter::new();
+    interpreter.execute(&ast).expect("Execution failed");
+    
+    let env = interpreter.get_environment_mut();
+    assert_eq!(env.get_variable("third"), Some(Value::Integer(3)));
+    assert_eq!(env.get_variable("fresh"), Some(Value::Integer(1)));
+    assert_eq!(env.get_variable("stored"), Some(Value::Integer(42)));
+    assert_eq!(env.get_variable("factorial"), Some(Value::Integer(120)));
+    assert_eq!(env.get_variable("total"), Some(Value::Integer(12)));
+}
+
+#[test]
+fn test_closures_share_captured_variables_in_vm() {
+    let mut lexer = Lexer::new(CLOSURE_SOURCE);
+    let tokens = lexer.tokenize().expect("Lexing failed");
+    
+    let mut parser = Parser::new(tokens);
+    let ast = parser.parse().expect("Parsing failed");
+    
+    let chunk = compile_program(&ast.statements).expect("Compilation failed");
+    let mut vm = VirtualMachine::new();
+    vm.load_chunk(chunk);
+    vm.run().expect("Execution failed");
+    
+    assert_eq!(vm.globals.get("third"), Some(&Value::Integer(3)));
+    assert_eq!(vm.globals.get("fresh"), Some(&Value::Integer(1)));
+    assert_eq!(vm.globals.get("stored"), Some(&Value::Integer(42)));
+    assert_eq!(vm.globals.get("factorial"), Some(&Value::Integer(120)));
+    assert_eq!(vm.globals.get("total"), Some(&Value::Integer(12)));
+}
+
+#[test]
+fn test_loop_iterations_get_their_own_variables() {
+    let source = r#"
+        let numbered be [null, null, null]
+        for i from 1 to 3 do
+            numbered[i - 1] = () => i
+        end
+        let first_number_closure be numbered[0]
+        let first_number be first_number_closure()
+        
+        let named be [null, null]
+        for index, name in ["p", "q"] do
+            named[index] = () => name
+        end
+        let first_name_closure be named[0]
+        let first_name be first_name_closure()
+        
+        let doubled be [null, null, null]
+        let n be 0
+        while n < 3 do
+            let twice be n * 2
+            doubled[n] = () => twice
+            n += 1
+        end
+        let first_double_closure be doubled[0]
+        let first_double be first_double_closure()
+        
+        def collect do
+            let found be [null, null]
+            for index, item in ["a", "b"] do
+                let label be item + "!"
+                found[index] = () => label
+            end
+            let first be found[0]
+            return first()
+        end
+        let first_label be collect()
+        
+        let total be 0
+        for i from 1 to 4 do
+            let total be total + i
+        end
+    "#;
+    
+    let mut lexer = Lexer::new(source);
+    let tokens = lexer.tokenize().expect("Lexing failed");
+    
+    let mut parser = Parser::new(tokens);
+    let ast = parser.parse().expect("Parsing failed");
+    
+    let mut interpreter = Interpreter::new();
+    interpreter.execute(&ast).expect("Execution failed");
+    let env = interpreter.get_environment_mut();
+    assert_eq!(env.get_variable("first_number"), Some(Value::Integer(1)));
+    assert_eq!(env.get_variable("first_name"), Some(Value::String("p".to_string())));
+    assert_eq!(env.get_variable("first_double"), Some(Value::Integer(0)));
+    assert_eq!(env.get_variable("first_label"), Some(Value::String("a!".to_string())));
+    assert_eq!(env.get_variable("total"), Some(Value::Integer(10)));
+    
+    let chunk = compile_program(&ast.statements).expect("Compilation failed");
+    let mut vm = VirtualMachine::new();
+    vm.load_chunk(chunk);
+    vm.run().expect("Execution failed");
+    assert_eq!(vm.globals.get("first_number"), Some(&Value::Integer(1)));
+    assert_eq!(vm.globals.get("first_name"), Some(&Value::String("p".to_string())));
+    assert_eq!(vm.globals.get("first_double"), Some(&Value::Integer(0)));
+    assert_eq!(vm.globals.get("first_label"), Some(&Value::String("a!".to_string())));
+    assert_eq!(vm.globals.get("total"), Some(&Value::Integer(10)));
+}
+
+#[test]
+fn test_arrays_and_objects_are_shared_references() {
+    let source = r#"
+        let list be [1, 2, 3]
+        list.push(4)
+        let alias be list
+        alias[0] = 100
+        let popped be alias.pop()
+        
+        def add_item with items, item do
+            items.push(item)
+        end
+        add_item(list, 9)
+        
+        let snapshot be list.copy()
+        snapshot.push(5)
+        
+        let config be {name: "app", tags: ["a"]}
+        let shallow be config.copy()
+        let deep be config.deep_copy()
+        shallow.name = "copy"
+        shallow.tags.push("b")
+        deep.tags.push("c")
+    "#;
+    
+    let mut lexer = Lexer::new(source);
+    let tokens = lexer.tokenize().expect("Lexing failed");
+    
+    let mut parser = Parser::new(tokens);
+    let ast = parser.parse().expect("Parsing failed");
+    
+    let mut interpreter = Interpreter::new();
+    interpreter.execute(&ast).expect("Execution failed");
+    
+    let env = interpreter.get_environment_mut();
+    assert_eq!(env.get_variable("list").map(|v| v.to_string()), Some("[100, 2, 3, 9]".to_string()));
+    assert_eq!(env.get_variable("popped"), Some(Value::Integer(4)));
+    assert_eq!(env.get_variable("snapshot").map(|v| v.to_string()), Some("[100, 2, 3, 9, 5]".to_string()));
+    match env.get_variable("config") {
+        Some(Value::Object(config)) => {
+            assert_eq!(config.get("name"), Some(Value::String("app".to_string())));
+            assert_eq!(config.get("tags").map(|v| v.to_string()), Some("[a, b]".to_string()));
+        }
+        other => panic!("Expected object, got {:?}", other),
+    }
+    match env.get_variable("deep") {
+        Some(Value::Object(deep)) => {
+            assert_eq!(deep.get("tags").map(|v| v.to_string()), Some("[a, c]".to_string()));
+        }
+        other => panic!("Expected object, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_self_referential_values() {
+    let source = r#"
+        let items be [1, 2]
+        items.push(items)
+        let node be {}
+        node.next = node
+        
+        let copy be items.deep_copy()
+        copy[0] = 9
+        let node_copy be node.deep_copy()
+    "#;
+    
+    let mut lexer = Lexer::new(source);
+    let tokens = lexer.tokenize().expect("Lexing failed");
+    
+    let mut parser = Parser::new(tokens);
+    let ast = parser.parse().expect("Parsing failed");
+    
+    let mut interpreter = Interpreter::new();
+    interpreter.execute(&ast).expect("Execution failed");
+    
+    let env = interpreter.get_environment_mut();
+    assert_eq!(env.get_variable("items").map(|v| v.to_string()), Some("[1, 2, [...]]".to_string()));
+    assert_eq!(env.get_variable("node").map(|v| v.to_string()), Some("{\"next\": {...}}".to_string()));
+    
+    // The copies keep their cycles, pointing at themselves rather than the originals
+    match env.get_variable("copy") {
+        Some(Value::Array(copy)) => {
+            assert_eq!(Value::Array(copy.clone()).to_string(), "[9, 2, [...]]");
+            assert!(matches!(copy.get(2), Some(Value::Array(inner)) if inner.ptr_eq(&copy)));
+        }
+        other => panic!("Expected array, got {:?}", other.map(|v| v.to_string())),
+    }
+    match env.get_variable("node_copy") {
+        Some(Value::Object(copy)) => {
+            assert!(matches!(copy.get("next"), Some(Value::Object(inner)) if inner.ptr_eq(&copy)));
+        }
+        other => panic!("Expected object, got {:?}", other.map(|v| v.to_string())),
+    }
+}
+
+#[test]
+fn test_reverse_and_sort_change_the_array() {
+    let source = r#"
+        let numbers be [3, 1, 2]
+        let alias be numbers
+        numbers.sort()
+        let letters be ["a", "b", "c"]
+        let reversed be letters.reverse()
+    "#;
+    
+    let mut lexer = Lexer::new(source);
+    let tokens = lexer.tokenize().expect("Lexing failed");
+    
+    let mut parser = Parser::new(tokens);
+    let ast = parser.parse().expect("Parsing failed");
+    
+    let mut interpreter = Interpreter::new();
+    interpreter.execute(&ast).expect("Execution failed");
+    
+    let env = interpreter.get_environment_mut();
+    assert_eq!(env.get_variable("alias").map(|v| v.to_string()), Some("[1, 2, 3]".to_string()));
+    assert_eq!(env.get_variable("letters").map(|v| v.to_string()), Some("[c, b, a]".to_string()));
+    match (env.get_variable("letters"), env.get_variable("reversed")) {
+        (Some(Value::Array(letters)), Some(Value::Array(reversed))) => assert!(letters.ptr_eq(&reversed)),
+        other => panic!("Expected arrays, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_shared_references_in_vm() {
+    let source = r#"
+        let list be [1, 2, 3]
+        let alias be list
+        alias[0] = 100
+        
+        let grid be [[0, 0], [0, 0]]
+        let row be grid[1]
+        row[0] = 7
+        
+        let person be {name: "Ada", address: {city: "Paris"}}
+        def move with p, city do
+            p.address.city = city
+        end
+        move(person, "London")
+        
+        let first be list[0]
+        let cell be grid[1][0]
+        let city be person.address.city
+    "#;
+    
+    let mut lexer = Lexer::new(source);
+    let tokens = lexer.tokenize().expect("Lexing failed");
+    
+    let mut parser = Parser::new(tokens);
+    let ast = parser.parse().expect("Parsing failed");
+    
+    let chunk = compile_program(&ast.statements).expect("Compilation failed");
+    let mut vm = VirtualMachine::new();
+    vm.load_chunk(chunk);
+    vm.run().expect("Execution failed");
+    
+    assert_eq!(vm.globals.get("first"), Some(&Value::Integer(100)));
+    assert_eq!(vm.globals.get("cell"), Some(&Value::Integer(7)));
+    assert_eq!(vm.globals.get("city"), Some(&Value::String("London".to_string())));
+}
+
+/// Write a set of module files into a fresh temporary directory
+fn write_module_files(test_name: &str, files: &[(&str, &str)]) -> std::path::PathBuf {
+    let dir = std::env::temp_dir().join(format!("flowlang_{}_{}", test_name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    for (name, source) in files {
+        let path = dir.join(name);
+        std::fs::create_dir_all(path.parent().unwrap()).expect("Failed to create module directory");
+        std::fs::write(path, source).expect("Failed to write module");
+    }
+    dir
+}
+
+#[test]
+fn test_file_modules_expose_only_exports() {
+    let dir = write_module_files("file_modules", &[
+        ("lib/helpers.flow", r#"
+export def twice with x do
+    return x * 2
+end
+"#),
+        ("lib/utils.flow", r#"
+import { twice } from "./helpers.flow"
+let secret be 41
+export def answer do
+    return secret + 1
+end
+export let quadruple be (x) => twice(twice(x))
+"#),
+        ("main.flow", r#"
+import utils from "./lib/utils.flow"
+import { answer as get_answer } from "./lib/utils.flow"
+import "lib/utils.flow" as again
+let from_method be utils.answer()
+let from_alias be get_answer()
+let from_lambda be again.quadruple(5)
+let hidden be utils.secret
+"#),
+    ]);
+    
+    let main_path = dir.join("main.flow");
+    let source = std::fs::read_to_string(&main_path).unwrap();
+    let mut lexer = Lexer::new(&source);
+    let tokens = lexer.tokenize().expect("Lexing failed");
+    
+    let mut parser = Parser::new(tokens);
+    let ast = parser.parse().expect("Parsing failed");
+    
+    let mut interpreter = Interpreter::new();
+    interpreter.set_script_path(&main_path);
+    interpreter.execute(&ast).expect("Execution failed");
+    
+    let env = interpreter.get_environment_mut();
+    assert_eq!(env.get_variable("from_method"), Some(Value::Integer(42)));
+    assert_eq!(env.get_variable("from_alias"), Some(Value::Integer(42)));
+    assert_eq!(env.get_variable("from_lambda"), Some(Value::Integer(20)));
+    assert_eq!(env.get_variable("hidden"), Some(Value::Null));
+    assert_eq!(env.get_variable("secret"), None);
+    
+    // Both imports of utils.flow see the same cached module
+    match (env.get_variable("utils"), env.get_variable("again")) {
+        (Some(Value::Object(first)), Some(Value::Object(second))) => assert!(first.ptr_eq(&second)),
+        other => panic!("Expected module objects, got {:?}", other),
+    }
+    
+    let _ = std::fs::remove_dir_all(dir);
+}
+
+#[test]
+fn test_file_module_errors() {
+    let dir = write_module_files("module_errors", &[
+        ("a.flow", "import \"./b.flow\"\n"),
+        ("b.flow", "import \"./a.flow\"\n"),
+        ("private.flow", "let hidden be 1\n"),
+        ("sub/bad.flow", "# fails when called\nexport def boom with x do\n    return x / 0\nend\n"),
+        ("sub/broken.flow", "let x be\n"),
+    ]);
+    
+    let run = |source: &str| {
+        let tokens = Lexer::new(source).tokenize().expect("Lexing failed");
+        let ast = Parser::new(tokens).parse().expect("Parsing failed");
+        let mut interpreter = Interpreter::new();
+        interpreter.set_script_path(dir.join("main.flow"));
+        interpreter.execute(&ast).unwrap_err().to_string()
+    };
+    
+    assert!(run("import \"./a.flow\"").contains("Circular import: a.flow -> b.flow -> a.flow"));
+    assert!(run("import { hidden } from \"./private.flow\"").contains("'hidden' is not exported"));
+    assert!(run("import \"./missing.flow\"").contains("Module './missing.flow' not found"));
+    
+    // Errors raised inside a module point at the module's source, not the import
+    let locate = |source: &str| {
+        let tokens = Lexer::new(source).tokenize().expect("Lexing failed");
+        let ast = Parser::new(tokens).parse().expect("Parsing failed");
+        let mut interpreter = Interpreter::new();
+        interpreter.set_script_path(dir.join("main.flow"));
+        let error = interpreter.execute(&ast).unwrap_err();
+        let file = interpreter.error_file().and_then(|path| path.file_name()).map(|name| name.to_string_lossy().into_owned());
+        let span = interpreter.error_span().expect("error has a span");
+        (error, file, span.start.line, span.start.column)
+    };
+    let (error, file, line, column) = locate("import bad from \"./sub/bad.flow\"\nshow 1\nbad.boom(1)");
+    assert!(matches!(error, FlowError::DivisionByZero));
+    assert_eq!((file.as_deref(), line, column), (Some("bad.flow"), 3, 12));
+    let (error, file, line, _) = locate("show 1\nimport broken from \"./sub/broken.flow\"");
+    assert!(matches!(error, FlowError::ParserError { .. }));
+    assert_eq!((file.as_deref(), line), (Some("broken.flow"), 1));
+    let (_, file, line, _) = locate("show 1\nshow missing");
+    assert_eq!((file, line), (None, 2));
+    
+    let tokens = Lexer::new("export show 1").tokenize().expect("Lexing failed");
+    assert!(Parser::new(tokens).parse().is_err());
+    
+    let _ = std::fs::remove_dir_all(dir);
+}
+
+#[test]
+fn test_modules_found_on_search_path() {
+    let dir = write_module_files("search_path", &[
+        ("project/main.flow", ""),
+        ("project/shared.flow", "export let origin be \"project\"\n"),
+        ("vendor/shared.flow", "export let origin be \"vendor\"\n"),
+        ("vendor/strings_extra.flow", "export def shout with s do\n    return s + \"!\"\nend\n"),
+        ("flow_path/colors.flow", "export let red be \"#f00\"\n"),
+    ]);
+    
+    let source = r#"
+        import strings_extra
+        import shared { origin }
+        import colors as c
+        let loud be strings_extra.shout("hi")
+        let red be c.red
+    "#;
+    
+    let tokens = Lexer::new(source).tokenize().expect("Lexing failed");
+    let ast = Parser::new(tokens).parse().expect("Parsing failed");
+    
+    std::env::set_var("FLOW_PATH", dir.join("flow_path"));
+    let mut interpreter = Interpreter::new();
+    interpreter.set_script_path(dir.join("project/main.flow"));
+    interpreter.add_module_path(dir.join("vendor"));
+    interpreter.add_module_paths_from_env();
+    std::env::remove_var("FLOW_PATH");
+    interpreter.execute(&ast).expect("Execution failed");
+    
+    let env = interpreter.get_environment_mut();
+    assert_eq!(env.get_variable("loud"), Some(Value::String("hi!".to_string())));
+    // The project directory is searched before library directories
+    assert_eq!(env.get_variable("origin"), Some(Value::String("project".to_string())));
+    assert_eq!(env.get_variable("red"), Some(Value::String("#f00".to_string())));
+    
+    // A missing module names every directory that was searched
+    let tokens = Lexer::new("import nowhere").tokenize().expect("Lexing failed");
+    let ast = Parser::new(tokens).parse().expect("Parsing failed");
+    let message = interpreter.execute(&ast).unwrap_err().to_string();
+    for searched in interpreter.module_search_path() {
+        assert!(message.contains(&searched.display().to_string()), "{}", message);
+    }
+    assert_eq!(interpreter.module_search_path().len(), 3);
+    
+    let _ = std::fs::remove_dir_all(dir);
+}
+
+#[test]
+fn test_throw_catch_and_finally() {
+    let source = r#"
+        let log be []
+        
+        def check with n do
+            if n < 0 then
+                throw {kind: "ValidationError", message: "negative"}
+            end
+            return n
+        end
+        
+        try
+            check(-1)
+        catch err
+            log.push(err.kind + ": " + err.message)
+            log.push(err.stack[0])
+        end
+        
+        try
+            let broken be 1 / 0
+        catch err
+            log.push(err.kind)
+        finally
+            log.push("cleanup")
+        end
+        
+        def rethrows do
+            try
+                throw "first"
+            catch err
+                throw
+            finally
+                log.push("inner finally")
+            end
+        end
+        
+        try
+            rethrows()
+        catch err
+            log.push(err.kind + ": " + err.message)
+        end
+        
+        def returns_early do
+            try
+                return "early"
+            catch err
+                return "caught"
+            finally
+                log.push("finally after return")
+            end
+        end
+        let early be returns_early()
+    "#;
+    
+    let mut lexer = Lexer::new(source);
+    let tokens = lexer.tokenize().expect("Lexing failed");
+    
+    let mut parser = Parser::new(tokens);
+    let ast = parser.parse().expect("Parsing failed");
+    
+    let mut interpreter = Interpreter::new();
+    interpreter.execute(&ast).expect("Execution failed");
+    
+    let env = interpreter.get_environment_mut();
+    assert_eq!(
+        env.get_variable("log").map(|log| log.to_string()),
+        Some("[ValidationError: negative, check, DivisionByZero, cleanup, inner finally, Error: first, finally after return]".to_string())
+    );
+    assert_eq!(env.get_variable("early"), Some(Value::String("early".to_string())));
+    
+    // Uncaught throws escape with their kind and message, in both engines
+    let tokens = Lexer::new("throw {kind: \"Fatal\", message: \"stop\"}").tokenize().expect("Lexing failed");
+    let ast = Parser::new(tokens).parse().expect("Parsing failed");
+    let error = Interpreter::new().execute(&ast).unwrap_err();
+    assert_eq!((error.kind(), error.message()), ("Fatal".to_string(), "stop".to_string()));
+    
+    let chunk = compile_program(&ast.statements).expect("Compilation failed");
+    let mut vm = VirtualMachine::new();
+    vm.load_chunk(chunk);
+    let error = vm.run().unwrap_err();
+    assert_eq!(error.to_string(), "Uncaught Fatal: stop");
+    
+    // A bare `throw` only makes sense inside `catch`
+    let tokens = Lexer::new("throw").tokenize().expect("Lexing failed");
+    assert!(Parser::new(tokens).parse().is_err());
+}
+
+#[test]
+fn test_try_finally_without_catch() {
+    let source = r#"
+        let log be ""
+        def cleanup_only do
+            try
+                let broken be 1 / 0
+            finally
+                log = log + "cleanup"
+            end
+            log = log + ", unreachable"
+        end
+        
+        try
+            cleanup_only()
+        catch err
+            log = log + ", " + err.kind
+        end
+        
+        def early do
+            try
+                return "returned"
+            finally
+                log = log + ", finally"
+            end
+        end
+        let result be early()
+    "#;
+    
+    let mut lexer = Lexer::new(source);
+    let tokens = lexer.tokenize().expect("Lexing failed");
+    
+    let mut parser = Parser::new(tokens);
+    let ast = parser.parse().expect("Parsing failed");
+    
+    let mut interpreter = Interpreter::new();
+    interpreter.execute(&ast).expect("Execution failed");
+    let env = interpreter.get_environment_mut();
+    assert_eq!(env.get_variable("log"), Some(Value::String("cleanup, DivisionByZero, finally".to_string())));
+    assert_eq!(env.get_variable("result"), Some(Value::String("returned".to_string())));
+    
+    let chunk = compile_program(&ast.statements).expect("Compilation failed");
+    let mut vm = VirtualMachine::new();
+    vm.load_chunk(chunk);
+    vm.run().expect("Execution failed");
+    assert_eq!(vm.globals.get("log"), Some(&Value::String("cleanup, DivisionByZero, finally".to_string())));
+    assert_eq!(vm.globals.get("result"), Some(&Value::String("returned".to_string())));
+    
+    // One of the two is still required
+    let tokens = Lexer::new("try\n    show 1\nend\n").tokenize().expect("Lexing failed");
+    let error = Parser::new(tokens).parse().unwrap_err();
+    assert!(error.to_string().contains("Expected 'catch' or 'finally'"), "unexpected error: {}", error);
+}
+
+#[test]
+fn test_try_catch_in_vm() {
+    let source = r#"
+        def check with n do
+            if n < 0 then
+                throw {kind: "ValidationError", message: "negative"}
+            end
+            return n
+        end
+        
+        def guarded with n do
+            try
+                return check(n)
+            catch err
+                return err.kind + ": " + err.message
+            end
+        end
+        
+        let first be guarded(-1)
+        let second be guarded(4)
+        
+        let log be ""
+        try
+            let broken be 1 / 0
+        catch err
+            log = log + err.kind
+        finally
+            log = log + ", cleanup"
+        end
+        
+        def rethrows do
+            try
+                throw "first"
+            catch err
+                throw
+            finally
+                log = log + ", inner finally"
+            end
+        end
+        
+        try
+            rethrows()
+        catch err
+            log = log + ", " + err.message
+        end
+        
+        let count be 0
+        for i in 1 to 3 do
+            try
+                if i == 2 then
+                    continue
+                end
+                count += 10
+            catch err
+                count = -1
+            finally
+                count += 1
+            end
+        end
+    "#;
+    
+    let mut lexer = Lexer::new(source);
+    let tokens = lexer.tokenize().expect("Lexing failed");
+    
+    let mut parser = Parser::new(tokens);
+    let ast = parser.parse().expect("Parsing failed");
+    
+    let chunk = compile_program(&ast.statements).expect("Compilation failed");
+    let mut vm = VirtualMachine::new();
+    vm.load_chunk(chunk);
+    vm.run().expect("Execution failed");
+    
+    assert_eq!(vm.globals.get("first"), Some(&Value::String("ValidationError: negative".to_string())));
+    assert_eq!(vm.globals.get("second"), Some(&Value::Integer(4)));
+    assert_eq!(
+        vm.globals.get("log"),
+        Some(&Value::String("DivisionByZero, cleanup, inner finally, first".to_string()))
+    );
+    assert_eq!(vm.globals.get("count"), Some(&Value::Integer(23)));
+}
+
+#[test]
+fn test_stack_traces() {
+    let source = "def divide with a, b do
+    return a / b
+end
+
+def average with total do
+    return divide(total, 0)
+end
+
+let result be average(10)
+";
+    
+    let mut lexer = Lexer::new(source);
+    let tokens = lexer.tokenize().expect("Lexing failed");
+    
+    let mut parser = Parser::new(tokens);
+    let ast = parser.parse().expect("Parsing failed");
+    
+    let mut interpreter = Interpreter::new();
+    assert!(matches!(interpreter.execute(&ast), Err(FlowError::DivisionByZero)));
+    let trace: Vec<(String, usize, Option<usize>)> = interpreter.stack_trace()
+        .iter()
+        .map(|frame| (frame.function.clone(), frame.line, frame.column))
+        .collect();
+    assert_eq!(trace, vec![
+        ("divide".to_string(), 6, Some(12)),
+        ("average".to_string(), 9, Some(15)),
+    ]);
+    
+    // The VM knows the same calls and lines, but not columns
+    let chunk = compile_program(&ast.statements).expect("Compilation failed");
+    let mut vm = VirtualMachine::new();
+    vm.load_chunk(chunk);
+    assert!(matches!(vm.run(), Err(FlowError::DivisionByZero)));
+    let trace: Vec<(String, usize)> = vm.stack_trace()
+        .iter()
+        .map(|frame| (frame.function.clone(), frame.line))
+        .collect();
+    assert_eq!(trace, vec![("divide".to_string(), 6), ("average".to_string(), 9)]);
+}
+
+#[test]
+fn test_source_spans() {
+    let source = "let name be \"héllo\"
+show name + 1 / count
+";
+    
+    let mut lexer = Lexer::new(source);
+    let tokens = lexer.tokenize().expect("Lexing failed");
+    
+    let mut parser = Parser::new(tokens);
+    let ast = parser.parse().expect("Parsing failed");
+    
+    // Statements end before their newline; offsets count bytes, columns characters
+    let declaration = &ast.statements[0];
+    assert_eq!(declaration.span.start, Position { line: 1, column: 1, offset: 0 });
+    assert_eq!(declaration.span.end, Position { line: 1, column: 20, offset: 20 });
+    
+    let show = &ast.statements[1];
+    assert_eq!(show.span.start, Position { line: 2, column: 1, offset: 21 });
+    assert_eq!(show.span.end, Position { line: 2, column: 22, offset: 42 });
+    let (left, right) = match &show.kind {
+        StatementKind::Show(Expression { kind: ExpressionKind::Binary { left, right, .. }, span }) => {
+            assert_eq!((span.start.column, span.end.column), (6, 22));
+            (left, right)
+        }
+        other => panic!("Expected a show of a binary expression, got {:?}", other),
+    };
+    assert_eq!((left.span.start.column, left.span.end.column), (6, 10));
+    assert_eq!((right.span.start.column, right.span.end.column), (13, 22));
+    
+    // Spans are part of the serialized tree
+    let json = serde_json::to_value(&ast).expect("Serializing failed");
+    assert_eq!(json["statements"][1]["span"]["start"]["offset"], 21);
+    let restored: Program = serde_json::from_value(json).expect("Deserializing failed");
+    assert_eq!(restored, ast);
+    
+    // Errors are reported at the innermost expression they were raised in
+    let mut interpreter = Interpreter::new();
+    assert!(matches!(interpreter.execute(&ast), Err(FlowError::UndefinedVariable { .. })));
+    let span = interpreter.error_span().expect("No error span");
+    assert_eq!((span.start.line, span.start.column, span.end.column), (2, 17, 22));
+    
+    let chunk = compile_program(&ast.statements).expect("Compilation failed");
+    let mut vm = VirtualMachine::new();
+    vm.load_chunk(chunk);
+    assert!(vm.run().is_err());
+    assert_eq!(vm.error_line(), Some(2));
+}
+
+#[test]
+fn test_parser_recovers_after_errors() {
+    let source = "let x be
+show 1
+
+def check with a do
+    if a
+        show \"missing then\"
+    end
+    show a +
+    return a
+end
+
+let y be 1 2
+end
+show \"done\"
+";
+    
+    let mut lexer = Lexer::new(source);
+    let tokens = lexer.tokenize().expect("Lexing failed");
+    
+    let mut parser = Parser::new(tokens);
+    let (program, errors) = parser.parse_recovering();
+    let lines: Vec<usize> = errors.iter()
+        .map(|error| match error {
+            FlowError::ParserError { line, .. } => *line,
+            other => panic!("Expected ParserError, got {:?}", other),
+        })
+        .collect();
+    // A missing value is reported at the end of its line. The bad `if` is
+    // skipped through its own `end`, which doesn't close `check`.
+    assert_eq!(lines, vec![1, 5, 8, 12, 13]);
+    
+    // Statements without errors are kept, including those in blocks with errors
+    let kept: Vec<usize> = program.statements.iter().map(|statement| statement.span.start.line).collect();
+    assert_eq!(kept, vec![2, 4, 14]);
+    match &program.statements[1].kind {
+        StatementKind::FunctionDeclaration { body, .. } => {
+            assert!(matches!(body.as_slice(), [Statement { kind: StatementKind::Return(_), .. }]));
+        }
+        other => panic!("Expected a function declaration, got {:?}", other),
+    }
+    
+    // `parse` still stops at the first error
+    let mut parser = Parser::new(Lexer::new(source).tokenize().expect("Lexing failed"));
+    assert!(matches!(parser.parse(), Err(FlowError::ParserError { line: 1, .. })));
+}
+
+#[test]
+fn test_diagnostics() {
+    let source = "let count be 2\nif count > 1\n    show count\nend\n";
+    let tokens = Lexer::new(source).tokenize().expect("Lexing failed");
+    let error = Parser::new(tokens).parse().expect_err("Expected a parser error");
+    assert_eq!(error.code(), "E0002");
+    assert!(error.hint().expect("No hint").contains("then"));
+    
+    let diagnostic = Diagnostic::from_error(&error).in_file("main.flow");
+    assert_eq!((diagnostic.line, diagnostic.column), (2, 13));
+    let rendered = diagnostic.render(source);
+    assert!(rendered.starts_with("error[E0002]: Expected 'then' after if condition\n"));
+    assert!(rendered.contains(" --> main.flow:2:13\n"));
+    assert!(rendered.contains("2 | if count > 1\n  |             ^\n"));
+    assert!(rendered.contains("= help: an `if` condition is followed by `then`"));
+    
+    // Misspelled keywords
+    let tokens = Lexer::new("whlie count do\nend\n").tokenize().expect("Lexing failed");
+    let (_, errors) = Parser::new(tokens).parse_recovering();
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].hint(), Some("did you mean `while`?"));
+    
+    // Unknown names close to a defined one, underlined across the whole name
+    let source = "let total be 10\nshow totl + 1\n";
+    let ast = Parser::new(Lexer::new(source).tokenize().expect("Lexing failed"))
+        .parse()
+        .expect("Parsing failed");
+    let mut interpreter = Interpreter::new();
+    let error = interpreter.execute(&ast).expect_err("Expected an undefined variable");
+    assert_eq!(error.code(), "E0005");
+    assert_eq!(interpreter.error_hint(), Some("did you mean `total`?"));
+    let diagnostic = Diagnostic::from_error(&error)
+        .at_span(interpreter.error_span().expect("No error span"))
+        .with_hint(interpreter.error_hint().unwrap());
+    assert!(diagnostic.render(source).contains("2 | show totl + 1\n  |      ^^^^\n"));
+    
+    let json: serde_json::Value = serde_json::from_str(&diagnostic.to_json()).expect("Invalid JSON");
+    assert_eq!(json["code"], "E0005");
+    assert_eq!((json["line"].as_u64(), json["column"].as_u64(), json["end_column"].as_u64()), (Some(2), Some(6), Some(10)));
+    assert_eq!(json["hints"][0], "did you mean `total`?");
+    
+    assert_eq!(closest_match("lenght", ["length", "len", "keys"]), Some("length"));
+    assert_eq!(closest_match("xyz", ["length", "len", "keys"]), None);
+}
+
+#[test]
+fn test_error_code_catalog() {
+    let errors = [
+        FlowError::lexer_error(1, 1, "Unexpected character"),
+        FlowError::parser_error("Expected expression"),
+        FlowError::runtime_error("Something failed"),
+        FlowError::type_error("Cannot negate string"),
+        FlowError::undefined_variable("x"),
+        FlowError::undefined_function("f"),
+        FlowError::invalid_operation("Array has no method 'add'"),
+        FlowError::DivisionByZero,
+        FlowError::IndexOutOfBounds { index: 3 },
+        FlowError::IoError { message: "Failed to read file".to_string() },
+        FlowError::thrown(Value::String("boom".to_string()), &[]),
+        FlowError::argument_count_error("Expected at most 2 arguments but got 3"),
+    ];
+    let codes: Vec<&str> = errors.iter().map(FlowError::code).collect();
+    let catalog: Vec<&str> = EXPLANATIONS.iter().map(|entry| entry.code).collect();
+    assert_eq!(codes, catalog);
+    
+    // Control-flow signals never reach the user and have no entry
+    for signal in [FlowError::Break, FlowError::Continue, FlowError::return_value(Value::Null)] {
+        assert_eq!(signal.code(), "E0000");
+    }
+    assert!(explain("E0000").is_none());
+    
+    assert_eq!(explain("E0008").map(|entry| entry.code), Some("E0008"));
+    assert_eq!(explain("e0008").map(|entry| entry.code), Some("E0008"));
+    assert_eq!(explain("8").map(|entry| entry.code), Some("E0008"));
+    assert!(explain("E0999").is_none());
+    assert!(explain("oops").is_none());
+    
+    // The causes E0001 lists are all caught by the lexer
+    for example in ["\"Ada", "@", "$", "0x", "0b102"] {
+        let source = format!("let name be {}", example);
+        let error = Lexer::new(&source).tokenize().expect_err(example);
+        assert_eq!(error.code(), "E0001", "{} raised {}", example, error);
+    }
+    
+    let text = explain("E0009").unwrap().to_string();
+    assert!(text.starts_with("E0009: Index out of bounds\n"));
+    assert!(text.contains("Erroneous code example:\n\n    let items be [1, 2, 3]\n    show items[3]\n"));
+    
+    let run = |source: &str| -> Result<(), FlowError> {
+        let tokens = Lexer::new(source).tokenize()?;
+        let ast = Parser::new(tokens).parse()?;
+        Interpreter::new().execute(&ast)
+    };
+    for entry in EXPLANATIONS {
+        if let Err(err) = run(entry.right) {
+            panic!("Corrected example for {} failed: {}", entry.code, err);
+        }
+        match run(entry.wrong) {
+            Err(err) => assert_eq!(err.code(), entry.code, "Example for {} raised {}", entry.code, err),
+            Ok(()) => panic!("Example for {} ran without an error", entry.code),
+        }
+    }
+}
+
+#[test]
+fn test_deep_equality_and_ordering() {
+    let source = r#"
+        let same_arrays be [1, [2, "x"]] == [1, [2.0, "x"]]
+        let different_arrays be [1, 2] == [1, 2, 3]
+        let same_objects be {a: 1, b: [true]} == {b: [true], a: 1.0}
+        let different_objects be {a: 1} != {a: 1, b: 2}
+        let big_equals_float be 9223372036854775807 + 1 == 9223372036854775808.0
+        let big_greater be 9223372036854775807 + 1 > 9223372036854775807
+        let strings_ordered be "apple" < "banana" and "b" >= "apple"
+        let arrays_ordered be [1, 2] < [1, 3] and [1, 2] < [1, 2, 0]
+    "#;
+    let expected = [
+        ("same_arrays", true),
+        ("different_arrays", false),
+        ("same_objects", true),
+        ("different_objects", true),
+        ("big_equals_float", true),
+        ("big_greater", true),
+        ("strings_ordered", true),
+        ("arrays_ordered", true),
+    ];
+    
+    let ast = Parser::new(Lexer::new(source).tokenize().expect("Lexing failed"))
+        .parse()
+        .expect("Parsing failed");
+    
+    let mut interpreter = Interpreter::new();
+    interpreter.execute(&ast).expect("Execution failed");
+    let env = interpreter.get_environment_mut();
+    for (name, value) in expected {
+        assert_eq!(env.get_variable(name), Some(Value::Boolean(value)), "{} in the interpreter", name);
+    }
+    
+    let chunk = compile_program(&ast.statements).expect("Compilation failed");
+    let mut vm = VirtualMachine::new();
+    vm.load_chunk(chunk);
+    vm.run().expect("Execution failed");
+    for (name, value) in expected {
+        assert_eq!(vm.globals.get(name), Some(&Value::Boolean(value)), "{} in the VM", name);
+    }
+    
+    // Sorting orders mixed kinds: null, booleans, numbers, strings, arrays, objects
+    let ast = Parser::new(Lexer::new(r#"let sorted be [3, "b", {}, null, 1.5, [1], true, "a", 2].sort()"#)
+        .tokenize().expect("Lexing failed"))
+        .parse()
+        .expect("Parsing failed");
+    let mut interpreter = Interpreter::new();
+    interpreter.execute(&ast).expect("Execution failed");
+    let sorted = interpreter.get_environment_mut().get_variable("sorted").unwrap();
+    assert_eq!(sorted.to_string(), "[null, true, 1.5, 2, 3, a, b, [1], {}]");
+    
+    // Comparison operators still reject values of different kinds
+    let ast = Parser::new(Lexer::new("let bad be 1 < \"a\"").tokenize().expect("Lexing failed"))
+        .parse()
+        .expect("Parsing failed");
+    assert!(matches!(Interpreter::new().execute(&ast), Err(FlowError::TypeError { .. })));
+    
+    assert!(Value::Float(f64::NAN).equals(&Value::Float(f64::NAN)));
+    assert_eq!(Value::Float(f64::NAN).compare(&Value::Integer(i64::MAX)), std::cmp::Ordering::Greater);
+    assert_eq!(Value::Integer(i64::MAX).compare(&Value::Float(9223372036854775807.0)), std::cmp::Ordering::Less);
+}
+
+#[test]
+fn test_equality_and_ordering_of_cyclic_values() {
+    let source = r#"
+        let a be [1, null]
+        a[1] = a
+        let b be [1, null]
+        b[1] = b
+        let c be [2, null]
+        c[1] = c
+        let x be {name: "x"}
+        x.next = x
+        let y be {name: "x"}
+        y.next = y
+        
+        let same_cycles be a == b
+        let different_cycles be a != c
+        let cycles_ordered be a < c and not (b < a)
+        let same_object_cycles be x == y
+    "#;
+    let expected = ["same_cycles", "different_cycles", "cycles_ordered", "same_object_cycles"];
+    
+    let ast = Parser::new(Lexer::new(source).tokenize().expect("Lexing failed"))
+        .parse()
+        .expect("Parsing failed");
+    
+    let mut interpreter = Interpreter::new();
+    interpreter.execute(&ast).expect("Execution failed");
+    let env = interpreter.get_environment_mut();
+    for name in expected {
+        assert_eq!(env.get_variable(name), Some(Value::Boolean(true)), "{} in the interpreter", name);
+    }
+    
+    let chunk = compile_program(&ast.statements).expect("Compilation failed");
+    let mut vm = VirtualMachine::new();
+    vm.load_chunk(chunk);
+    vm.run().expect("Execution failed");
+    for name in expected {
+        assert_eq!(vm.globals.get(name), Some(&Value::Boolean(true)), "{} in the VM", name);
+    }
+}
+
+#[test]
+fn test_extended_operators() {
+    let source = r#"
+        def fail do
+            throw "evaluated"
+        end
+        let power be 2 ** 10
+        let big_power be 2 ** 64
+        let negated_power be -2 ** 2
+        let right_assoc be 2 ** 3 ** 2
+        let float_power be 4 ** 0.5
+        let floored be -7 // 2
+        let floored_float be 7.5 // 2
+        let remainder be -7 % 2
+        let negative_divisor be 7 % -2
+        let both_negative be -7 % -2
+        let big_remainder be (0 - 2 ** 100) % 7
+        let remainder_of_big be (0 - 2 ** 100 - 1) % (2 ** 70)
+        let anded be 6 & 3
+        let ored be 6 | 3
+        let xored be 6 ^ 3
+        let shifted be 1 << 70
+        let shifted_right be -8 >> 1
+        let inverted be ~5
+        let in_array be 2 in [1, 2]
+        let in_object be "a" in {a: 1}
+        let in_string be "ell" in "hello"
+        let not_in be not (3 in [1, 2])
+        let defaulted be null ?? 5
+        let kept be 0 ?? 5
+        let short_circuit be 1 ?? fail()
+        let precedence be 1 + 2 << 1
+        let mixed be 1 | 2 == 3
+    "#;
+    let expected = [
+        ("power", "1024"),
+        ("big_power", "18446744073709551616"),
+        ("negated_power", "-4"),
+        ("right_assoc", "512"),
+        ("float_power", "2"),
+        ("floored", "-4"),
+        ("floored_float", "3"),
+        ("remainder", "1"),
+        ("negative_divisor", "-1"),
+        ("both_negative", "-1"),
+        ("big_remainder", "5"),
+        ("remainder_of_big", "1180591620717411303423"),
+        ("anded", "2"),
+        ("ored", "7"),
+        ("xored", "5"),
+        ("shifted", "1180591620717411303424"),
+        ("shifted_right", "-4"),
+        ("inverted", "-6"),
+        ("in_array", "true"),
+        ("in_object", "true"),
+        ("in_string", "true"),
+        ("not_in", "true"),
+        ("defaulted", "5"),
+        ("kept", "0"),
+        ("short_circuit", "1"),
+        ("precedence", "6"),
+        ("mixed", "true"),
+    ];
+    
+    let ast = Parser::new(Lexer::new(source).tokenize().expect("Lexing failed"))
+        .parse()
+        .expect("Parsing failed");
+    
+    let mut interpreter = Interpreter::new();
+    interpreter.execute(&ast).expect("Execution failed");
+    let env = interpreter.get_environment_mut();
+    for (name, value) in expected {
+        assert_eq!(env.get_variable(name).map(|v| v.to_string()), Some(value.to_string()), "{} in the interpreter", name);
+    }
+    
+    let chunk = compile_program(&ast.statements).expect("Compilation failed");
+    let mut vm = VirtualMachine::new();
+    vm.load_chunk(chunk);
+    vm.run().expect("Execution failed");
+    for (name, value) in expected {
+        assert_eq!(vm.globals.get(name).map(|v| v.to_string()), Some(value.to_string()), "{} in the VM", name);
+    }
+    
+    let ast = Parser::new(Lexer::new("let bad be 1 // 0").tokenize().expect("Lexing failed"))
+        .parse()
+        .expect("Parsing failed");
+    assert!(matches!(Interpreter::new().execute(&ast), Err(FlowError::DivisionByZero)));
+    let ast = Parser::new(Lexer::new("let bad be (2 ** 100) % 0").tokenize().expect("Lexing failed"))
+        .parse()
+        .expect("Parsing failed");
+    assert!(matches!(Interpreter::new().execute(&ast), Err(FlowError::DivisionByZero)));
+    let ast = Parser::new(Lexer::new("let bad be 1.5 & 1").tokenize().expect("Lexing failed"))
+        .parse()
+        .expect("Parsing failed");
+    assert!(matches!(Interpreter::new().execute(&ast), Err(FlowError::TypeError { .. })));
+}
+
+#[test]
+fn test_conditional_expressions_and_optional_chaining() {
+    let source = r#"
+        def fail do
+            throw "evaluated"
+        end
+        let n be 5
+        let prefix_form be if n > 3 then "big" else "small"
+        let suffix_form be "negative" if n < 0 else "zero" if n == 0 else "positive"
+        let chained be if n > 10 then 1 else if n > 4 then 2 else 3
+        let lazy be 1 if true else fail()
+        let config be {server: null, items: [10, 20]}
+        let missing_port be config?.server?.port
+        let defaulted_port be config.missing?.port ?? 8080
+        let present be config?.items?[1]
+        let nothing be null
+        let skipped_index be nothing?[fail()]
+        let skipped_call be nothing?.push(fail())
+        let whole_chain be nothing?.x.y
+        let whole_chain_with_index be config.server?.hosts[fail()].name
+        let whole_chain_with_call be nothing?.items.push(fail())
+        let continued be config?.items[0]
+    "#;
+    let expected = [
+        ("prefix_form", Value::String("big".to_string())),
+        ("suffix_form", Value::String("positive".to_string())),
+        ("chained", Value::Integer(2)),
+        ("lazy", Value::Integer(1)),
+        ("missing_port", Value::Null),
+        ("defaulted_port", Value::Integer(8080)),
+        ("present", Value::Integer(20)),
+        ("skipped_index", Value::Null),
+        ("skipped_call", Value::Null),
+        ("whole_chain", Value::Null),
+        ("whole_chain_with_index", Value::Null),
+        ("whole_chain_with_call", Value::Null),
+        ("continued", Value::Integer(10)),
+    ];
+    
+    let ast = Parser::new(Lexer::new(source).tokenize().expect("Lexing failed"))
+        .parse()
+        .expect("Parsing failed");
+    
+    let mut interpreter = Interpreter::new();
+    interpreter.execute(&ast).expect("Execution failed");
+    let env = interpreter.get_environment_mut();
+    for (name, value) in &expected {
+        assert_eq!(env.get_variable(name).as_ref(), Some(value), "{} in the interpreter", name);
+    }
+    
+    let chunk = compile_program(&ast.statements).expect("Compilation failed");
+    let mut vm = VirtualMachine::new();
+    vm.load_chunk(chunk);
+    vm.run().expect("Execution failed");
+    for (name, value) in &expected {
+        assert_eq!(vm.globals.get(*name), Some(value), "{} in the VM", name);
+    }
+    
+    // Plain access on null still fails, and an optional chain can't be assigned to
+    let ast = Parser::new(Lexer::new("let config be {}\nlet port be config.server.port").tokenize().expect("Lexing failed"))
+        .parse()
+        .expect("Parsing failed");
+    assert!(Interpreter::new().execute(&ast).is_err());
+    let tokens = Lexer::new("let config be {}\nconfig?.port = 1").tokenize().expect("Lexing failed");
+    assert!(Parser::new(tokens).parse().is_err());
+    let tokens = Lexer::new("let size be 1 if true").tokenize().expect("Lexing failed");
+    assert!(Parser::new(tokens).parse().is_err());
+}
+
+#[test]
+fn test_pipe_operator() {
+    let source = r#"
+        def double with n do
+            return n * 2
+        end
+        def subtract with a, b do
+            return a - b
+        end
+        let triple be (x) => x * 3
+        let piped_def be 5 |> double
+        let with_arguments be 10 |> subtract(4)
+        let chained be 5 |> double |> subtract(1) |> triple
+        let lambda_call be 2 |> triple()
+        let lower_precedence be 1 + 2 |> double
+        let compared be 3 |> double > 5
+    "#;
+    let expected = [
+        ("piped_def", 10),
+        ("with_arguments", 6),
+        ("chained", 27),
+        ("lambda_call", 6),
+        ("lower_precedence", 6),
+    ];
+    
+    let ast = Parser::new(Lexer::new(source).tokenize().expect("Lexing failed"))
+        .parse()
+        .expect("Parsing failed");
+    
+    let mut interpreter = Interpreter::new();
+    interpreter.execute(&ast).expect("Execution failed");
+    let env = interpreter.get_environment_mut();
+    for (name, value) in expected {
+        assert_eq!(env.get_variable(name), Some(Value::Integer(value)), "{} in the interpreter", name);
+    }
+    assert_eq!(env.get_variable("compared"), Some(Value::Boolean(true)));
+    
+    let chunk = compile_program(&ast.statements).expect("Compilation failed");
+    let mut vm = VirtualMachine::new();
+    vm.load_chunk(chunk);
+    vm.run().expect("Execution failed");
+    for (name, value) in expected {
+        assert_eq!(vm.globals.get(name), Some(&Value::Integer(value)), "{} in the VM", name);
+    }
+    assert_eq!(vm.globals.get("compared"), Some(&Value::Boolean(true)));
+    
+    // Stdlib functions and methods of the piped value
+    let source = r#"
+        let shout be "flow" |> str_upper
+        let evens be [1, 2, 3, 4] |> .filter((n) => n % 2 == 0) |> .map((n) => n * 10)
+        let letters be ["b", "a"]
+        let joined be letters |> .sort() |> str_join("-")
+        let sorted_in_place be letters
+        let greeter be {greet: (name) => "hi " + name}
+        let greeting be "Ada" |> greeter.greet()
+    "#;
+    let ast = Parser::new(Lexer::new(source).tokenize().expect("Lexing failed"))
+        .parse()
+        .expect("Parsing failed");
+    let mut interpreter = Interpreter::new();
+    interpreter.execute(&ast).expect("Execution failed");
+    let env = interpreter.get_environment_mut();
+    assert_eq!(env.get_variable("shout"), Some(Value::String("FLOW".to_string())));
+    assert_eq!(env.get_variable("evens").map(|v| v.to_string()), Some("[20, 40]".to_string()));
+    assert_eq!(env.get_variable("joined"), Some(Value::String("a-b".to_string())));
+    assert_eq!(env.get_variable("sorted_in_place").map(|v| v.to_string()), Some("[a, b]".to_string()));
+    assert_eq!(env.get_variable("greeting"), Some(Value::String("hi Ada".to_string())));
+    
+    let chunk = compile_program(&ast.statements).expect("Compilation failed");
+    let mut vm = VirtualMachine::new();
+    vm.load_chunk(chunk);
+    vm.run().expect("Execution failed");
+    assert_eq!(vm.globals.get("shout"), Some(&Value::String("FLOW".to_string())));
+    assert_eq!(vm.globals.get("evens").map(|v| v.to_string()), Some("[20, 40]".to_string()));
+    assert_eq!(vm.globals.get("joined"), Some(&Value::String("a-b".to_string())));
+    assert_eq!(vm.globals.get("sorted_in_place").map(|v| v.to_string()), Some("[a, b]".to_string()));
+    assert_eq!(vm.globals.get("greeting"), Some(&Value::String("hi Ada".to_string())));
+    
+    // The right-hand side has to be something that can be called
+    let tokens = Lexer::new("let bad be 1 |> 2").tokenize().expect("Lexing failed");
+    assert!(Parser::new(tokens).parse().is_err());
+}
+
+#[test]
+fn test_destructuring() {
+    let source = r#"
+        let [first, second, ...rest] be [1, 2, 3, 4]
+        let {status, body: text} be {status: 200, body: "ok", headers: {}}
+        let {data: {id, tags: [tag] = ["none"]}, page = 1} be {data: {id: 7}, page: null}
+        let [a, b = a * 10] be [5]
+        def area with {width, height = 1} do
+            return width * height
+        end
+        let full be area({width: 3, height: 4})
+        let defaulted be area({width: 3})
+        let add_pair be ([x, y]) => x + y
+        let pair_sum be add_pair([2, 3])
+    "#;
+    let expected = [
+        ("first", "1"),
+        ("second", "2"),
+        ("rest", "[3, 4]"),
+        ("status", "200"),
+        ("text", "ok"),
+        ("id", "7"),
+        ("tag", "none"),
+        ("page", "1"),
+        ("b", "50"),
+        ("full", "12"),
+        ("defaulted", "3"),
+        ("pair_sum", "5"),
+    ];
+    
+    let ast = Parser::new(Lexer::new(source).tokenize().expect("Lexing failed"))
+        .parse()
+        .expect("Parsing failed");
+    
+    let mut interpreter = Interpreter::new();
+    interpreter.execute(&ast).expect("Execution failed");
+    let env = interpreter.get_environment_mut();
+    for (name, value) in expected {
+        assert_eq!(env.get_variable(name).map(|v| v.to_string()), Some(value.to_string()), "{} in the interpreter", name);
+    }
+    
+    let chunk = compile_program(&ast.statements).expect("Compilation failed");
+    let mut vm = VirtualMachine::new();
+    vm.load_chunk(chunk);
+    vm.run().expect("Execution failed");
+    for (name, value) in expected {
+        assert_eq!(vm.globals.get(name).map(|v| v.to_string()), Some(value.to_string()), "{} in the VM", name);
+    }
+    
+    // Values that don't fit the pattern are errors in both engines
+    for (source, message) in [
+        ("let [a, b] be [1]", "needs at least 2"),
+        ("let [a] be [1, 2]", "add `...rest`"),
+        ("let {a} be {b: 1}", "missing key 'a'"),
+        ("let [a] be {a: 1}", "Cannot destructure object with an array pattern"),
+        ("def f with {a} do\n    return a\nend\nf(5)", "Cannot destructure integer with an object pattern"),
+    ] {
+        let ast = Parser::new(Lexer::new(source).tokenize().expect("Lexing failed"))
+            .parse()
+            .expect("Parsing failed");
+        let error = Interpreter::new().execute(&ast).expect_err(source);
+        assert!(error.to_string().contains(message), "{}: {}", source, error);
+        
+        let chunk = compile_program(&ast.statements).expect("Compilation failed");
+        let mut vm = VirtualMachine::new();
+        vm.load_chunk(chunk);
+        let error = vm.run().expect_err(source);
+        assert!(error.to_string().contains(message), "{} in the VM: {}", source, error);
+    }
+    
+    let tokens = Lexer::new("let [...rest, last] be [1, 2]").tokenize().expect("Lexing failed");
+    assert!(Parser::new(tokens).parse().is_err());
+    
+    // The unpacking instructions can be written to a bytecode file
+    let mut chunk = Chunk::new();
+    chunk.write_instruction(Instruction::UnpackArray(1, 2, true), 1);
+    chunk.write_instruction(Instruction::UnpackObject(vec![("id".to_string(), true)]), 1);
+    chunk.finalize();
+    let mut bytes = Vec::new();
+    chunk.write_to_file(&mut bytes).expect("Serialization failed");
+    assert!(bytes.ends_with(&[0x64, 1, 0, 2, 0, 1, 0x65, 1, 0, 1, 2, 0, 0, 0, b'i', b'd']), "{:?}", bytes);
+}
+
+#[test]
+fn test_spread_syntax() {
+    let source = r#"
+        let middle be [2, 3]
+        let all be [1, ...middle, 4, ...[], ...middle]
+        let base be {name: "flow", version: 1}
+        let updated be {...base, version: 2}
+        let kept be {version: 3, ...base}
+        def add3 with a, b, c do
+            return a + b + c
+        end
+        let sum be add3(1, ...middle)
+        let scale be (n, factor) => n * factor
+        let scaled be scale(...[6, 7])
+        let text be str(...[42])
+        def collect with first, ...rest do
+            return rest
+        end
+        let gathered be collect(...[1, 2, 3])
+        let gathered_mixed be collect(0, ...middle, 4)
+        let nothing_extra be collect(1)
+        let count be (...items) => items.length()
+        let counted be count(...middle, ...middle)
+        let name be updated.name
+        let version be updated.version
+        let kept_version be kept.version
+    "#;
+    let expected = [
+        ("all", "[1, 2, 3, 4, 2, 3]"),
+        ("middle", "[2, 3]"),
+        ("sum", "6"),
+        ("scaled", "42"),
+        ("text", "42"),
+        ("gathered", "[2, 3]"),
+        ("gathered_mixed", "[2, 3, 4]"),
+        ("nothing_extra", "[]"),
+        ("counted", "4"),
+        ("name", "flow"),
+        ("version", "2"),
+        ("kept_version", "1"),
+    ];
+    
+    let ast = Parser::new(Lexer::new(source).tokenize().expect("Lexing failed"))
+        .parse()
+        .expect("Parsing failed");
+    
+    let mut interpreter = Interpreter::new();
+    interpreter.execute(&ast).expect("Execution failed");
+    let env = interpreter.get_environment_mut();
+    for (name, value) in expected {
+        assert_eq!(env.get_variable(name).map(|v| v.to_string()), Some(value.to_string()), "{} in the interpreter", name);
+    }
+    
+    let chunk = compile_program(&ast.statements).expect("Compilation failed");
+    let mut vm = VirtualMachine::new();
+    vm.load_chunk(chunk);
+    vm.run().expect("Execution failed");
+    for (name, value) in expected {
+        assert_eq!(vm.globals.get(name).map(|v| v.to_string()), Some(value.to_string()), "{} in the VM", name);
+    }
+    
+    // Only arrays spread into arrays and arguments, and only objects into objects
+    for (source, message) in [
+        ("let items be [...5]", "Cannot spread integer"),
+        ("def f with a do\n    return a\nend\nf(...{a: 1})", "Cannot spread object"),
+        ("let merged be {...[1]}", "only objects can be spread into objects"),
+    ] {
+        let ast = Parser::new(Lexer::new(source).tokenize().expect("Lexing failed"))
+            .parse()
+            .expect("Parsing failed");
+        let error = Interpreter::new().execute(&ast).expect_err(source);
+        assert!(error.to_string().contains(message), "{}: {}", source, error);
+        
+        let chunk = compile_program(&ast.statements).expect("Compilation failed");
+        let mut vm = VirtualMachine::new();
+        vm.load_chunk(chunk);
+        let error = vm.run().expect_err(source);
+        assert!(error.to_string().contains(message), "{} in the VM: {}", source, error);
+    }
+    
+    // A rest parameter takes every remaining argument, so it has to come last
+    let tokens = Lexer::new("def f with ...rest, last do
+end").tokenize().expect("Lexing failed");
+    assert!(Parser::new(tokens).parse().is_err());
+    
+    // The standard library offers the same as functions
+    let source = r#"
+        let joined be array_concat([1], [2, 3], [])
+        let merged be object_merge({a: 1, b: 1}, {b: 2})
+        let b be merged.b
+        let items be [3, 1, 2]
+        let largest be max(...items)
+    "#;
+    let ast = Parser::new(Lexer::new(source).tokenize().expect("Lexing failed"))
+        .parse()
+        .expect("Parsing failed");
+    let mut interpreter = Interpreter::new();
+    interpreter.execute(&ast).expect("Execution failed");
+    let env = interpreter.get_environment_mut();
+    assert_eq!(env.get_variable("joined").map(|v| v.to_string()), Some("[1, 2, 3]".to_string()));
+    assert_eq!(env.get_variable("b").map(|v| v.to_string()), Some("2".to_string()));
+    assert_eq!(env.get_variable("largest").map(|v| v.to_string()), Some("3".to_string()));
+    
+    // The spreading instructions can be written to a bytecode file
+    let mut chunk = Chunk::new();
+    for instruction in [Instruction::CallSpread, Instruction::SpreadArguments, Instruction::ExtendArray, Instruction::ExtendObject] {
+        chunk.write_instruction(instruction, 1);
+    }
+    chunk.finalize();
+    let mut bytes = Vec::new();
+    chunk.write_to_file(&mut bytes).expect("Serialization failed");
+    assert!(bytes.ends_with(&[0x57, 0x58, 0x59, 0x5A]), "{:?}", bytes);
+}